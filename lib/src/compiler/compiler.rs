@@ -3,22 +3,86 @@ use serde::{Deserialize, Serialize};
 use serde_json::Result;
 use crate::compiler::preprocessor;
 use std::thread::sleep;
-use crate::compiler::utils::{strings, strings::HandlebarType};
+use crate::compiler::utils::{entities, i18n, namespaces, strings, strings::HandlebarType};
+use crate::compiler::utils::names::NameAllocator;
 use log::info;
 use crate::compiler::constants;
 use crate::compiler::constants::imports::Import;
 use std::collections::HashSet;
 use crate::compiler::renderer::dom_renderer::DomRenderer;
+use crate::compiler::renderer::ssr_renderer::SsrRenderer;
 use crate::compiler::renderer::renderer::Renderer;
-use html_parser::Element;
-use html_parser::{Dom, Node};
+use crate::compiler::parser::{self, Element, Node};
 use std::collections::HashMap;
 
+// whitespace inside these is kept exactly as written
+const PRESERVE_WHITESPACE_ELEMENTS: [&str; 2] = ["pre", "textarea"];
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+// elements whose text is never shown, so whitespace inside them can go
+const ELEMENTS_WITHOUT_TEXT: [&str; 6] = ["audio", "datalist", "dl", "optgroup", "select", "video"];
+// svg elements that can sit next to text that matters, ex: <tspan>a</tspan> <tspan>b</tspan>
+const SVG_TEXT_ELEMENTS: [&str; 3] = ["a", "text", "tspan"];
+
+// Joins up text that sits next to {tags} so it all becomes one node, ex: `Hello {name}!`
+// becomes a single text node with `Hello ${name}!` as its value
+fn merge_text(fragments: &mut Vec<Fragment>) {
+	let mut merged: Vec<Fragment> = vec![];
+	let mut run: Vec<Fragment> = vec![];
+	for mut fragment in std::mem::take(fragments) {
+		merge_text(&mut fragment.children);
+		merge_text(&mut fragment.else_children);
+		if fragment.ttype == FragmentType::Text && fragment.renderable {
+			run.push(fragment);
+			continue;
+		}
+		merged.extend(merge_text_run(std::mem::take(&mut run)));
+		merged.push(fragment);
+	}
+	merged.extend(merge_text_run(run));
+	*fragments = merged;
+}
+
+fn merge_text_run(mut run: Vec<Fragment>) -> Vec<Fragment> {
+	if run.len() < 2 {
+		return run;
+	}
+	let mut value = String::from("`");
+	for fragment in &run {
+		let data = fragment.data.as_deref().unwrap_or("");
+		if fragment.contains_code {
+			value.push_str(&format!("${{{}}}", data.trim()));
+		} else {
+			value.push_str(&strings::to_template_literal_text(data));
+		}
+	}
+	value.push('`');
+	run.truncate(1);
+	run[0].data = Some(value);
+	run[0].contains_code = true;
+	return run;
+}
+
+// Plain text, without any {tags} in it
+fn is_static_text(fragment: &Fragment) -> bool {
+	return fragment.ttype == FragmentType::Text && !fragment.contains_code;
+}
+
 pub fn compile(r_str: &str) -> String {
 	let mut compiler = Compiler::default();
 	return serde_json::to_string_pretty(&compiler.compile(r_str)).unwrap();
 }
 
+// Same as compile, but with options given as JSON, ex: `{"immutable": true}`
+pub fn compile_with_options(r_str: &str, options: &str) -> String {
+	let options = serde_json::from_str::<CompileOptions>(options)
+		.unwrap_or_else(|error| panic!("Invalid compile options: {}", error));
+	let mut compiler = Compiler {
+		options,
+		..Default::default()
+	};
+	return serde_json::to_string_pretty(&compiler.compile(r_str)).unwrap();
+}
+
 #[derive(Default, Debug)]
 pub struct Compiler {
 	pub has_instance: bool,
@@ -27,26 +91,45 @@ pub struct Compiler {
 	pub has_script_fragments: bool,
 	pub hacky_style_index: usize,
 	pub imports: HashMap<String, HashSet<String>>,
-	pub short_name_counts: HashMap<String, isize>,
+	pub names: NameAllocator, // hands out every name in our output
+	pub special_elements: HashSet<String>, // <svelte:window> and friends, which can only be used once
+	pub in_head: bool, // <script> and <style> are plain elements inside <svelte:head>
+	pub warnings: Vec<Warning>,
+	pub ignores: Vec<Vec<String>>, // warnings silenced by <!-- svelte-ignore --> for whatever we're converting
+	pub options: CompileOptions, // whatever we were given, plus anything set by <svelte:options>
+	pub source: String, // after preprocessing, which is what fragment positions point into
+	pub hash: String, // identifies this component's markup, ex: to find its <svelte:head> contents when hydrating
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Fragment {
 	pub short_name: String,
 	pub name: String,
-	pub data: Option<String>,
+	pub data: Option<String>, // NOTE: for blocks this is the expression, ex: the `x` in {#if x}
 	pub ttype: FragmentType,
 	pub renderable: bool,
 	pub attributes: Vec<Attribute>,
+	pub directives: Vec<Directive>,
 	pub children: Vec<Fragment>,
+	pub else_children: Vec<Fragment>, // the {:else} branch of if/each blocks
+	pub each: Option<EachBlock>,
+	pub slot: Option<String>, // the `x` in slot="x", for content passed into a component's slot
+	pub namespace: Option<String>, // for elements in <svg> and the like, ex: http://www.w3.org/2000/svg
 	pub has_parent: bool,
 	pub contains_code: bool, // NOTE: only makes sense for Text nodes
+	pub start: usize, // where this starts in the source, for add_location in dev mode
 }
 
 impl Fragment {
     pub fn into_iter<'a>(&'a self) -> FragmentIterator<'a> {
         FragmentIterator { stack: vec![&self] }
     }
+
+	pub fn is_whitespace(&self) -> bool {
+		return self.ttype == FragmentType::Text
+			&& !self.contains_code
+			&& self.data.as_deref().unwrap_or("").chars().all(strings::is_html_whitespace);
+	}
 }
 
 pub struct FragmentIterator<'a> {
@@ -69,26 +152,76 @@ impl<'a> Iterator for FragmentIterator<'a> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Attribute {
 	pub key: String,
 	pub value: Option<String>
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum DirectiveType {
+	Transition, // transition:fade
+	In, // in:fly
+	Out, // out:fade
+	Animate, // animate:flip
+	Let, // let:item={alias}
+	On, // on:click|once={handler}
+	Binding, // bind:innerWidth={width}
+}
+
+#[derive(Clone, Debug)]
+pub struct Directive {
+	pub ttype: DirectiveType,
+	pub name: String,
+	pub modifiers: Vec<String>,
+	pub value: Option<String>,
+	pub handler: Option<String>, // for event handlers that get hoisted into the instance, ex: on:click={() => x++}
+}
+
+impl Directive {
+	pub fn has_modifier(&self, modifier: &str) -> bool {
+		return self.modifiers.iter().any(|m| m == modifier);
+	}
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EachBlock {
+	pub context: String, // the `item` in {#each items as item}
+	pub index: Option<String>,
+	pub key: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
 pub enum FragmentType {
+	#[default]
 	Text, // just text
 	Basic, // h1, h2, div, etc.
 	Svelte, // other svelte components
 	Script, // script sections
 	Style, // style sections
+	IfBlock, // {#if x}...{:else if y}...{:else}...{/if}
+	EachBlock, // {#each items as item, i (item.id)}...{:else}...{/each}
+	KeyBlock, // {#key x}...{/key}, which gets thrown away and rebuilt whenever x changes
+	Head, // <svelte:head>, whose contents go into document.head
+	Window, // <svelte:window>
+	Body, // <svelte:body>
+	Document, // <svelte:document>
+	RawHtml, // {@html x}
+	Debug, // {@debug x, y}, which pauses whenever x or y change (in dev mode)
+	Const, // {@const x = y}, a value derived from a block's context
+	Options, // <svelte:options>, which only affects how we compile and doesn't render anything
+	DynamicElement, // <svelte:element this={tag}>, an element whose tag name is only known at runtime
+	Slot, // <slot>, where a component renders whatever it was given
+	SlotTemplate, // <svelte:fragment slot="x">, which fills a slot without adding an element
+	Comment, // <!-- x -->, which only sticks around with preserveComments
+	Tag, // unresolved {#...}, {:...} and {/...} markers, only exist until we group blocks
 }
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct CompileResult {
 	pub js: JSResult,
 	pub css: JSResult, // TODO: why is this re-used here?
 	pub ast: TODO,
-	pub warnings: Vec<TODO>,
+	pub warnings: Vec<Warning>,
 	pub vars: Vec<TODO>,
 	pub stats: TODO,
 }
@@ -112,6 +245,30 @@ pub struct JSMap {
 pub struct TODO {
 }
 
+// Mirrors svelte's own compile options, ex: `{ immutable: true, customElement: true }`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CompileOptions {
+	pub dev: bool, // extra runtime checks and debugging help
+	pub immutable: bool, // data only changes by reassignment, so we can compare with !== instead
+	pub accessors: bool, // props get getters and setters on the component
+	pub custom_element: bool,
+	pub tag: Option<String>, // the custom element's name, ex: my-element
+	pub namespace: Option<String>,
+	pub preserve_whitespace: bool, // keep whitespace as written, instead of collapsing and trimming it
+	pub preserve_comments: bool, // keep html comments in the output
+	pub generate: Option<String>, // "dom" (the default) for the browser, or "ssr" to render html strings on a server
+	pub hydratable: bool, // adopt the server's html instead of replacing it, see Block::claimer
+	pub filename: Option<String>, // where the component came from, which dev mode points back at
+}
+
+// Something that compiles, but probably isn't what the author meant
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Warning {
+	pub code: String,
+	pub message: String,
+}
+
 
 impl Compiler {
 	pub fn compile(&mut self, source: &str) -> CompileResult {
@@ -119,34 +276,234 @@ impl Compiler {
 			preprocessor::preprocess(source),
 			"preprocess"
 		);
+		self.hash = strings::hash(&source);
+		self.source = source.clone();
+
+		let is_ssr = match self.options.generate.as_deref() {
+			None | Some("dom") => false,
+			Some("ssr") => true,
+			Some(generate) => self.error(&format!("options.generate must be \"dom\" or \"ssr\", not \"{}\"", generate)),
+		};
 
-		// NOTE: this only does basic HTML parsing via external library.
-		// Complex info (JavaScript, for ex.) are only stored as unparsed strings
-		let dom = crate::time_function(|| 
-			Dom::parse(&source),
+		// NOTE: this only does HTML-ish parsing. Complex info (JavaScript, for ex.)
+		// are only stored as unparsed strings
+		let nodes = crate::time_function(||
+			parser::parse(&source),
 			"dom parsing"
-		).unwrap();
+		).unwrap_or_else(|message| self.error(&message));
+
+		if let Some(namespace) = self.options.namespace.clone() {
+			self.options.namespace = Some(self.resolve_namespace(&namespace).to_string());
+		}
+
+		// our names can't shadow anything the scripts declare, so those need reserving before we hand any out
+		for node in &nodes {
+			if let Node::Element(element) = node {
+				if element.name.to_lowercase() == "script" {
+					for child in &element.children {
+						if let Node::Text(text) = child {
+							self.names.reserve_script(text);
+						}
+					}
+				}
+			}
+		}
 
 		// convert HTML to Fragments and store any state we need to
-		let fragments = crate::time_function(||
-			self.convert_dom_nodes_to_fragments(&dom.children, false),
+		let mut fragments = crate::time_function(||
+			self.convert_dom_nodes_to_fragments(&nodes, false),
 			"fragment generation"
 		);
+		self.resolve_namespaces(&mut fragments, None);
+		self.strip_whitespace(&mut fragments, None, self.options.preserve_whitespace);
+		merge_text(&mut fragments);
+		self.check_self_references(&fragments);
 
 		// finally render those fragments
-		let result = crate::time_function(|| {
-			let mut renderer = DomRenderer::new(self);
-			renderer.render_fragments(&fragments)
+		let mut result = crate::time_function(|| {
+			if is_ssr {
+				SsrRenderer::new(self).render_fragments(&fragments)
+			} else {
+				DomRenderer::new(self).render_fragments(&fragments)
+			}
 		}, "final render");
+		result.warnings = std::mem::take(&mut self.warnings);
 		return result;
 	}
 
+	pub fn error(&self, message: &str) -> ! {
+		// TODO: should we return these as part of the result instead?
+		panic!("{}", message);
+	}
+
+	fn resolve_namespace(&self, namespace: &str) -> &'static str {
+		return namespaces::resolve(namespace)
+			.unwrap_or_else(|| self.error(&format!("Invalid namespace '{}'", namespace)));
+	}
+
+	// Figures out which namespace each element is in, now that we know whether <svelte:options> set one.
+	// The parent is the nearest element above these fragments, along with its namespace.
+	fn resolve_namespaces(&self, fragments: &mut Vec<Fragment>, parent: Option<(String, Option<String>)>) {
+		for fragment in fragments {
+			match fragment.ttype {
+				FragmentType::Basic | FragmentType::DynamicElement => {
+					let namespace = namespaces::get_namespace(
+						&fragment.name,
+						parent.as_ref().map(|(name, namespace)| (name.as_str(), namespace.as_deref())),
+						self.options.namespace.as_deref()
+					);
+					fragment.namespace = namespace.map(|namespace| namespace.to_string());
+					if fragment.namespace.is_none() {
+						self.check_html_attributes(fragment);
+					}
+					let parent = Some((fragment.name.clone(), fragment.namespace.clone()));
+					self.resolve_namespaces(&mut fragment.children, parent);
+				}
+				_ => {
+					// blocks and components don't change anything, ex: {@html} in an <svg> is still svg
+					fragment.namespace = match &parent {
+						Some((_, namespace)) => namespace.clone(),
+						None => self.options.namespace.clone(),
+					};
+					self.resolve_namespaces(&mut fragment.children, parent.clone());
+					self.resolve_namespaces(&mut fragment.else_children, parent.clone());
+				}
+			}
+		}
+	}
+
+	// Html attribute names aren't case sensitive, ex: class and CLASS are the same attribute. Exact
+	// duplicates get caught in extract_attributes, but until now we didn't know which elements are html.
+	fn check_html_attributes(&self, fragment: &Fragment) {
+		let bindings = fragment.directives.iter()
+			.filter(|directive| directive.ttype == DirectiveType::Binding)
+			.map(|directive| &directive.name);
+		let attributes = fragment.attributes.iter()
+			.filter(|attribute| !attribute.key.starts_with("..."))
+			.map(|attribute| &attribute.key);
+		let mut names = HashSet::new();
+		for name in bindings.chain(attributes) {
+			if !names.insert(name.to_lowercase()) {
+				self.error(&format!("Attributes need to be unique, found '{}' more than once on <{}>", name, fragment.name));
+			}
+		}
+	}
+
+	// <svelte:self> always rendering would recurse forever, so it has to be behind an {#if}, an {#each}
+	// or in a slot, which only render when asked to
+	fn check_self_references(&self, fragments: &Vec<Fragment>) {
+		for fragment in fragments {
+			match fragment.ttype {
+				FragmentType::IfBlock | FragmentType::EachBlock => {}
+				FragmentType::Svelte if fragment.name == constants::SVELTE_SELF => {
+					self.error("<svelte:self> components can only exist inside {#if} blocks, {#each} blocks, or slots passed to components");
+				}
+				FragmentType::Svelte => {}
+				_ => self.check_self_references(&fragment.children),
+			}
+		}
+	}
+
+	// Collapses whitespace, and drops it wherever it can't show up, ex: at the edges of an element.
+	// Everything inside <pre> and <textarea> is kept as written, as is everything with preserveWhitespace.
+	fn strip_whitespace(&self, fragments: &mut Vec<Fragment>, parent: Option<&Fragment>, keep_space: bool) {
+		for fragment in fragments.iter_mut() {
+			if is_static_text(fragment) && !keep_space {
+				fragment.data = fragment.data.as_deref().map(strings::collapse_whitespace);
+			}
+
+			let name = fragment.name.to_lowercase();
+			let keep_space = keep_space || match fragment.ttype {
+				FragmentType::Basic => PRESERVE_WHITESPACE_ELEMENTS.contains(&&*name) || RAW_TEXT_ELEMENTS.contains(&&*name),
+				_ => false,
+			};
+			let mut children = std::mem::take(&mut fragment.children);
+			let mut else_children = std::mem::take(&mut fragment.else_children);
+			self.strip_whitespace(&mut children, Some(fragment), keep_space);
+			self.strip_whitespace(&mut else_children, Some(fragment), keep_space);
+			fragment.children = children;
+			fragment.else_children = else_children;
+		}
+		if keep_space {
+			return;
+		}
+
+		// whitespace at the edges doesn't show up, except at the end of each blocks where it keeps items apart
+		let is_text = |fragment: &&mut Fragment| is_static_text(fragment);
+		if let Some(first) = fragments.iter_mut().find(|fragment| fragment.renderable).filter(is_text) {
+			first.data = first.data.as_deref().map(|data| data.trim_start_matches(strings::is_html_whitespace).to_string());
+		}
+		if parent.is_none_or(|parent| parent.ttype != FragmentType::EachBlock) {
+			if let Some(last) = fragments.iter_mut().filter(|fragment| fragment.renderable).last().filter(is_text) {
+				last.data = last.data.as_deref().map(|data| data.trim_end_matches(strings::is_html_whitespace).to_string());
+			}
+		}
+
+		// some elements can't have text at all, ex: whitespace between <option>s does nothing
+		let drops_whitespace = match parent {
+			Some(parent) if parent.ttype == FragmentType::Head => true,
+			Some(parent) if parent.ttype == FragmentType::Basic => {
+				ELEMENTS_WITHOUT_TEXT.contains(&&*parent.name.to_lowercase())
+					|| parent.namespace.as_deref() == Some(namespaces::SVG)
+			}
+			_ => false,
+		};
+		let mut previous: Option<&Fragment> = None;
+		let keep = fragments.iter()
+			.map(|fragment| {
+				let is_empty = is_static_text(fragment)
+					&& fragment.data.as_deref().is_some_and(|data| data.len() == 0);
+				// svg text still needs its spaces, ex: <tspan>a</tspan> <tspan>b</tspan>
+				let follows_svg_text = previous.is_some_and(|previous| {
+					previous.ttype == FragmentType::Basic && SVG_TEXT_ELEMENTS.contains(&&*previous.name)
+				});
+				let keep = !(is_empty || (drops_whitespace && fragment.is_whitespace() && !follows_svg_text));
+				previous = Some(fragment);
+				keep
+			})
+			.collect::<Vec<bool>>();
+		let mut keep = keep.into_iter();
+		fragments.retain(|_| keep.next().unwrap());
+	}
+
+	pub fn warn(&mut self, code: &str, message: &str) {
+		if self.ignores.iter().any(|codes| codes.iter().any(|ignored| ignored == code)) {
+			return;
+		}
+		self.warnings.push(Warning {
+			code: code.to_string(),
+			message: message.to_string(),
+		});
+	}
+
 	fn convert_dom_nodes_to_fragments(&mut self, children: &Vec<Node>, has_parent: bool) -> Vec<Fragment> {
 		let mut fragments = Vec::new();
+		let mut ignores = vec![];
 		for child in children {
-			fragments.extend(self.convert_dom_node_to_fragments(child, has_parent));
+			// <!-- svelte-ignore x y --> applies to the next element, and everything inside it
+			let is_element = matches!(child, Node::Element(_));
+			if is_element {
+				self.ignores.push(std::mem::take(&mut ignores));
+			}
+			for fragment in self.convert_dom_node_to_fragments(child, has_parent) {
+				// text on either side of a dropped comment is still one piece of text, ex: a<!-- x -->b
+				match fragments.last_mut() {
+					Some(last) if is_static_text(last) && is_static_text(&fragment) => {
+						last.data.as_mut().unwrap().push_str(fragment.data.as_deref().unwrap());
+					}
+					_ => fragments.push(fragment),
+				}
+			}
+			match child {
+				Node::Element(_) => {
+					self.ignores.pop();
+				}
+				Node::Comment(comment) => ignores.extend(strings::parse_svelte_ignore(comment)),
+				Node::Text(text) if text.chars().all(strings::is_html_whitespace) => {}
+				_ => ignores.clear(),
+			}
 		}
-		return fragments;
+		return self.group_blocks(fragments, has_parent);
 	}
 
 	pub fn convert_dom_node_to_fragments(&mut self, child: &Node, has_parent: bool) -> Vec<Fragment> {
@@ -156,56 +513,231 @@ impl Compiler {
 			},
 
 			Node::Text(text) => {
-				if has_parent {
-					return vec![Fragment {
-						name: "SHOULDN'T BE RENDERED".into(),
-						short_name: "SHOULDN'T BE RENDERED".into(),
-						attributes: vec![],
-						data: Some(text.to_string()),
-						ttype: FragmentType::Text,
-						renderable: true,
-						children: vec![],
-						contains_code: false,
-						has_parent
-					}];
-				} else {
-					// special case: we might want to split up text elements
-					let mut fragments = vec![];
-					let pieces = strings::split_handlebars_into_pieces(&text);
-					for (i, piece) in pieces.iter().enumerate() {
-						match piece.ttype {
-							HandlebarType::Text => {
+				// we might want to split up text elements
+				let mut fragments = vec![];
+				let pieces = strings::split_handlebars_into_pieces(text);
+				for (i, piece) in pieces.iter().enumerate() {
+					match piece.ttype {
+						HandlebarType::Text => {
+							fragments.push(Fragment {
+								name: "SHOULDN'T BE RENDERED".into(),
+								short_name: self.generate_short_name("t"),
+								data: Some(entities::decode_character_references(&piece.value, false)),
+								ttype: FragmentType::Text,
+								renderable: true,
+								has_parent,
+								..Default::default()
+							});
+						}
+						HandlebarType::Code => {
+							let code = piece.value.trim();
+							if code.starts_with('#') || code.starts_with(':') || code.starts_with('/') {
 								fragments.push(Fragment {
 									name: "SHOULDN'T BE RENDERED".into(),
-									short_name: self.generate_short_name("t"),
-									attributes: vec![],
-									data: Some(piece.value.clone()),
-									ttype: FragmentType::Text,
-									contains_code: false,
-									renderable: true,
-									children: vec![],
-									has_parent
+									data: Some(code.to_string()),
+									ttype: FragmentType::Tag,
+									..Default::default()
 								});
+								continue;
 							}
-							HandlebarType::Code => {
-								fragments.push(Fragment {
-									name: "SHOULDN'T BE RENDERED".into(),
-									short_name: self.generate_short_name("t"),
-									attributes: vec![],
-									data: Some(piece.value.trim().to_string()),
-									ttype: FragmentType::Text,
-									contains_code: true,
-									renderable: true,
-									children: vec![],
-									has_parent
-								});
+							if code.starts_with('@') {
+								fragments.push(self.create_special_tag_fragment(code, has_parent));
+								continue;
 							}
+
+							fragments.push(Fragment {
+								name: "SHOULDN'T BE RENDERED".into(),
+								short_name: self.generate_short_name("t"),
+								data: Some(code.to_string()),
+								ttype: FragmentType::Text,
+								contains_code: true,
+								renderable: true,
+								has_parent,
+								..Default::default()
+							});
 						}
 					}
-					return fragments;
 				}
+				return fragments;
 			},
-			_ => todo!()
+
+			// TODO: should we keep these around?
+			Node::Comment(comment) => {
+				if !self.options.preserve_comments {
+					return vec![];
+				}
+				return vec![Fragment {
+					name: "SHOULDN'T BE RENDERED".into(),
+					short_name: self.generate_short_name("comment"),
+					data: Some(comment.clone()),
+					ttype: FragmentType::Comment,
+					renderable: true,
+					has_parent,
+					..Default::default()
+				}];
+			}
+		}
+	}
+
+	// {@html x}, {@debug x, y} and {@const x = y}
+	fn create_special_tag_fragment(&mut self, code: &str, has_parent: bool) -> Fragment {
+		let (tag, expression) = strings::split_block_tag(code);
+		let expression = expression.trim();
+		let (ttype, short_name) = match tag {
+			"@html" => (FragmentType::RawHtml, self.generate_short_name("html_tag")),
+			"@debug" => {
+				let is_valid = expression.len() == 0 || expression.split(',').all(|name| strings::is_identifier(name.trim()));
+				if !is_valid {
+					self.error("{@debug ...} arguments must be identifiers, not arbitrary expressions");
+				}
+				(FragmentType::Debug, String::new())
+			}
+			"@const" => {
+				if strings::split_assignment(expression).is_none() {
+					self.error("{@const ...} must be an assignment, ex: {@const x = y}");
+				}
+				(FragmentType::Const, String::new())
+			}
+			_ => self.error(&format!("Expected 'html', 'debug' or 'const' after '{{@', not '{}'", code)),
+		};
+		if ttype == FragmentType::RawHtml && expression.len() == 0 {
+			self.error("{@html} needs an expression, ex: {@html x}");
+		}
+
+		Fragment {
+			renderable: true,
+			name: tag.to_string(),
+			short_name,
+			ttype,
+			data: Some(expression.to_string()),
+			has_parent,
+			..Default::default()
+		}
+	}
+
+	// Gathers everything between block tags, ex: {#if x}...{/if}, into block fragments
+	fn group_blocks(&mut self, fragments: Vec<Fragment>, has_parent: bool) -> Vec<Fragment> {
+		let mut result: Vec<Fragment> = vec![];
+		// open blocks, along with whether we're in their {:else} branch and
+		// whether they're an {:else if} (which gets closed along with its parent)
+		let mut stack: Vec<(Fragment, bool, bool)> = vec![];
+
+		for mut fragment in fragments {
+			if fragment.ttype != FragmentType::Tag {
+				if let Some((block, in_else, _)) = stack.last_mut() {
+					fragment.has_parent = false; // blocks mount their own children
+					if *in_else {
+						block.else_children.push(fragment);
+					} else {
+						block.children.push(fragment);
+					}
+				} else {
+					result.push(fragment);
+				}
+				continue;
+			}
+
+			let tag = fragment.data.unwrap();
+			let (keyword, expression) = strings::split_block_tag(&tag);
+			match keyword {
+				"#if" => stack.push((self.create_if_block(expression, has_parent), false, false)),
+				"#each" => stack.push((self.create_each_block(expression, has_parent), false, false)),
+				"#key" => stack.push((self.create_key_block(expression, has_parent), false, false)),
+				// NOTE: so {@const} in {:then} and {:catch} isn't supported either
+				"#await" | ":then" | ":catch" | "/await" => self.error("{#await} blocks aren't supported yet"),
+				":else" => {
+					match stack.last_mut() {
+						Some((block, in_else, _)) if !*in_else && block.ttype != FragmentType::KeyBlock => *in_else = true,
+						_ => self.error(&format!("Unexpected {{{}}}", tag)),
+					}
+
+					// {:else if y} is just an {#if y} nested in the else branch
+					if let Some(condition) = expression.strip_prefix("if ") {
+						let block = self.create_if_block(condition.trim(), false);
+						stack.push((block, false, true));
+					}
+				}
+				"/if" | "/each" | "/key" => {
+					let ttype = match keyword {
+						"/if" => FragmentType::IfBlock,
+						"/each" => FragmentType::EachBlock,
+						_ => FragmentType::KeyBlock,
+					};
+					loop {
+						let (block, _, is_else_if) = match stack.pop() {
+							Some(block) => block,
+							None => self.error(&format!("Unexpected {{{}}}", tag)),
+						};
+						if block.ttype != ttype {
+							self.error(&format!("Unexpected {{{}}}", tag));
+						}
+
+						match stack.last_mut() {
+							Some((parent, in_else, _)) => {
+								if *in_else {
+									parent.else_children.push(block);
+								} else {
+									parent.children.push(block);
+								}
+							}
+							None => result.push(block),
+						}
+						if !is_else_if {
+							break;
+						}
+					}
+				}
+				_ => self.error(&format!("Unknown block {{{}}}", tag)),
+			}
+		}
+
+		if let Some((block, _, _)) = stack.last() {
+			self.error(&format!("Block was left open: {}", block.name));
+		}
+		return result;
+	}
+
+	pub fn create_if_block(&mut self, condition: &str, has_parent: bool) -> Fragment {
+		Fragment {
+			renderable: true,
+			name: "if".to_string(),
+			short_name: self.generate_short_name("if_block"),
+			ttype: FragmentType::IfBlock,
+			data: Some(condition.to_string()),
+			has_parent,
+			..Default::default()
+		}
+	}
+
+	pub fn create_each_block(&mut self, expression: &str, has_parent: bool) -> Fragment {
+		let (list, each) = match strings::parse_each_expression(expression) {
+			Some(parsed) => parsed,
+			None => self.error(&format!("Invalid each block: {{#each {}}}", expression)),
+		};
+		Fragment {
+			renderable: true,
+			name: "each".to_string(),
+			short_name: self.generate_short_name("each"),
+			ttype: FragmentType::EachBlock,
+			data: Some(list),
+			each: Some(each),
+			has_parent,
+			..Default::default()
+		}
+	}
+
+	pub fn create_key_block(&mut self, expression: &str, has_parent: bool) -> Fragment {
+		if expression.len() == 0 {
+			self.error("{#key} needs an expression, ex: {#key x}");
+		}
+		Fragment {
+			renderable: true,
+			name: "key".to_string(),
+			short_name: self.generate_short_name("key_block"),
+			ttype: FragmentType::KeyBlock,
+			data: Some(expression.to_string()),
+			has_parent,
+			..Default::default()
 		}
 	}
 
@@ -216,61 +748,228 @@ impl Compiler {
 		}
 
 		match &*element.name.to_lowercase() {
+			"script" | "style" if self.in_head => self.create_raw_text_fragment(element, has_parent),
 			"script" => self.create_script_fragment(element),
 			"style" => self.create_style_fragment(element),
+			"slot" => self.create_slot_fragment(element, has_parent),
+			"svelte:fragment" => self.create_slot_template_fragment(element),
+			"svelte:head" => self.create_head_fragment(element),
+			"svelte:window" => self.create_special_fragment(element, FragmentType::Window),
+			"svelte:body" => self.create_special_fragment(element, FragmentType::Body),
+			"svelte:document" => self.create_special_fragment(element, FragmentType::Document),
+			"svelte:component" | "svelte:self" => self.create_svelte_fragment(element, has_parent),
+			"svelte:element" => self.create_dynamic_element_fragment(element, has_parent),
+			"svelte:options" => self.create_options_fragment(element, has_parent),
+			name if name.starts_with("svelte:") => {
+				self.error(&format!("Valid <svelte:...> tag names are svelte:head, svelte:window, svelte:body, svelte:document, svelte:component, svelte:self, svelte:element, svelte:options or svelte:fragment, not <{}>", element.name))
+			}
 			name => self.create_basic_fragment(element, name, has_parent)
 		}
 	}
 
+	pub fn create_head_fragment(&mut self, element: &Element) -> Fragment {
+		let in_head = std::mem::replace(&mut self.in_head, true);
+		let children = self.convert_dom_nodes_to_fragments(&element.children, true);
+		self.in_head = in_head;
+		Fragment {
+			renderable: true,
+			name: element.name.to_lowercase(),
+			short_name: "document.head".to_string(), // children get appended straight into it
+			ttype: FragmentType::Head,
+			children,
+			..Default::default()
+		}
+	}
+
+	// <svelte:window>, <svelte:body> and <svelte:document>, which only hold event handlers and bindings
+	pub fn create_special_fragment(&mut self, element: &Element, ttype: FragmentType) -> Fragment {
+		let name = element.name.to_lowercase();
+		if !self.special_elements.insert(name.clone()) {
+			self.error(&format!("A component can only have one <{}> element", name));
+		}
+		if element.children.len() > 0 {
+			self.error(&format!("<{}> cannot have children", name));
+		}
+
+		let attributes = self.extract_attributes(element);
+		if attributes.len() > 0 {
+			self.error(&format!("Valid attributes on <{}> are event handlers and bindings, not '{}'", name, attributes[0].key));
+		}
+		Fragment {
+			renderable: true,
+			name,
+			ttype,
+			directives: self.extract_directives(element),
+			..Default::default()
+		}
+	}
+
+	// <svelte:options> overrides whatever options we were compiled with
+	pub fn create_options_fragment(&mut self, element: &Element, has_parent: bool) -> Fragment {
+		if has_parent {
+			self.error("<svelte:options> tags cannot be inside elements or blocks");
+		}
+		if !self.special_elements.insert(constants::SVELTE_OPTIONS.to_string()) {
+			self.error("A component can only have one <svelte:options> element");
+		}
+		if element.children.len() > 0 {
+			self.error("<svelte:options> cannot have children");
+		}
+
+		for attribute in &element.attributes {
+			// everything has to be known at compile time, ex: immutable or immutable={true}
+			let value = match attribute.value.as_deref() {
+				None => None,
+				Some(value) if !strings::contains_handlebars(value) => Some(value.to_string()),
+				Some(value) => match strings::strip_mustache(value) {
+					Some(expression) if expression == "true" || expression == "false" => Some(expression.to_string()),
+					Some(expression) if expression.starts_with(['"', '\'']) && expression.len() > 1 && expression.ends_with(&expression[..1]) => {
+						Some(expression[1..expression.len() - 1].to_string())
+					}
+					_ => self.error(&format!("<svelte:options> '{}' must be known at compile time", attribute.key)),
+				},
+			};
+
+			match &*attribute.key {
+				"immutable" | "accessors" => {
+					let flag = match value.as_deref() {
+						None | Some("true") => true,
+						Some("false") => false,
+						_ => self.error(&format!("'{}' attribute must be true or false", attribute.key)),
+					};
+					if attribute.key == "immutable" {
+						self.options.immutable = flag;
+					} else {
+						self.options.accessors = flag;
+					}
+				}
+				"tag" => {
+					let tag = value.unwrap_or_else(|| self.error("'tag' must be a string literal"));
+					if !tag.contains('-') {
+						self.error("tag name must be two or more words joined by the '-' character");
+					}
+					if !self.options.custom_element {
+						self.warn("missing-custom-element-compile-options", "The 'tag' option is used when generating a custom element. Did you forget the 'customElement: true' compile option?");
+					}
+					self.options.tag = Some(tag);
+				}
+				"namespace" => {
+					let namespace = value.unwrap_or_else(|| self.error("The 'namespace' attribute must be a string literal representing a valid namespace"));
+					self.options.namespace = Some(self.resolve_namespace(&namespace).to_string());
+				}
+				key => self.error(&format!("<svelte:options> unknown attribute '{}'", key)),
+			}
+		}
+
+		Fragment {
+			renderable: false,
+			name: element.name.to_lowercase(),
+			ttype: FragmentType::Options,
+			..Default::default()
+		}
+	}
+
 	pub fn create_basic_fragment(&mut self, element: &Element, name: &str, has_parent: bool) -> Fragment {
+		if element.attributes.iter().any(|attribute| attribute.key == "autofocus") {
+			self.warn("a11y-autofocus", "A11y: Avoid using autofocus");
+		}
 		Fragment {
 			renderable: true,
-			name: name.to_string(),
+			name: element.name.clone(), // NOTE: casing matters for svg, ex: <clipPath>
 			short_name: self.generate_short_name(name),
 			ttype: FragmentType::Basic,
 			children: self.convert_dom_nodes_to_fragments(&element.children, true),
-			attributes: self.extract_attributes(&element),
-			data: None,
-			contains_code: false,
+			attributes: self.extract_element_attributes(element),
+			directives: self.extract_directives(element),
 			has_parent,
+			start: element.start,
+			..Default::default()
 		}
 	}
 
-	pub fn create_script_fragment(&mut self, element: &Element) -> Fragment {
-		if element.children.len() == 0 {
-			// Should I handle this somehow?
-			todo!();
+	// <script> or <style> inside <svelte:head>, whose text is taken as is, ex: no {expressions}
+	pub fn create_raw_text_fragment(&mut self, element: &Element, has_parent: bool) -> Fragment {
+		let name = element.name.to_lowercase();
+		let mut fragment = self.create_basic_fragment(&Element { children: vec![], ..element.clone() }, &name, has_parent);
+		for child in &element.children {
+			if let Node::Text(text) = child {
+				if text.len() > 0 {
+					fragment.children.push(Fragment {
+						name: "SHOULDN'T BE RENDERED".into(),
+						short_name: self.generate_short_name("t"),
+						data: Some(text.clone()),
+						ttype: FragmentType::Text,
+						renderable: true,
+						has_parent: true,
+						..Default::default()
+					});
+				}
+			}
 		}
+		return fragment;
+	}
 
+	pub fn create_dynamic_element_fragment(&mut self, element: &Element, has_parent: bool) -> Fragment {
+		let children = self.convert_dom_nodes_to_fragments(&element.children, true);
+		let mut attributes = self.extract_element_attributes(element);
+		let index = attributes.iter().position(|attribute| attribute.key == "this");
+		let attribute = match index {
+			Some(index) => attributes.remove(index),
+			None => self.error("<svelte:element> must have a 'this' attribute"),
+		};
+
+		// either a plain string, ex: this="h1", or an expression that gives one, ex: this={tag}
+		let tag = match attribute.value.as_deref() {
+			Some(value) if !strings::contains_handlebars(value) => {
+				if parser::is_void(value) && children.iter().any(|child| !child.is_whitespace()) {
+					self.warn("invalid-void-content", &format!("<svelte:element this=\"{}\"> is self-closing and cannot have content", value));
+				}
+				strings::to_js_string(value)
+			}
+			Some(value) => match strings::strip_mustache(value) {
+				Some(expression) => expression.to_string(),
+				None => self.error("Invalid element definition -- expected a string or an {expression} for 'this'"),
+			},
+			None => self.error("Invalid element definition -- expected a string or an {expression} for 'this'"),
+		};
+
+		Fragment {
+			renderable: true,
+			name: element.name.to_lowercase(),
+			short_name: self.generate_short_name("svelte_element"),
+			ttype: FragmentType::DynamicElement,
+			data: Some(tag),
+			children,
+			attributes,
+			directives: self.extract_directives(element),
+			has_parent,
+			start: element.start,
+			..Default::default()
+		}
+	}
+
+	pub fn create_script_fragment(&mut self, element: &Element) -> Fragment {
 		if element.children.len() != 1 {
 			panic!("this shouldn't happen");
 		}
 
-		let styles = match &element.children[0] {
+		let script = match &element.children[0] {
 			Node::Text(text) => text,
 			_ => panic!()
 		};
 
-		self.has_style_fragments = true;
+		self.has_script_fragments = true;
 		Fragment {
 			renderable: false,
 			name: element.name.clone(),
-			short_name: self.generate_style_name(&styles),
 			ttype: FragmentType::Script,
-			children: vec![],
 			attributes: self.extract_attributes(&element),
-			data: Some(styles.clone()),
-			has_parent: false,
-			contains_code: false,
+			data: Some(script.clone()),
+			..Default::default()
 		}
 	}
 
 	pub fn create_style_fragment(&mut self, element: &Element) -> Fragment {
-		if element.children.len() == 0 {
-			// Should I handle this somehow?
-			todo!();
-		}
-
 		if element.children.len() != 1 {
 			panic!("this shouldn't happen");
 		}
@@ -286,85 +985,190 @@ impl Compiler {
 			name: element.name.clone(),
 			short_name: self.generate_style_name(&styles),
 			ttype: FragmentType::Style,
-			children: vec![],
 			attributes: self.extract_attributes(&element),
 			data: Some(styles.clone()),
-			has_parent: false,
-			contains_code: false,
+			..Default::default()
 		}
 	}
 
 	pub fn create_svelte_fragment(&mut self, element: &Element, has_parent: bool) -> Fragment {
-		if element.children.len() > 0 {
-			todo!();
+		self.has_svelte_fragments = true;
+		let mut children = self.convert_dom_nodes_to_fragments(&element.children, false);
+
+		// anything with a slot="x" attribute goes into that slot instead of the default one
+		for child in &mut children {
+			let index = child.attributes.iter().position(|attribute| attribute.key == "slot");
+			if let Some(index) = index {
+				let attribute = child.attributes.remove(index);
+				child.slot = match attribute.value {
+					Some(value) if !strings::contains_handlebars(&value) => Some(value),
+					_ => self.error("slot attribute cannot have a dynamic value"),
+				};
+			}
+		}
+		for child in &children {
+			if child.ttype == FragmentType::SlotTemplate && child.slot.is_none() {
+				self.error("<svelte:fragment> must have a slot attribute");
+			}
 		}
 
-		self.has_svelte_fragments = true;
+		// <svelte:component this={x}> renders whatever constructor x holds
+		let mut attributes = self.extract_attributes(element);
+		let mut data = None;
+		let name = element.name.to_lowercase();
+		let short_name = match &*name {
+			"svelte:component" => {
+				let index = attributes.iter().position(|attribute| attribute.key == "this");
+				let attribute = match index {
+					Some(index) => attributes.remove(index),
+					None => self.error("<svelte:component> must have a 'this' attribute"),
+				};
+				data = match attribute.value.as_deref().and_then(strings::strip_mustache) {
+					Some(expression) => Some(expression.trim().to_string()),
+					None => self.error("Invalid component definition -- must be an {expression}"),
+				};
+				self.generate_short_name("switch_instance")
+			}
+			"svelte:self" => self.generate_short_name("component"),
+			_ => self.generate_short_name(&element.name),
+		};
+
 		Fragment {
 			renderable: true,
-			name: element.name.clone(),
-			short_name: self.generate_short_name(&element.name),
+			name: if name.starts_with("svelte:") { name } else { element.name.clone() },
+			short_name,
 			ttype: FragmentType::Svelte,
-			children: vec![],
-			attributes: self.extract_attributes(&element),
-			data: None,
+			data,
+			children,
+			attributes,
+			directives: self.extract_directives(element),
 			has_parent,
-			contains_code: false,
+			..Default::default()
+		}
+	}
+
+	pub fn create_slot_fragment(&mut self, element: &Element, has_parent: bool) -> Fragment {
+		Fragment {
+			renderable: true,
+			name: element.name.clone(),
+			ttype: FragmentType::Slot,
+			children: self.convert_dom_nodes_to_fragments(&element.children, true),
+			attributes: self.extract_attributes(element),
+			has_parent,
+			..Default::default()
+		}
+	}
+
+	pub fn create_slot_template_fragment(&mut self, element: &Element) -> Fragment {
+		Fragment {
+			renderable: true,
+			name: element.name.clone(),
+			ttype: FragmentType::SlotTemplate,
+			children: self.convert_dom_nodes_to_fragments(&element.children, false),
+			attributes: self.extract_attributes(element),
+			directives: self.extract_directives(element),
+			..Default::default()
 		}
 	}
 
 	pub fn extract_attributes(&mut self, element: &Element) -> Vec<Attribute> {
-		// converting hashmap to vector for (hopefully) ease of use
-		let mut attributes = vec![];
-		for (key, value) in &element.attributes {
-			// NOTE: this is a hack, since the current parsing doesn't handle attributes with no values well
-			let key_pieces: Vec<&str> = key.split(" ").collect();
-			for (i, key) in key_pieces.iter().enumerate() {
-				if i == key_pieces.len()-1 { // final piece, use real value
-					attributes.push(Attribute {
-						key: key.to_string(),
-						value: value.clone()
-					});
-				} else {
-					attributes.push(Attribute { // pre-final piece, it has no value
-						key: key.to_string(),
-						value: None
-					});
-				}
+		// bind:value sets the same thing as value does, so they count as duplicates too
+		let mut names = HashSet::new();
+		for attribute in &element.attributes {
+			let name = match strings::parse_directive(&attribute.key) {
+				Some(("bind", name, _)) => name,
+				Some(_) => continue,
+				None if attribute.key.starts_with("...") => continue,
+				None => &attribute.key,
+			};
+			if !names.insert(name) {
+				self.error(&format!("Attributes need to be unique, found '{}' more than once on <{}>", name, element.name));
 			}
 		}
 
-		if element.classes.len() > 0 {
-			let mut classes = String::new();
-			for (i, class) in element.classes.iter().enumerate() {
-				classes.push_str(class);
-				if i < element.classes.len()-1 {
-					classes.push(' ');
+		return element.attributes.iter()
+			.filter(|attribute| strings::parse_directive(&attribute.key).is_none())
+			.cloned()
+			.collect();
+	}
+
+	// Same as extract_attributes, but tidied up the way html elements need
+	pub fn extract_element_attributes(&mut self, element: &Element) -> Vec<Attribute> {
+		let mut attributes = self.extract_attributes(element);
+		for attribute in &mut attributes {
+			if let Some(suggestion) = i18n::react_attributes.get(&*attribute.key) {
+				self.warn("invalid-html-attribute", &format!("'{}' is not a valid HTML attribute. Did you mean '{}'?", attribute.key, suggestion));
+			}
+
+			// ex: class="  a\n  b " is the same as class="a b"
+			if !i18n::attributes_to_compact_whitespace.contains(&&*attribute.key.to_lowercase()) {
+				continue;
+			}
+			if let Some(value) = &attribute.value {
+				let mut compacted = String::new();
+				let mut index = 0;
+				for span in strings::find_mustaches(value) {
+					compacted.push_str(&strings::collapse_whitespace(&value[index..span.start]));
+					compacted.push_str(&value[span.clone()]);
+					index = span.end;
 				}
+				compacted.push_str(&strings::collapse_whitespace(&value[index..]));
+				attribute.value = Some(compacted.trim_matches(strings::is_html_whitespace).to_string());
 			}
-			attributes.push(Attribute { // pre-final piece, it has no value
-				key: constants::CLASS.to_string(),
-				value: Some(classes)
-			});
 		}
-
 		return attributes;
 	}
 
+	pub fn extract_directives(&mut self, element: &Element) -> Vec<Directive> {
+		let mut directives = vec![];
+		for attribute in &element.attributes {
+			// these would otherwise end up as plain attributes, ex: class:active="..."
+			if let Some((prefix @ ("class" | "style" | "use"), _)) = attribute.key.split_once(':') {
+				self.error(&format!("{}: directives aren't supported yet", prefix));
+			}
+			if let Some((prefix, name, modifiers)) = strings::parse_directive(&attribute.key) {
+				let ttype = match prefix {
+					"transition" => DirectiveType::Transition,
+					"in" => DirectiveType::In,
+					"out" => DirectiveType::Out,
+					"animate" => DirectiveType::Animate,
+					"let" => DirectiveType::Let,
+					"on" => DirectiveType::On,
+					"bind" => DirectiveType::Binding,
+					_ => unreachable!(),
+				};
+
+				// anything other than a plain function name needs to live in the instance,
+				// so assignments in it get invalidated
+				let mut handler = None;
+				if ttype == DirectiveType::On {
+					let is_reference = attribute.value.as_deref()
+						.and_then(strings::strip_mustache)
+						.is_some_and(strings::is_identifier);
+					if !is_reference {
+						handler = Some(self.generate_short_name(&format!("{}_handler", strings::to_identifier(name))));
+					}
+				}
+
+				directives.push(Directive {
+					ttype,
+					name: name.to_string(),
+					modifiers,
+					value: attribute.value.clone(),
+					handler,
+				});
+			}
+		}
+		return directives;
+	}
+
 	pub fn generate_style_name(&mut self, styles: &str) -> String {
 		// TODO: how to do this for real?
 		return format!("");
 	}
 
 	pub fn generate_short_name(&mut self, name: &str) -> String {
-		let name = &name.to_lowercase();
-		let count = self.short_name_counts.entry(name.to_string()).or_insert(-1);
-		*count += 1;
-		if *count == 0 {
-			return format!("{}", name);
-		} else {
-			return format!("{}{}", name, count);
-		}
+		return self.names.allocate(name);
 	}
 
 	pub fn insert_import(&mut self, import: &Import) {
@@ -403,27 +1207,588 @@ impl Compiler {
 
 #[cfg(test)]
 mod tests {
-	use super::compile;
+	use super::{compile, compile_with_options, parser, Attribute, Node};
 	// use std::{thread, time};
 
+	// Just the generated js, without the json around it
+	fn compile_js(program: &str) -> String {
+		let result: serde_json::Value = serde_json::from_str(&compile(program)).unwrap();
+		return result["js"]["code"].as_str().unwrap().to_string();
+	}
+
+	// The message a program fails to compile with, given options as JSON
+	fn compile_error(program: &str, options: &str) -> String {
+		let error = std::panic::catch_unwind(|| compile_with_options(program, options)).expect_err(program);
+		return match error.downcast::<String>() {
+			Ok(message) => *message,
+			Err(error) => error.downcast::<&str>().map(|message| message.to_string()).unwrap_or_default(),
+		};
+	}
+
 	#[test]
 	fn basic() {
 		// let program = "<SCRIPT>let x = 5;</SCRIPT><h1>Hi</h1>";
 		// let program = "<h1>Hi</h1>";
 		// let program = "yo";
 
-		// NOTE: newlines are stripped out :\
 		// let program = "<h1>{{ x }}</h1>";
 		// let program = "<h1 width=50>{{ x }}</h1>";
 
-		// NOTE: duplicate attributes are ignored
-
-		// NOTE: <div a b=1></div> yields only one attributes, "a b". We split it later on,
-		// but if we re-write the parser we should fix that
 		let program = "<canvas super width=\"hello {x}\" name=\"hi\"></canvas>";
 
 		// dbg!(&program);
 		let result = compile(program);
 		println!("{}", result);
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn parser() {
+		let attribute = |key: &str, value: Option<&str>| Attribute { key: key.to_string(), value: value.map(|value| value.to_string()) };
+		let element = |node: &Node| match node {
+			Node::Element(element) => element.clone(),
+			_ => panic!("expected an element, got {:?}", node),
+		};
+
+		// mustaches can hold anything, ex: > and quotes, without ending the tag or the attribute
+		let nodes = parser::parse("<p a b=1 title=\"x {a > b ? '\"' : 'y'}\" {name} {...rest} on:click|once={() => f(\"}\")}>{a < b}</p>").unwrap();
+		let p = element(&nodes[0]);
+		assert_eq!(p.name, "p");
+		assert_eq!(p.attributes, vec![
+			attribute("a", None),
+			attribute("b", Some("1")),
+			attribute("title", Some("x {a > b ? '\"' : 'y'}")),
+			attribute("name", Some("{name}")),
+			attribute("...rest", None),
+			attribute("on:click|once", Some("{() => f(\"}\")}")),
+		]);
+		assert_eq!(p.children, vec![Node::Text("{a < b}".to_string())]);
+
+		// void elements don't need closing, and script and style contents are left as they are
+		let nodes = parser::parse("<input><br/><!-- hi --><script>if (a < b) { x = '<p>'; }</script>").unwrap();
+		assert_eq!(element(&nodes[0]).children, vec![]);
+		assert_eq!(element(&nodes[1]).name, "br");
+		assert_eq!(nodes[2], Node::Comment(" hi ".to_string()));
+		assert_eq!(element(&nodes[3]).children, vec![Node::Text("if (a < b) { x = '<p>'; }".to_string())]);
+
+		assert_eq!(parser::parse("<div><p></div>"), Err("<p> was left open at 8".to_string()));
+		assert_eq!(parser::parse("</div>"), Err("Unexpected closing tag at 0".to_string()));
+		assert_eq!(parser::parse("<p>{a</p>"), Err("Unclosed mustache at 3".to_string()));
+		assert_eq!(parser::parse("<!-- x"), Err("Unclosed comment at 4".to_string()));
+	}
+
+	#[test]
+	fn transitions() {
+		let program = "<script>import { fade } from 'svelte/transition'; let visible = true;</script>{#if visible}<p transition:fade|local>hi</p>{/if}";
+		let result = compile(program);
+		assert!(result.contains("create_bidirectional_transition(p, fade, {}, true)"));
+		assert!(result.contains("group_outros()"));
+	}
+
+	#[test]
+	fn component_props() {
+		let program = "<script>import Child from './Child.svelte'; let name = 'x';</script><Child a={1} b=\"hi {name}\" {...$$restProps}/>";
+		let result = compile(program);
+		assert!(result.contains("get_spread_update(child_spread_levels"));
+		assert!(result.contains("compute_rest_props($$props, omit_props_names)"));
+	}
+
+	#[test]
+	fn window_bindings() {
+		let program = "<script>let y;</script><svelte:window bind:scrollY={y} on:keydown={() => y = 0}/>";
+		let result = compile(program);
+		assert!(result.contains("$$invalidate(0, y = window.pageYOffset)"));
+		assert!(result.contains("listen(window, \\\"keydown\\\", /*keydown_handler*/ ctx[2])"));
+		// nothing to create, so no empty c()
+		assert!(result.contains("c: noop,"));
+	}
+
+	#[test]
+	fn dynamic_components() {
+		let program = "<script>import A from './A.svelte'; let c = A;</script><svelte:component this={c}/>";
+		let result = compile(program);
+		assert!(result.contains("var switch_value = /*c*/ ctx[0];"));
+		assert!(result.contains("if (switch_value !== (switch_value = /*c*/ ctx[0]))"));
+		assert!(result.contains("switch_instance = new switch_value(switch_props(ctx));\\n\\t\\t\\t\\t\\tcreate_component(switch_instance.$$.fragment);"));
+	}
+
+	#[test]
+	fn dynamic_elements() {
+		let program = "<script>let tag = 'h1';</script><svelte:element this={tag}>hi</svelte:element>";
+		let result = compile(program);
+		assert!(result.contains("svelte_element = element(/*tag*/ ctx[0]);"));
+		assert!(result.contains("safe_not_equal(previous_svelte_element_tag, /*tag*/ ctx[0])"));
+	}
+
+	#[test]
+	fn component_options() {
+		let program = "<svelte:options immutable accessors/><script>export let a;</script>{a}";
+		let result = compile(program);
+		assert!(result.contains("not_equal, { a: 0 }"));
+		assert!(result.contains("set a(a) {"));
+	}
+
+	#[test]
+	fn special_tags() {
+		let program = "<script>let items = []; let h = '';</script>{#each items as item}{@const doubled = item * 2}{doubled}{/each}{@html h}";
+		let result = compile(program);
+		assert!(result.contains("const doubled = /*item*/ ctx[2] * 2;"));
+		assert!(result.contains("ctx = get_each_block_context(new_ctx);"));
+		assert!(result.contains("html_tag.m(/*h*/ ctx[1], target, anchor);"));
+	}
+
+	#[test]
+	fn key_blocks() {
+		let program = "<script>let v = 1;</script>{#key v}<p>{v}</p>{/key}";
+		let result = compile(program);
+		assert!(result.contains("safe_not_equal(previous_key_block_key, previous_key_block_key = /*v*/ ctx[0])"));
+		assert!(result.contains("key_block = create_key_block(ctx);"));
+	}
+
+	#[test]
+	fn namespaces() {
+		let program = "<svg viewbox='0 0 1 1'><use xlink:href='#a'/><foreignObject><div/></foreignObject></svg>";
+		let result = compile(program);
+		assert!(result.contains("svg = svg_element(\\\"svg\\\");"));
+		assert!(result.contains("attr(svg, \\\"viewBox\\\", \\\"0 0 1 1\\\");"));
+		assert!(result.contains("xlink_attr(use, \\\"xlink:href\\\", \\\"#a\\\");"));
+		assert!(result.contains("div = element(\\\"div\\\");"));
+	}
+
+	#[test]
+	fn custom_elements() {
+		let program = "<script>export let name;</script><my-widget value={name}/>";
+		let result = compile(program);
+		assert!(result.contains("let my_widget;"));
+		assert!(result.contains("set_custom_element_data(my_widget, \\\"value\\\", my_widget_value_value = /*name*/ ctx[0]);"));
+	}
+
+	#[test]
+	fn safe_names() {
+		let program = "<script>let p = 1;</script><var>a</var><svg><text>b</text></svg><p>{p}</p>";
+		let result = compile(program);
+		assert!(result.contains("let var1;"));
+		assert!(result.contains("let text1;"));
+		assert!(result.contains("let p1;"));
+
+		// internal names are only claimed by the blocks that use them
+		let result = compile("<i>a</i><svg><rect/></svg>");
+		assert!(result.contains("let i;"));
+		assert!(result.contains("let rect;"));
+		let result = compile("<script>let items = [];</script><i>{#each items as item (item)}<rect animate:flip/>{/each}</i>");
+		assert!(result.contains("_blocks[i1].m(i, null);"));
+		assert!(result.contains("rect1 = rect.getBoundingClientRect();"));
+	}
+
+	#[test]
+	fn escaping() {
+		let cases = [
+			("<p>a \"quote\"</p>", r#"p.textContent = "a \"quote\"";"#),
+			("<p>back\\slash</p>", r#"p.textContent = "back\\slash";"#),
+			("<p>`tick` $</p>", r#"p.textContent = "`tick` $";"#),
+			("<pre>line\nbreak</pre>", r#"pre.textContent = "line\nbreak";"#),
+			("<p>\u{2028}</p>", r#"p.textContent = "\u2028";"#),
+			("<p>\\{x}</p>", r#"t = text(`\\${/*x*/ ctx[0]}`);"#),
+			("<p title='\"'/>", r#"attr(p, "title", "\"");"#),
+			("<p title=\"a\\b\"/>", r#"attr(p, "title", "a\\b");"#),
+			("<p title=\"{x}\\\"/>", r#"p_title_value = /*x*/ ctx[0] + "\\""#),
+		];
+		for (program, expected) in cases {
+			let program = format!("<script>let x;</script>{}", program);
+			let result = compile_js(&program);
+			assert!(result.contains(expected), "{} should contain {}, got:\n{}", program, expected, result);
+		}
+	}
+
+	#[test]
+	fn entities() {
+		let cases = [
+			("<p>a &amp; b</p>", r#"p.textContent = "a & b";"#),
+			("<p>&nbsp;&lt;&#x27;&#65;</p>", "p.textContent = \"\u{a0}<'A\";"),
+			("<p>&copy 2020</p>", "p.textContent = \"\u{a9} 2020\";"),
+			("<p>&notin; &notit;</p>", "p.textContent = \"\u{2209} \u{ac}it;\";"),
+			("<p>&#128;&#0;&bogus;</p>", "p.textContent = \"\u{20ac}\u{fffd}&bogus;\";"),
+			("<a href=\"?a=1&copy=2&amp;b\"/>", r#"attr(a, "href", "?a=1&copy=2&b");"#),
+			("<a title=\"&copy {x}\"/>", "a_title_value = \"\u{a9} \" + /*x*/ ctx[0]"),
+		];
+		for (program, expected) in cases {
+			let program = format!("<script>let x;</script>{}", program);
+			let result = compile_js(&program);
+			assert!(result.contains(expected), "{} should contain {}, got:\n{}", program, expected, result);
+		}
+	}
+
+	#[test]
+	fn whitespace() {
+		let program = "<script>let x;</script>\n<div>\n\t<b>a</b>  <i>b</i>\n\t{x}\n</div>\n<pre>  a\n  b</pre>\n<p>  a\n\tb&nbsp;</p>\n<select>\n\t<option>a</option>\n</select>\n";
+		let result = compile_js(program);
+		assert!(result.contains("t3 = space();"));
+		assert!(result.contains("pre.textContent = \"  a\\n  b\";"));
+		assert!(result.contains("p.textContent = \"a b\u{a0}\";"));
+		assert!(!result.contains("text(\"\\n"));
+		assert!(result.contains("select.innerHTML = \"<option>a</option>\";"));
+
+		let result: serde_json::Value = serde_json::from_str(&compile_with_options(program, "{\"preserveWhitespace\": true}")).unwrap();
+		let result = result["js"]["code"].as_str().unwrap();
+		assert!(result.contains("text(\"\\n\\t\")"));
+		assert!(result.contains("p.textContent = \"  a\\n\\tb\u{a0}\";"));
+	}
+
+	#[test]
+	fn comments() {
+		let program = "<p>a <!-- x --> b</p>\n<!-- svelte-ignore a11y-autofocus -->\n<input autofocus>\n<div><input autofocus></div>";
+		let result: serde_json::Value = serde_json::from_str(&compile(program)).unwrap();
+		let js = result["js"]["code"].as_str().unwrap();
+		assert!(js.contains("p.textContent = \"a b\";"));
+		assert!(!js.contains("createComment"));
+		let warnings = result["warnings"].as_array().unwrap();
+		assert_eq!(warnings.len(), 1);
+		assert_eq!(warnings[0]["code"], "a11y-autofocus");
+
+		let result: serde_json::Value = serde_json::from_str(&compile_with_options(program, "{\"preserveComments\": true}")).unwrap();
+		let js = result["js"]["code"].as_str().unwrap();
+		assert!(js.contains("p.innerHTML = \"a <!-- x --> b\";"));
+		assert!(js.contains("document.createComment(\" svelte-ignore a11y-autofocus \");"));
+	}
+
+	#[test]
+	fn static_subtrees() {
+		let program = "<script>let x;</script><div class=\"a\"><h1 title=\"&quot;hi&quot;\">a &lt; b</h1><br><pre>\nx</pre></div><div><p>{x}</p><p>static <b>bold</b></p></div>";
+		let result = compile_js(program);
+		assert!(result.contains(r#"div.innerHTML = "<h1 title=\"&quot;hi&quot;\">a &lt; b</h1><br><pre>\n\nx</pre>";"#));
+		assert!(result.contains(r#"attr(div, "class", "a");"#));
+		assert!(result.contains(r#"div1.innerHTML = "<p></p><p>static <b>bold</b></p>";"#));
+		assert!(result.contains("p = div1.childNodes[0];"));
+		assert!(result.contains("t2 = text(/*x*/ ctx[0]);"));
+		assert!(result.contains("append(p, t2);"));
+		assert!(!result.contains("append(div1, p);"));
+
+		// neighbouring text ends up as one node
+		let result = compile_js("<script>let x;</script><div>a <!-- c --> b <h1>t</h1> <input value={x}></div>");
+		assert!(result.contains(r#"div.innerHTML = "a b <h1>t</h1> <input>";"#));
+		assert!(result.contains("input = div.childNodes[3];"));
+
+		// tables get rearranged by the browser, so they're built node by node
+		let result = compile_js("<script>let x;</script><table><tr><td>a</td><td>{x}</td></tr></table>");
+		assert!(!result.contains("innerHTML"));
+	}
+
+	#[test]
+	fn merged_text() {
+		let program = "<script>let name; let n;</script><p>\n\tHello {name}! `${n}`\n</p>{#if n}{n}{/if}";
+		let result = compile_js(program);
+		assert!(result.contains("t = text(`Hello ${/*name*/ ctx[0]}! \\`$${/*n*/ ctx[1]}\\``);"));
+		assert!(result.contains("if (dirty & /*name, n*/ 3) set_data(t, `Hello ${/*name*/ ctx[0]}! \\`$${/*n*/ ctx[1]}\\``);"));
+		assert!(result.contains("= text(/*n*/ ctx[1]);"));
+
+		// line breaks are escaped, so indenting the code around them leaves them alone
+		let result = compile_js("<script>let x;</script><pre>a\n  {x}\nb</pre>");
+		assert!(result.contains("t = text(`a\\n  ${/*x*/ ctx[0]}\\nb`);"));
+	}
+
+	#[test]
+	fn mustache_scanning() {
+		let program = "<script>let a; let s;</script><p title={ \"}\" }>{`${a}}`}</p><p>{(/}/).test(s)} {a /* } */}</p><div style=\"{ a ? 'x}' : `y` }\">{#if a}{ {b: 1}.b }{/if}</div>";
+		let result = compile_js(program);
+		assert!(result.contains(r#"attr(p, "title", p_title_value = "}");"#));
+		assert!(result.contains("t = text(`${/*a*/ ctx[0]}}`);"));
+		assert!(result.contains("text(`${(/}/).test(/*s*/ ctx[1])} ${/*a*/ ctx[0] /* } */}`);"));
+		assert!(result.contains("div_style_value = /*a*/ ctx[0] ? 'x}' : `y`"));
+		assert!(result.contains("text({b: 1}.b);"));
+
+		// braces that are never closed are just text
+		let pieces = super::strings::split_handlebars_into_pieces("a { b");
+		assert_eq!(pieces.len(), 1);
+		assert_eq!(pieces[0].value, "a { b");
+	}
+
+	#[test]
+	fn typed_attributes() {
+		let program = "<script>let x; let w;</script><input disabled value=\"a &amp; b\" checked={x}><canvas width={w} foo></canvas><button disabled=\"false\" {x}>b</button>";
+		let result = compile_js(program);
+		assert!(result.contains("input.disabled = true;"));
+		assert!(result.contains("input.value = \"a & b\";"));
+		assert!(result.contains("input.checked = input_checked_value = /*x*/ ctx[0];"));
+		assert!(result.contains("input.checked = input_checked_value;"));
+		assert!(result.contains("attr(canvas, \"width\", canvas_width_value = /*w*/ ctx[1]);"));
+		assert!(result.contains("attr(canvas, \"foo\", \"\");"));
+		assert!(result.contains("button.disabled = true;"));
+		assert!(result.contains("attr(button, \"x\", button_x_value = /*x*/ ctx[0]);"));
+	}
+
+	#[test]
+	fn attribute_normalization() {
+		let program = "<script>let x;</script><div class=\"  a\n\t b {x}  c \" style=\" color: red;\n\" className=\"a\">{x}</div>";
+		let result: serde_json::Value = serde_json::from_str(&compile(program)).unwrap();
+		let js = result["js"]["code"].as_str().unwrap();
+		assert!(js.contains("div_class_value = \"a b \" + /*x*/ ctx[0] + \" c\""));
+		assert!(js.contains("attr(div, \"style\", \"color: red;\");"));
+		assert_eq!(result["warnings"][0]["code"], "invalid-html-attribute");
+		assert_eq!(result["warnings"][0]["message"], "'className' is not a valid HTML attribute. Did you mean 'class'?");
+
+		for program in ["<div a=1 a=2/>", "<input value={x} bind:value={x}>", "<Child a b/><Child a a/>", "<div class=\"a\" CLASS=\"b\"/>", "<input VALUE={x} bind:value={x}>"] {
+			let program = format!("<script>import Child from './Child.svelte'; let x;</script>{}", program);
+			let error = compile_error(&program, "{}");
+			assert!(error.contains("Attributes need to be unique"), "{} failed with: {}", program, error);
+		}
+
+		// only html ignores casing, so components and svg can have both
+		let js = compile_js("<script>import Child from './Child.svelte';</script><Child a A/><svg><path d=\"a\" D=\"b\"/></svg><p CLASS=\" a  b \"/>");
+		assert!(js.contains("props: {\n\t\t\ta: true,\n\t\t\tA: true\n\t\t}"));
+		assert!(js.contains("path = svg_element(\"path\");"));
+		assert!(js.contains("attr(p, \"CLASS\", \"a b\");"));
+	}
+
+	#[test]
+	fn ssr() {
+		let program = "<script>import Child from './Child.svelte'; export let name; let items = [];</script>\
+			<svelte:head><title>{name}</title></svelte:head>\
+			<h1 id={name} class=\"a {name}\">Hi &amp; {name}</h1>\
+			{#each items as item}<li>{item}</li>{/each}\
+			<Child a={name}><b>{name}</b></Child>";
+		let result: serde_json::Value = serde_json::from_str(&compile_with_options(program, "{\"generate\": \"ssr\"}")).unwrap();
+		let js = result["js"]["code"].as_str().unwrap();
+		assert!(js.contains("const Component = create_ssr_component(($$result, $$props, $$bindings, slots) => {"));
+		assert!(js.contains("let { name } = $$props;"));
+		assert!(js.contains("if ($$props.name === void 0 && $$bindings.name && name !== void 0) $$bindings.name(name);"));
+		assert!(js.contains("$$result.title = `<title>${escape(name)}</title>`;"));
+		assert!(js.contains("<h1${add_attribute(\"id\", name, 0)} class=\"a ${escape(name, true)}\">${escape(`Hi & ${name}`)}</h1>"));
+		assert!(js.contains("${each(items, (item) => `<li>${escape(item)}</li>`)}"));
+		assert!(js.contains("${validate_component(Child, \"Child\").$$render($$result, { a: name }, {}, { default: () => `<b>${escape(name)}</b>` })}"));
+
+		let result = std::panic::catch_unwind(|| compile_with_options("<h1>hi</h1>", "{\"generate\": \"html\"}"));
+		assert!(result.is_err());
+
+		// line breaks survive re-indenting, and a <textarea>'s value is its content
+		let program = "<script>let v;</script><pre>\n  x\n</pre><textarea class=\"a\" value={v}></textarea><textarea bind:value={v}/>";
+		let result: serde_json::Value = serde_json::from_str(&compile_with_options(program, "{\"generate\": \"ssr\"}")).unwrap();
+		let js = result["js"]["code"].as_str().unwrap();
+		assert!(js.contains("return `<pre>\\n  x\\n</pre><textarea class=\"a\">${escape(v)}</textarea><textarea>${escape(v)}</textarea>`;"));
+
+		assert_eq!(
+			compile_error("<script>let x; $: y = x * 2;</script>{y}", "{\"generate\": \"ssr\"}"),
+			"$: statements aren't supported yet when generating ssr code"
+		);
+	}
+
+	#[test]
+	fn hydration() {
+		let program = "<script>let name; let html;</script>\
+			<svelte:head><meta name=\"x\"></svelte:head>\
+			<h1 class=\"big\">Hello {name}!</h1> <p>static</p>\
+			{#if name}<span>yes</span>{/if}\
+			{@html html}";
+		let result: serde_json::Value = serde_json::from_str(&compile_with_options(program, "{\"hydratable\": true}")).unwrap();
+		let js = result["js"]["code"].as_str().unwrap();
+		let hash = crate::compiler::utils::strings::hash(program);
+		assert!(js.contains(&format!("const head_nodes = head_selector(\"svelte-{}\", document.head);", hash)));
+		assert!(js.contains("meta = claim_element(head_nodes, \"META\", { name: true });"));
+		assert!(js.contains("h1 = claim_element(nodes, \"H1\", { class: true });"));
+		assert!(js.contains("var h1_nodes = children(h1);\n\t\t\tt = claim_text(h1_nodes, `Hello ${/*name*/ ctx[0]}!`);\n\t\t\th1_nodes.forEach(detach);"));
+		assert!(js.contains("t3 = claim_space(nodes);"));
+		assert!(js.contains("p_nodes = children(p);\n\t\t\tt4 = claim_text(p_nodes, \"static\");"));
+		assert!(js.contains("if (if_block) if_block.l(nodes);"));
+		assert!(js.contains("html_tag = claim_html_tag(nodes, false);"));
+		assert!(!compile_js(program).contains("claim_"));
+
+		let result: serde_json::Value = serde_json::from_str(&compile_with_options(program, "{\"generate\": \"ssr\", \"hydratable\": true}")).unwrap();
+		let js = result["js"]["code"].as_str().unwrap();
+		assert!(js.contains(&format!("$$result.head += `<!-- HEAD_svelte-{}_START --><meta name=\"x\"><!-- HEAD_svelte-{}_END -->`;", hash, hash)));
+		assert!(js.contains("<!-- HTML_TAG_START -->${html}<!-- HTML_TAG_END -->"));
+	}
+
+	#[test]
+	fn dev() {
+		let program = "<script>export let name; export let count = 0; let items = [];</script>\n<h1 class=\"x\">Hi {name}</h1>\n{#each items as item (item.id)}<p>{item.text}</p>{/each}";
+		let result: serde_json::Value = serde_json::from_str(&compile_with_options(program, "{\"dev\": true, \"filename\": \"App.svelte\"}")).unwrap();
+		let js = result["js"]["code"].as_str().unwrap();
+		assert!(js.contains("class Component extends SvelteComponentDev {\n\tconstructor(options) {\n\t\tsuper(options);"));
+		assert!(js.contains("dispatch_dev(\"SvelteRegisterComponent\", {"));
+		assert!(js.contains("dispatch_dev(\"SvelteRegisterBlock\", {\n\t\tblock,\n\t\tid: create_fragment.name,\n\t\ttype: \"component\","));
+		assert!(js.contains("const file = \"App.svelte\";"));
+		assert!(js.contains("attr_dev(h1, \"class\", \"x\");\n\t\t\tadd_location(h1, file, 1, 0, 72);"));
+		assert!(js.contains("insert_dev(target, h1, anchor);"));
+		assert!(js.contains("set_data_dev(t1, `Hi ${/*name*/ ctx[0]}`);"));
+		assert!(js.contains("validate_each_keys(ctx, each1_value, get_each_context, each1_get_key);"));
+		assert!(js.contains("console.warn(\"<Component> was created without expected prop 'name'\");"));
+		assert!(!js.contains("expected prop 'count'"));
+		assert!(js.contains("$$self.$capture_state = () => ({ name, count, items });"));
+		assert!(js.contains("if ('items' in $$props) $$invalidate(2, items = $$props.items);"));
+		assert!(js.contains("get name() {\n\t\tthrow new Error("));
+
+		let js = compile_js(program);
+		assert!(!js.contains("_dev"));
+		assert!(!js.contains("add_location"));
+
+		// every dev block declares `block`, and they all point at `file`
+		let program = "<script>import Block from './Block.svelte';</script><Block/><file></file>";
+		let result: serde_json::Value = serde_json::from_str(&compile_with_options(program, "{\"dev\": true}")).unwrap();
+		let js = result["js"]["code"].as_str().unwrap();
+		assert!(js.contains("block1 = new Block({"));
+		assert!(js.contains("add_location(file1, file, 0, "));
+	}
+
+	#[test]
+	fn each_block_dependencies() {
+		let js = compile_js("<script>let items = []; let v; let w;</script>{#each items as item}<p>{item}</p>{/each}{v}{w}");
+		assert!(js.contains("if (dirty & /*items*/ 1) {"));
+		assert!(!js.contains("/*items, v, w*/"));
+
+		let js = compile_js("<script>let items = []; let v; let w;</script>{#each items as item (item)}<p>{item} {v}</p>{/each}{#each items as a}{#each a as b}{w}{/each}{/each}");
+		assert!(js.contains("if (dirty & /*items, v*/ 3) {"));
+		assert!(js.contains("if (dirty & /*items, w*/ 5) {"));
+	}
+
+	#[test]
+	fn slots() {
+		let js = compile_js("<script>let item = 1;</script>\
+			<slot name=\"header\" {item} extra={item}><p>fallback</p></slot>\
+			{#if $$slots.footer}<footer><slot name=\"footer\"/></footer>{/if}");
+		assert!(js.contains("const get_header_slot_changes = dirty => ({ item: dirty & /*item*/ 1, extra: dirty & /*item*/ 1 });"));
+		assert!(js.contains("const get_header_slot_context = ctx => ({ item: /*item*/ ctx[0], extra: /*item*/ ctx[0] });"));
+		assert!(js.contains("const header_slot = create_slot(header_slot_template, ctx, /*$$scope*/ ctx[2], get_header_slot_context);"));
+		assert!(js.contains("const header_slot_or_fallback = header_slot || fallback_block(ctx);"));
+		assert!(js.contains("update_slot_base(header_slot, header_slot_template, ctx, /*$$scope*/ ctx[2], !current ? get_all_dirty_from_scope(/*$$scope*/ ctx[2]) : get_slot_changes(header_slot_template, /*$$scope*/ ctx[2], dirty, get_header_slot_changes), get_header_slot_context);"));
+		assert!(js.contains("if (/*$$slots*/ ctx[1].footer) return 0;"));
+		assert!(js.contains("const $$slots = compute_slots(slots);"));
+
+		let js = compile_js("<script>import Child from './Child.svelte'; let name;</script>\
+			<Child let:item><p>{item} {name}</p><h1 slot=\"header\">{name}</h1>\
+			<svelte:fragment slot=\"footer\" let:extra={e}>{e}</svelte:fragment></Child>");
+		assert!(js.contains("default: [create_default_slot, ({ item }) => ({ 1: item }), ({ item }) => item ? 2 : 0],"));
+		assert!(js.contains("header: [create_header_slot],"));
+		assert!(js.contains("footer: [create_footer_slot, ({ extra: e }) => ({ 2: e }), ({ extra }) => extra ? 4 : 0]"));
+		assert!(js.contains("$$scope: { ctx }"));
+		assert!(js.contains("child_changes.$$scope = { dirty, ctx };"));
+
+		// slot contents and fallbacks only update when something they read changes
+		let js = compile_js("<script>import Child from './Child.svelte'; let name; let other;</script>\
+			<Child let:item><p>{item} {name}</p></Child><slot><p>{other}</p></slot>{other}");
+		assert!(js.contains("if (dirty & /*name*/ 1) {\n\t\t\t\tchild_changes.$$scope = { dirty, ctx };"));
+		assert!(js.contains("if (default_slot_or_fallback && default_slot_or_fallback.p && (!current || dirty & /*other*/ 2)) {"));
+	}
+
+	#[test]
+	fn unsupported_bindings() {
+		for program in ["<script>let x;</script><input bind:value={x}>", "<script>import Child from './Child.svelte'; let x;</script><Child bind:value={x}/>"] {
+			assert_eq!(compile_error(program, "{}"), "bind: is only supported on <svelte:window> for now");
+		}
+	}
+
+	#[test]
+	fn unsupported_directives() {
+		assert_eq!(compile_error("<script>let x;</script><div class:active={x}></div>", "{}"), "class: directives aren't supported yet");
+		assert_eq!(compile_error("<script>let x;</script><div style:color={x}></div>", "{}"), "style: directives aren't supported yet");
+		assert_eq!(compile_error("<script>function f() {}</script><div use:f></div>", "{}"), "use: directives aren't supported yet");
+		// namespaced attributes aren't directives
+		assert!(compile_js("<svg><use xlink:href=\"#a\"/></svg>").contains("xlink_attr(use, \"xlink:href\", \"#a\");"));
+	}
+
+	#[test]
+	fn head() {
+		let js = compile_js("<script>let name;</script><svelte:head><title>Hi {name}</title><meta name=\"a\" content={name}></svelte:head><p>x</p>");
+		assert!(js.contains("document.title = title_value = `Hi ${/*name*/ ctx[0]}`;"));
+		assert!(js.contains("if (dirty & /*name*/ 1 && title_value !== (title_value = `Hi ${/*name*/ ctx[0]}`)) {\n\t\t\t\tdocument.title = title_value;"));
+		assert!(js.contains("append(document.head, meta);"));
+		// head nodes aren't inside our target, so they always need removing
+		assert!(js.contains("if (detaching) {\n\t\t\t\tdetach(p);\n\t\t\t}\n\t\t\tdetach(meta);"));
+
+		// scripts and styles in the head are ordinary elements, with their text left alone
+		let program = "<svelte:head><script src=\"/a.js\"></script><style>a > b { color: red }</style></svelte:head>";
+		let js = compile_js(program);
+		assert!(js.contains("script = element(\"script\");\n\t\t\tattr(script, \"src\", \"/a.js\");"));
+		assert!(js.contains("append(document.head, script);"));
+		assert!(js.contains("style.textContent = \"a > b { color: red }\";"));
+		assert!(js.contains("append(document.head, style);"));
+		let result: serde_json::Value = serde_json::from_str(&compile_with_options(program, "{\"generate\": \"ssr\"}")).unwrap();
+		let js = result["js"]["code"].as_str().unwrap();
+		assert!(js.contains("$$result.head += `<script src=\"/a.js\"></script><style>a > b { color: red }</style>`;"));
+	}
+
+	#[test]
+	fn await_blocks() {
+		for options in ["{}", "{\"generate\": \"ssr\"}"] {
+			let program = "<script>let p;</script>{#await p}...{:then value}{@const x = value * 2}{x}{:catch error}{error}{/await}";
+			assert_eq!(compile_error(program, options), "{#await} blocks aren't supported yet");
+		}
+	}
+
+	#[test]
+	fn self_references() {
+		for options in ["{}", "{\"generate\": \"ssr\"}"] {
+			for program in ["<svelte:self/>", "<div><svelte:self/></div>", "{#key x}<svelte:self/>{/key}"] {
+				let program = format!("<script>let x;</script>{}", program);
+				assert_eq!(
+					compile_error(&program, options),
+					"<svelte:self> components can only exist inside {#if} blocks, {#each} blocks, or slots passed to components"
+				);
+			}
+			for program in ["{#if x}<svelte:self/>{/if}", "{#each x as y}<p><svelte:self/></p>{/each}", "<Child><svelte:self/></Child>"] {
+				let program = format!("<script>import Child from './Child.svelte'; let x;</script>{}", program);
+				compile_with_options(&program, options);
+			}
+		}
+	}
+	#[test]
+	fn large_contexts() {
+		// past 31 entries in ctx, dirty gets split into 31-bit words
+		let variables = (0..70).map(|i| format!("let v{} = {};", i, i)).collect::<Vec<String>>().join(" ");
+		let program = format!("<script>import Child from './Child.svelte'; {}</script>\
+			<p>{{v0}} {{v40}}</p>{{#if v69}}<b>{{v33}}</b>{{/if}}<Child let:x>{{x}}</Child>", variables);
+		let js = compile_js(&program);
+		assert!(js.contains("p(ctx, dirty) {"));
+		assert!(js.contains("if (dirty[0] & /*v0*/ 1 | dirty[1] & /*v40*/ 512) set_data(t, `${/*v0*/ ctx[0]} ${/*v40*/ ctx[40]}`);"));
+		assert!(js.contains("if (dirty[1] & /*v33*/ 4) set_data(t3, /*v33*/ ctx[33]);"));
+		assert!(js.contains("let current_if_block_type = select_block_type(ctx, [-1, -1, -1]);"));
+		assert!(js.contains("default: [create_default_slot, ({ x }) => ({ 70: x }), ({ x }) => [0, 0, x ? 256 : 0]]"));
+		assert!(js.contains("init(this, options, instance, create_fragment, safe_not_equal, {}, null, [-1, -1, -1]);"));
+
+		let js = compile_js("<script>let a;</script>{a}");
+		assert!(js.contains("p(ctx, [dirty]) {"));
+		assert!(js.contains("init(this, options, instance, create_fragment, safe_not_equal, {});"));
+	}
+
+	#[test]
+	fn reactivity() {
+		let js = compile_js("<script>export let step = 1; let count = 0; let name = 'x';\n\
+			const inc = () => count += step;\n\
+			function rename() { name = name + '!'; }\n\
+			</script><button on:click={inc}>{count}</button><p>{name}</p>{#if count > 10}<b>{name}</b>{/if}");
+		// assignments to anything in ctx tell the component what changed
+		assert!(js.contains("let { step = 1 } = $$props;"));
+		assert!(js.contains("const inc = () => $$invalidate(1, count += step);"));
+		assert!(js.contains("function rename() { $$invalidate(2, name = name + '!'); }"));
+		assert!(js.contains("if ('step' in $$props) $$invalidate(0, step = $$props.step);"));
+		assert!(js.contains("return [step, count, name, inc, rename];"));
+		assert!(js.contains("init(this, options, instance, create_fragment, safe_not_equal, { step: 0 });"));
+
+		// and each block only updates what depends on it
+		assert!(js.contains("p(ctx, [dirty]) {\n\t\t\tif (dirty & /*count*/ 2) set_data(t, /*count*/ ctx[1]);\n\t\t\tif (dirty & /*name*/ 4) set_data(t1, /*name*/ ctx[2]);"));
+		assert!(js.contains("if (/*count*/ ctx[1] > 10) return create_if_block;"));
+		assert!(js.contains("if_block.p(ctx, dirty);"));
+		assert!(js.contains("p(new_ctx, dirty) {\n\t\t\tctx = new_ctx;\n\t\t\tif (dirty & /*name*/ 4) set_data(t2, /*name*/ ctx[2]);"));
+		assert!(js.contains("dispose = listen(button, \"click\", /*inc*/ ctx[3]);"));
+	}
+
+	#[test]
+	fn scoped_invalidation() {
+		let js = compile_js("<script>let count = 0; let a = 1; let b = 2; let o = {};\n\
+			function f(count) { count = 5; }\n\
+			function g() { let count = 1; count++; var a; a = 3; }\n\
+			const h = count => count++;\n\
+			const k = (x, { count }) => { count += 1; b = x; };\n\
+			function swap() { [a, b] = [b, a]; }\n\
+			function pick() { ({ a, b: count } = o); }\n\
+			function shadowed() { let a; [a, b] = [1, 2]; }\n\
+			try { b = 1; } catch (count) { count = 2; }\n\
+			</script><button on:click={() => { let count = 0; count++; b++; }}>{count}{a}{b}{o}</button>");
+		assert!(js.contains("function f(count) { count = 5; }"));
+		assert!(js.contains("function g() { let count = 1; count++; var a; a = 3; }"));
+		assert!(js.contains("const h = count => count++;"));
+		assert!(js.contains("const k = (x, { count }) => { count += 1; $$invalidate(2, b = x); };"));
+		assert!(js.contains("function swap() { ([a, b] = [b, a], $$invalidate(1, a), $$invalidate(2, b)); }"));
+		assert!(js.contains("function pick() { (({ a, b: count } = o, $$invalidate(1, a), $$invalidate(0, count))); }"));
+		assert!(js.contains("function shadowed() { let a; ([a, b] = [1, 2], $$invalidate(2, b)); }"));
+		assert!(js.contains("try { $$invalidate(2, b = 1); } catch (count) { count = 2; }"));
+		assert!(js.contains("const click_handler = () => { let count = 0; count++; $$invalidate(2, b++, b); };"));
+	}
+}
@@ -15,20 +15,108 @@ impl Import {
 	}
 }
 
-lazy_static! {
-	pub static ref SPACE: Import = Import::new("svelte/internal", "space");
-	pub static ref TEXT: Import = Import::new("svelte/internal", "text");
-	pub static ref ELEMENT: Import = Import::new("svelte/internal", "element");
-	pub static ref NOOP: Import = Import::new("svelte/internal", "noop");
-	pub static ref COMPONENT: Import = Import::new("svelte/internal", "SvelteComponent");
-	pub static ref SAFE_NOT_EQUAL: Import = Import::new("svelte/internal", "safe_not_equal");
-	pub static ref INSERT: Import = Import::new("svelte/internal", "insert");
-	pub static ref DETACH: Import = Import::new("svelte/internal", "detach");
-	pub static ref INIT: Import = Import::new("svelte/internal", "init");
-	pub static ref ATTR: Import = Import::new("svelte/internal", "attr");
-	pub static ref DESTROY_COMPONENT: Import = Import::new("svelte/internal", "destroy_component");
-	pub static ref TRANSITION_IN: Import = Import::new("svelte/internal", "transition_in");
-	pub static ref TRANSITION_OUT: Import = Import::new("svelte/internal", "transition_out");
-	pub static ref MOUNT_COMPONENT: Import = Import::new("svelte/internal", "mount_component");
-	pub static ref CREATE_COMPONENT: Import = Import::new("svelte/internal", "create_component");
+// Declares a static Import for each svelte/internal function, along with a list of all of their names
+macro_rules! imports {
+	($($name:ident: $function:expr),* $(,)?) => {
+		$(lazy_static! {
+			pub static ref $name: Import = Import::new("svelte/internal", $function);
+		})*
+
+		pub const FUNCTIONS: &'static [&'static str] = &[$($function),*];
+	};
+}
+
+imports! {
+	SPACE: "space",
+	TEXT: "text",
+	ELEMENT: "element",
+	SVG_ELEMENT: "svg_element",
+	CLAIM_SPACE: "claim_space",
+	CLAIM_TEXT: "claim_text",
+	CLAIM_ELEMENT: "claim_element",
+	CLAIM_SVG_ELEMENT: "claim_svg_element",
+	CLAIM_COMMENT: "claim_comment",
+	CLAIM_COMPONENT: "claim_component",
+	CLAIM_HTML_TAG: "claim_html_tag",
+	CHILDREN: "children",
+	HEAD_SELECTOR: "head_selector",
+	NOOP: "noop",
+	COMPONENT: "SvelteComponent",
+	CUSTOM_ELEMENT: "SvelteElement",
+	ATTRIBUTE_TO_OBJECT: "attribute_to_object",
+	SAFE_NOT_EQUAL: "safe_not_equal",
+	NOT_EQUAL: "not_equal",
+	FLUSH: "flush",
+	INSERT: "insert",
+	DETACH: "detach",
+	INIT: "init",
+	ATTR: "attr",
+	XLINK_ATTR: "xlink_attr",
+	SET_CUSTOM_ELEMENT_DATA: "set_custom_element_data",
+	DESTROY_COMPONENT: "destroy_component",
+	TRANSITION_IN: "transition_in",
+	TRANSITION_OUT: "transition_out",
+	MOUNT_COMPONENT: "mount_component",
+	CREATE_COMPONENT: "create_component",
+	EMPTY: "empty",
+	APPEND: "append",
+	SET_DATA: "set_data",
+	GROUP_OUTROS: "group_outros",
+	CHECK_OUTROS: "check_outros",
+	ADD_RENDER_CALLBACK: "add_render_callback",
+	CREATE_BIDIRECTIONAL_TRANSITION: "create_bidirectional_transition",
+	CREATE_IN_TRANSITION: "create_in_transition",
+	CREATE_OUT_TRANSITION: "create_out_transition",
+	CREATE_ANIMATION: "create_animation",
+	FIX_POSITION: "fix_position",
+	ADD_TRANSFORM: "add_transform",
+	DESTROY_EACH: "destroy_each",
+	UPDATE_KEYED_EACH: "update_keyed_each",
+	DESTROY_BLOCK: "destroy_block",
+	OUTRO_AND_DESTROY_BLOCK: "outro_and_destroy_block",
+	FIX_AND_DESTROY_BLOCK: "fix_and_destroy_block",
+	FIX_AND_OUTRO_AND_DESTROY_BLOCK: "fix_and_outro_and_destroy_block",
+	CREATE_SLOT: "create_slot",
+	UPDATE_SLOT_BASE: "update_slot_base",
+	GET_SLOT_CHANGES: "get_slot_changes",
+	GET_ALL_DIRTY_FROM_SCOPE: "get_all_dirty_from_scope",
+	COMPUTE_SLOTS: "compute_slots",
+	ASSIGN: "assign",
+	GET_SPREAD_UPDATE: "get_spread_update",
+	GET_SPREAD_OBJECT: "get_spread_object",
+	EXCLUDE_INTERNAL_PROPS: "exclude_internal_props",
+	COMPUTE_REST_PROPS: "compute_rest_props",
+	SET_ATTRIBUTES: "set_attributes",
+	SET_SVG_ATTRIBUTES: "set_svg_attributes",
+	SET_CUSTOM_ELEMENT_DATA_MAP: "set_custom_element_data_map",
+	LISTEN: "listen",
+	RUN_ALL: "run_all",
+	BUBBLE: "bubble",
+	PREVENT_DEFAULT: "prevent_default",
+	STOP_PROPAGATION: "stop_propagation",
+	SELF: "self",
+	HTML_TAG: "HtmlTag",
+	TRUSTED: "trusted",
+	CREATE_SSR_COMPONENT: "create_ssr_component",
+	ESCAPE: "escape",
+	ESCAPE_OBJECT: "escape_object",
+	ADD_ATTRIBUTE: "add_attribute",
+	EACH: "each",
+	VALIDATE_COMPONENT: "validate_component",
+	MISSING_COMPONENT: "missing_component",
+	SPREAD: "spread",
+	IS_VOID: "is_void",
+	COMPONENT_DEV: "SvelteComponentDev",
+	DISPATCH_DEV: "dispatch_dev",
+	INSERT_DEV: "insert_dev",
+	APPEND_DEV: "append_dev",
+	DETACH_DEV: "detach_dev",
+	ATTR_DEV: "attr_dev",
+	LISTEN_DEV: "listen_dev",
+	SET_DATA_DEV: "set_data_dev",
+	ADD_LOCATION: "add_location",
+	VALIDATE_EACH_ARGUMENT: "validate_each_argument",
+	VALIDATE_EACH_KEYS: "validate_each_keys",
+	VALIDATE_DYNAMIC_ELEMENT: "validate_dynamic_element",
+	VALIDATE_VOID_DYNAMIC_ELEMENT: "validate_void_dynamic_element",
 }
\ No newline at end of file
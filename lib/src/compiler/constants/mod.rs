@@ -17,6 +17,8 @@ pub const CREATE_FRAGMENT: &'static str = "create_fragment";
 pub const TARGET: &'static str = "target";
 pub const ANCHOR: &'static str = "anchor";
 pub const DETACHING: &'static str = "detaching";
+pub const NODES: &'static str = "nodes";
+pub const HEAD_NODES: &'static str = "head_nodes";
 pub const NULL: &'static str = "null";
 pub const INSTANCE: &'static str = "instance";
 pub const CURRENT: &'static str = "current";
@@ -26,5 +28,41 @@ pub const GLOBAL_FRAGMENT: &'static str = "$$.fragment";
 pub const GLOBAL_PROPS: &'static str = "$$props";
 pub const GLOBAL_INVALIDATE: &'static str = "$$invalidate";
 pub const PROPS: &'static str = "props";
-pub const CLASS: &'static str = "class";
-pub const TEXT_CONTENT: &'static str = "textContent";
\ No newline at end of file
+pub const TEXT_CONTENT: &'static str = "textContent";
+pub const INNER_HTML: &'static str = "innerHTML";
+pub const CTX: &'static str = "ctx";
+pub const NEW_CTX: &'static str = "new_ctx";
+pub const DIRTY: &'static str = "dirty";
+pub const CREATE_IF_BLOCK: &'static str = "create_if_block";
+pub const CREATE_ELSE_BLOCK: &'static str = "create_else_block";
+pub const CREATE_DYNAMIC_ELEMENT: &'static str = "create_dynamic_element";
+pub const CREATE_EACH_BLOCK: &'static str = "create_each_block";
+pub const CREATE_KEY_BLOCK: &'static str = "create_key_block";
+pub const GET_EACH_CONTEXT: &'static str = "get_each_context";
+pub const SELECT_BLOCK_TYPE: &'static str = "select_block_type";
+pub const CHILD_CTX: &'static str = "child_ctx";
+pub const GET_KEY: &'static str = "get_key";
+pub const RECT: &'static str = "rect";
+pub const STOP_ANIMATION: &'static str = "stop_animation";
+pub const INDEX: &'static str = "i";
+pub const FIRST: &'static str = "first";
+pub const GLOBAL_SLOTS: &'static str = "$$slots";
+pub const GLOBAL_SCOPE: &'static str = "$$scope";
+pub const SLOTS: &'static str = "slots";
+pub const DEFAULT_SLOT: &'static str = "default";
+pub const FALLBACK_BLOCK: &'static str = "fallback_block";
+pub const GLOBAL_NEW_PROPS: &'static str = "$$new_props";
+pub const GLOBAL_REST_PROPS: &'static str = "$$restProps";
+pub const OMIT_PROPS_NAMES: &'static str = "omit_props_names";
+pub const MOUNTED: &'static str = "mounted";
+pub const DISPOSE: &'static str = "dispose";
+pub const COMPONENT: &'static str = "Component";
+pub const SVELTE_COMPONENT: &'static str = "svelte:component";
+pub const SVELTE_SELF: &'static str = "svelte:self";
+pub const SVELTE_OPTIONS: &'static str = "svelte:options";
+pub const OPTIONS: &'static str = "options";
+pub const FILE: &'static str = "file";
+pub const BLOCK: &'static str = "block";
+pub const GLOBAL_RESULT: &'static str = "$$result";
+pub const GLOBAL_BINDINGS: &'static str = "$$bindings";
+pub const CSS: &'static str = "css";
@@ -1,4 +1,5 @@
 pub mod compiler;
+pub mod parser;
 pub mod renderer;
 pub mod constants;
 pub mod utils;
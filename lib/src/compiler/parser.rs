@@ -0,0 +1,263 @@
+// A small HTML parser that knows about svelte's syntax: mustaches in text and attributes,
+// directives (on:click|once, transition:fade|local, etc.), spread attributes and so on.
+// Blocks ({#if}...{/if}) are left in the text, they're grouped later on by the compiler.
+use crate::compiler::compiler::Attribute;
+use crate::compiler::utils::script;
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+
+lazy_static! {
+	static ref VOID_ELEMENTS: HashSet<&'static str> = vec![
+		"area", "base", "br", "col", "command", "embed", "hr", "img", "input", "keygen", "link",
+		"meta", "param", "source", "track", "wbr",
+	].into_iter().collect();
+
+	// elements whose contents we don't parse at all
+	static ref RAW_TEXT_ELEMENTS: HashSet<&'static str> = vec!["script", "style"].into_iter().collect();
+}
+
+pub fn is_void(name: &str) -> bool {
+	return VOID_ELEMENTS.contains(&*name.to_lowercase());
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+	Element(Element),
+	Text(String),
+	Comment(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Element {
+	pub name: String,
+	pub start: usize, // where the `<` is in the source
+	pub attributes: Vec<Attribute>,
+	pub children: Vec<Node>,
+}
+
+pub fn parse(source: &str) -> Result<Vec<Node>, String> {
+	let mut parser = Parser { source, index: 0 };
+	let nodes = parser.parse_nodes(None)?;
+	if parser.index < source.len() {
+		return Err(format!("Unexpected closing tag at {}", parser.index));
+	}
+	return Ok(nodes);
+}
+
+struct Parser<'a> {
+	source: &'a str,
+	index: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn remaining(&self) -> &'a str {
+		return &self.source[self.index..];
+	}
+
+	fn peek(&self) -> Option<char> {
+		return self.remaining().chars().next();
+	}
+
+	fn eat(&mut self, value: &str) -> bool {
+		if self.remaining().starts_with(value) {
+			self.index += value.len();
+			return true;
+		}
+		return false;
+	}
+
+	fn skip_whitespace(&mut self) {
+		while let Some(c) = self.peek() {
+			if !c.is_whitespace() {
+				break;
+			}
+			self.index += c.len_utf8();
+		}
+	}
+
+	fn error<T>(&self, message: &str) -> Result<T, String> {
+		return Err(format!("{} at {}", message, self.index));
+	}
+
+	// Returns the end of the mustache that starts at `start`
+	fn find_mustache_end(&self, start: usize) -> Result<usize, String> {
+		return script::find_mustache_end(self.source, start)
+			.ok_or_else(|| format!("Unclosed mustache at {}", start));
+	}
+
+	fn at_tag_start(&self) -> bool {
+		let rest = self.remaining();
+		if rest.starts_with("<!--") || rest.starts_with("</") {
+			return true;
+		}
+		let mut chars = rest.chars();
+		return chars.next() == Some('<') && chars.next().is_some_and(|c| c.is_ascii_alphabetic());
+	}
+
+	fn parse_nodes(&mut self, parent: Option<&str>) -> Result<Vec<Node>, String> {
+		let mut nodes = vec![];
+		while self.index < self.source.len() {
+			if self.remaining().starts_with("</") {
+				match parent {
+					Some(_) => break,
+					None => return self.error("Unexpected closing tag"),
+				}
+			} else if self.eat("<!--") {
+				let end = match self.remaining().find("-->") {
+					Some(end) => end,
+					None => return self.error("Unclosed comment"),
+				};
+				nodes.push(Node::Comment(self.remaining()[..end].to_string()));
+				self.index += end + 3;
+			} else if self.at_tag_start() {
+				nodes.push(Node::Element(self.parse_element()?));
+			} else {
+				nodes.push(Node::Text(self.parse_text()?));
+			}
+		}
+		return Ok(nodes);
+	}
+
+	fn parse_text(&mut self) -> Result<String, String> {
+		let start = self.index;
+		while self.index < self.source.len() && !self.at_tag_start() {
+			if self.peek() == Some('{') {
+				self.index = self.find_mustache_end(self.index)?;
+			} else {
+				self.index += self.peek().unwrap().len_utf8();
+			}
+		}
+		return Ok(self.source[start..self.index].to_string());
+	}
+
+	fn parse_name(&mut self) -> String {
+		let start = self.index;
+		while let Some(c) = self.peek() {
+			if c.is_whitespace() || c == '>' || c == '/' || c == '=' || c == '{' {
+				break;
+			}
+			self.index += c.len_utf8();
+		}
+		return self.source[start..self.index].to_string();
+	}
+
+	fn parse_element(&mut self) -> Result<Element, String> {
+		let start = self.index;
+		self.eat("<");
+		let name = self.parse_name();
+		let attributes = self.parse_attributes()?;
+		let mut element = Element {
+			name,
+			start,
+			attributes,
+			children: vec![],
+		};
+
+		if self.eat("/>") {
+			return Ok(element);
+		}
+		if !self.eat(">") {
+			return self.error(&format!("Expected > to close <{}>", element.name));
+		}
+		if is_void(&element.name) {
+			return Ok(element);
+		}
+
+		let closing_tag = format!("</{}", element.name);
+		if RAW_TEXT_ELEMENTS.contains(&*element.name.to_lowercase()) {
+			let end = match self.remaining().find(&closing_tag) {
+				Some(end) => end,
+				None => return self.error(&format!("<{}> was left open", element.name)),
+			};
+			element.children.push(Node::Text(self.remaining()[..end].to_string()));
+			self.index += end;
+		} else {
+			element.children = self.parse_nodes(Some(&element.name))?;
+		}
+
+		if !self.eat(&closing_tag) {
+			return self.error(&format!("<{}> was left open", element.name));
+		}
+		self.skip_whitespace();
+		if !self.eat(">") {
+			return self.error(&format!("Expected > to close </{}>", element.name));
+		}
+		return Ok(element);
+	}
+
+	fn parse_attributes(&mut self) -> Result<Vec<Attribute>, String> {
+		let mut attributes = vec![];
+		loop {
+			self.skip_whitespace();
+			match self.peek() {
+				None => return self.error("Unexpected end of input"),
+				Some('>') => break,
+				Some('/') if self.remaining().starts_with("/>") => break,
+				Some('{') => {
+					// either a spread, {...props}, or a shorthand, {name}
+					let end = self.find_mustache_end(self.index)?;
+					let inner = self.source[self.index + 1..end - 1].trim();
+					if inner.starts_with("...") {
+						attributes.push(Attribute {
+							key: inner.to_string(),
+							value: None,
+						});
+					} else {
+						attributes.push(Attribute {
+							key: inner.to_string(),
+							value: Some(format!("{{{}}}", inner)),
+						});
+					}
+					self.index = end;
+				}
+				Some(_) => {
+					let key = self.parse_name();
+					if key.len() == 0 {
+						return self.error("Expected an attribute name");
+					}
+					let value = if self.eat("=") {
+						Some(self.parse_attribute_value()?)
+					} else {
+						None
+					};
+					attributes.push(Attribute { key, value });
+				}
+			}
+		}
+		return Ok(attributes);
+	}
+
+	fn parse_attribute_value(&mut self) -> Result<String, String> {
+		let start = self.index;
+		match self.peek() {
+			Some(quote) if quote == '"' || quote == '\'' => {
+				self.index += 1;
+				let start = self.index;
+				while let Some(c) = self.peek() {
+					if c == quote {
+						let value = self.source[start..self.index].to_string();
+						self.index += 1;
+						return Ok(value);
+					} else if c == '{' {
+						self.index = self.find_mustache_end(self.index)?;
+					} else {
+						self.index += c.len_utf8();
+					}
+				}
+				return self.error("Unclosed attribute value");
+			}
+			Some('{') => {
+				self.index = self.find_mustache_end(self.index)?;
+			}
+			_ => {
+				while let Some(c) = self.peek() {
+					if c.is_whitespace() || c == '>' || self.remaining().starts_with("/>") {
+						break;
+					}
+					self.index += c.len_utf8();
+				}
+			}
+		}
+		return Ok(self.source[start..self.index].to_string());
+	}
+}
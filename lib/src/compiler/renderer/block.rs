@@ -0,0 +1,65 @@
+// A block is a single `create_*` function, ex: create_fragment or create_if_block.
+// Each of these strings is the body of one of the methods the block returns.
+#[derive(Debug, Default)]
+pub struct Block {
+	pub name: String,
+	pub is_root: bool,
+	pub is_keyed: bool, // keyed each blocks get a key and a `first` node to help with moves
+	pub variables: String,
+	pub creator: String,
+	pub claimer: String, // l(nodes), which adopts the server's html instead of creating nodes, when hydratable
+	pub mounter: String,
+	pub p: String,
+	pub measure: String, // r(), f() and a() are only used by animated each blocks
+	pub fix: String,
+	pub animate: String,
+	pub transition_in: String,
+	pub transition_out: String,
+	pub detach: String, // plain DOM nodes, which only get removed when we're detaching
+	pub destroy: String, // everything else
+	pub listeners: Vec<String>, // event listeners, which get added on mount and disposed of on destroy
+	pub context_getter: Option<String>, // adds any {@const}s to ctx, ex: get_if_block_context
+	pub has_intros: bool,
+	pub has_outros: bool,
+}
+
+impl Block {
+	pub fn new(name: &str) -> Block {
+		Block {
+			name: name.to_string(),
+			..Default::default()
+		}
+	}
+
+	// Code that runs however we got our nodes, ex: setting attributes
+	pub fn create(&mut self, code: &str) {
+		self.creator.push_str(code);
+		self.claimer.push_str(code);
+	}
+
+	// A node that c() creates, but l(nodes) claims from what's already there
+	pub fn create_node(&mut self, create: &str, claim: &str) {
+		self.creator.push_str(create);
+		self.claimer.push_str(claim);
+	}
+
+	pub fn is_empty(&self) -> bool {
+		return self.variables.len() == 0
+			&& self.creator.len() == 0
+			&& self.mounter.len() == 0
+			&& self.p.len() == 0
+			&& self.transition_in.len() == 0
+			&& self.transition_out.len() == 0
+			&& self.detach.len() == 0
+			&& self.destroy.len() == 0
+			&& self.listeners.len() == 0;
+	}
+
+	pub fn has_transitions(&self) -> bool {
+		return self.has_intros || self.has_outros;
+	}
+
+	pub fn has_animation(&self) -> bool {
+		return self.animate.len() > 0;
+	}
+}
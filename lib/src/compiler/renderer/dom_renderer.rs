@@ -2,38 +2,51 @@
 use crate::compiler::compiler::CompileResult;
 use std::collections::HashMap;
 use super::renderer::Renderer;
-use crate::compiler::compiler::{Fragment, FragmentType, Compiler};
-use crate::compiler::{constants, constants::imports};
-use crate::compiler::utils::{script, strings, strings::HandlebarType};
+use super::block::Block;
+use crate::compiler::compiler::{Attribute, Fragment, FragmentType, Compiler, Directive, DirectiveType};
+use crate::compiler::{constants, constants::imports, constants::imports::Import, parser};
+use crate::compiler::utils::{entities, namespaces, script, strings, strings::HandlebarType};
+use crate::compiler::utils::attribute_lookup::{self, AttributeLookup};
+use crate::compiler::utils::fix_attribute_casing::fix_attribute_casing;
+
+#[derive(Debug)]
+struct ContextVariable {
+	index: usize, // where this lives in ctx
+	dependencies: Vec<usize>, // which instance variables need to change for this to change
+}
 
 #[derive(Debug)]
 pub struct DomRenderer<'a> {
 	pub compiler: &'a mut Compiler,
-	extra_variables: HashMap<String, Vec<String>>,
+	blocks: Vec<String>, // every block other than create_fragment, in the order they should be declared
+	user_imports: Vec<String>,
+	module_script: String,
+	instance_script: String,
+	variables: Vec<String>, // everything the instance returns, in order
+	props: Vec<String>,
+	contexts: Vec<HashMap<String, ContextVariable>>,
+	context_count: usize,
+	dirty_words: usize, // dirty is split into 31-bit words once ctx has more than 31 entries, ex: dirty[1] & 1
+	read_dependencies: Vec<usize>, // every dependency read by expressions and dirty checks so far, see render_each_block
+	internal_names: HashMap<String, String>, // names only some blocks need, see get_internal_name
+	animated_element: Option<String>,
+	has_slots: bool, // whether this component renders any <slot>s, or checks $$slots
+	uses_slots_object: bool, // whether $$slots is used anywhere
+	uses_props: bool, // whether $$props is used anywhere
+	uses_rest_props: bool, // whether $$restProps is used anywhere
+	handlers: Vec<String>, // event handlers hoisted out of the markup and into the instance
 }
 
 impl Renderer for DomRenderer<'_> {
 	fn render_fragments(&mut self, fragments: &Vec<Fragment>) -> CompileResult {
-		// grab the pieces for create_fragment
-		let variables = &self.render_variables(fragments);
-		let creator = &self.render_fragments_creator(None, fragments);
-		let mounter = &self.render_mounter(None, fragments);
-		let p = &self.render_p(None, fragments);
-		let transition_in = &self.render_transition_in(None, fragments);
-		let transition_out = &self.render_transition_out(None, fragments);
-		let detach = &self.render_detach(None, fragments);
+		// figure out what our scripts declare before we look at the markup that uses them
+		self.extract_scripts(fragments);
 
 		// create create_fragment
-		let create_fragment = &self.render_create_fragment(
-			fragments,
-			variables,
-			creator,
-			mounter,
-			p,
-			transition_in,
-			transition_out,
-			detach
-		);
+		let mut block = Block::new(constants::CREATE_FRAGMENT);
+		block.is_root = true;
+		self.render_children(&mut block, None, fragments);
+		let create_fragment = &self.render_block(&block);
 
 		// define component
 		let component = &self.render_component(fragments, create_fragment.len() > 0);
@@ -46,7 +59,27 @@ impl Renderer for DomRenderer<'_> {
 		code.push_str(&constants::LITHE_COMMENT);
 		code.push_str("\n");
 		code.push_str(&self.compiler.render_imports());
+		for import in &self.user_imports {
+			code.push_str(import);
+			code.push_str("\n");
+		}
 		code.push_str("\n");
+		if self.compiler.options.dev {
+			// where add_location says our elements came from
+			let file = match &self.compiler.options.filename {
+				Some(filename) => strings::to_js_string(filename),
+				None => "undefined".to_string(),
+			};
+			code.push_str(&format!("const {} = {};\n\n", constants::FILE, file));
+		}
+		if self.module_script.len() > 0 {
+			code.push_str(&self.module_script);
+			code.push_str("\n\n");
+		}
+		for block in &self.blocks {
+			code.push_str(block);
+			code.push_str("\n");
+		}
 		code.push_str(&create_fragment);
 		code.push_str("\n");
 		if instance.len() > 0 {
@@ -54,7 +87,7 @@ impl Renderer for DomRenderer<'_> {
 			code.push_str("\n");
 		}
 		code.push_str(&component.trim());
-		
+
 		// TODO: fill out the rest of this return value
 		let mut result = CompileResult::default();
 		result.js.code = Some(code);
@@ -63,43 +96,330 @@ impl Renderer for DomRenderer<'_> {
 }
 
 impl DomRenderer<'_> {
-	pub fn new(compiler: &mut Compiler) -> DomRenderer {
+	pub fn new(compiler: &mut Compiler) -> DomRenderer<'_> {
 		DomRenderer {
 			compiler,
-			extra_variables: HashMap::new()
+			blocks: vec![],
+			user_imports: vec![],
+			module_script: String::new(),
+			instance_script: String::new(),
+			variables: vec![],
+			props: vec![],
+			contexts: vec![],
+			context_count: 0,
+			dirty_words: 1,
+			read_dependencies: vec![],
+			internal_names: HashMap::new(),
+			animated_element: None,
+			has_slots: false,
+			uses_slots_object: false,
+			uses_props: false,
+			uses_rest_props: false,
+			handlers: vec![],
 		}
 	}
 
-	fn render_instance(&mut self, fragments: &Vec<Fragment>) -> String {
-		let mut content = String::new();
-		let mut variables: Vec<String> = vec![];
-
+	fn extract_scripts(&mut self, fragments: &Vec<Fragment>) {
 		for fragment in fragments {
-			for fragment in fragment.into_iter() {
-				if fragment.ttype == FragmentType::Script {
-					// looks like we want to parse the script, extract the variables/exports, and move on
-					todo!();
+			if fragment.ttype != FragmentType::Script {
+				continue;
+			}
+
+			let data = fragment.data.as_deref().unwrap_or("");
+			let (imports, body) = script::extract_imports(data);
+			self.user_imports.extend(imports);
+
+			let is_module = fragment.attributes.iter()
+				.any(|attribute| attribute.key == "context" && attribute.value.as_deref() == Some("module"));
+			if is_module {
+				// module-level code is shared between instances, so it stays as-is
+				self.module_script = body.trim().to_string();
+			} else {
+				self.instance_script = body;
+			}
+		}
+
+		let declarations = script::extract_declarations(&self.instance_script);
+		self.variables = declarations.iter().map(|declaration| declaration.name.clone()).collect();
+		self.props = script::extract_exports(&self.instance_script);
+
+		// $$props and $$restProps need to be kept up to date, so they get their own spot in ctx
+		self.uses_props = self.uses_global(fragments, constants::GLOBAL_PROPS);
+		self.uses_rest_props = self.uses_global(fragments, constants::GLOBAL_REST_PROPS);
+		if self.uses_props {
+			self.variables.push(constants::GLOBAL_PROPS.to_string());
+		}
+		if self.uses_rest_props {
+			self.variables.push(constants::GLOBAL_REST_PROPS.to_string());
+		}
+
+		// components with slots get handed their contents through $$props
+		self.uses_slots_object = self.uses_global(fragments, constants::GLOBAL_SLOTS);
+		self.has_slots = self.uses_slots_object
+			|| self.find_in_markup(fragments, &|fragment| fragment.ttype == FragmentType::Slot);
+		if self.uses_slots_object {
+			self.variables.push(constants::GLOBAL_SLOTS.to_string());
+		}
+		if self.has_slots {
+			self.variables.push(constants::GLOBAL_SCOPE.to_string());
+			self.variables.push(constants::SLOTS.to_string());
+		}
+
+		// inline event handlers and window bindings get hoisted into the instance
+		let mut handlers: Vec<String> = vec![];
+		visit_markup(fragments, &mut |fragment| {
+			for directive in &fragment.directives {
+				if let Some(handler) = &directive.handler {
+					handlers.push(handler.clone());
+				}
+				if fragment.ttype == FragmentType::Window && directive.ttype == DirectiveType::Binding {
+					if let Some((handler, _, _)) = get_window_binding(&directive.name) {
+						if !handlers.iter().any(|existing| existing == handler) {
+							handlers.push(handler.to_string());
+						}
+					}
 				}
 			}
+		});
+		self.variables.extend(handlers);
+
+		let mut scope = HashMap::new();
+		for (i, name) in self.variables.iter().enumerate() {
+			scope.insert(name.clone(), ContextVariable {
+				index: i,
+				dependencies: vec![i],
+			});
 		}
+		self.context_count = self.variables.len();
+		self.contexts.push(scope);
+		self.dirty_words = (self.context_count + count_markup_contexts(fragments)).div_ceil(31).max(1);
+		self.compiler.has_instance = self.compiler.options.dev
+			|| self.instance_script.trim().len() > 0
+			|| self.variables.len() > 0
+			|| self.has_slots
+			|| self.uses_props
+			|| self.uses_rest_props;
+	}
+
+	// Whether the script or markup mention one of svelte's globals, ex: $$props
+	fn uses_global(&self, fragments: &Vec<Fragment>, name: &str) -> bool {
+		let uses_global = |code: &str| script::tokenize(code).iter()
+			.any(|token| token.ttype == script::TokenType::Identifier && token.value == name);
+		return uses_global(&self.instance_script)
+			|| self.find_in_markup(fragments, &|fragment| fragment.data.as_deref().is_some_and(|data| fragment.ttype != FragmentType::Script && data.contains(name))
+				|| fragment.attributes.iter().any(|attribute| attribute.key.starts_with("...") && attribute.key.contains(name)
+					|| attribute.value.as_deref().is_some_and(|value| value.contains(name))));
+	}
+
+	// Whether any fragment in the markup (including the ones nested in blocks) matches
+	fn find_in_markup(&self, fragments: &Vec<Fragment>, predicate: &dyn Fn(&Fragment) -> bool) -> bool {
+		return fragments.iter().any(|fragment| predicate(fragment)
+			|| self.find_in_markup(&fragment.children, predicate)
+			|| self.find_in_markup(&fragment.else_children, predicate));
+	}
 
+	fn render_instance(&mut self, fragments: &Vec<Fragment>) -> String {
 		if !self.compiler.has_instance {
-			return content
-		} else {
-			let mut result = String::new();
-			result.push_str(&format!("function {}({}, {}, {}) {{\n",
-				constants::INSTANCE,
-				constants::GLOBAL_SELF,
+			return String::new();
+		}
+
+		let indices: HashMap<String, usize> = self.variables.iter().cloned()
+			.enumerate()
+			.map(|(i, name)| (name, i))
+			.collect();
+		let mut content = self.instance_script.trim_end().to_string();
+		for handler in &self.handlers {
+			content.push_str("\n\n");
+			content.push_str(handler);
+		}
+		let content = script::invalidate_assignments(&content, constants::GLOBAL_INVALIDATE, &indices);
+		let mut content = script::rewrite_exports(&content, constants::GLOBAL_PROPS);
+		content = content.trim().to_string();
+
+		if self.uses_rest_props {
+			// everything that isn't one of our props
+			self.compiler.insert_import(&imports::COMPUTE_REST_PROPS);
+			let names = self.props.iter().map(|prop| format!("\"{}\"", prop)).collect::<Vec<String>>();
+			content = format!("const {} = [{}];\nlet {} = {}({}, {});\n{}",
+				constants::OMIT_PROPS_NAMES,
+				names.join(", "),
+				constants::GLOBAL_REST_PROPS,
+				imports::COMPUTE_REST_PROPS.function,
 				constants::GLOBAL_PROPS,
-				constants::GLOBAL_INVALIDATE,
+				constants::OMIT_PROPS_NAMES,
+				content
+			);
+		}
+
+		if self.has_slots {
+			let mut slots = format!("let {{ {}: {} = {{}}, {} }} = {};\n",
+				constants::GLOBAL_SLOTS,
+				constants::SLOTS,
+				constants::GLOBAL_SCOPE,
+				constants::GLOBAL_PROPS
+			);
+			if self.uses_slots_object {
+				self.compiler.insert_import(&imports::COMPUTE_SLOTS);
+				slots.push_str(&format!("const {} = {}({});\n", constants::GLOBAL_SLOTS, imports::COMPUTE_SLOTS.function, constants::SLOTS));
+			}
+			content = format!("{}{}", slots, content);
+		}
+
+		let uses_all_props = self.uses_props || self.uses_rest_props;
+		if self.compiler.options.dev {
+			content.push_str(&self.render_prop_warnings(uses_all_props));
+		}
+		if self.props.len() > 0 || self.has_slots || uses_all_props {
+			// if we hold onto $$props, the new props need a name of their own
+			let new_props = if uses_all_props { constants::GLOBAL_NEW_PROPS } else { constants::GLOBAL_PROPS };
+			content.push_str(&format!("\n\n{}.$$set = {} => {{\n", constants::GLOBAL_SELF, new_props));
+			if uses_all_props {
+				self.compiler.insert_import(&imports::ASSIGN);
+				self.compiler.insert_import(&imports::EXCLUDE_INTERNAL_PROPS);
+				let merged = format!("{} = {}({}({{}}, {}), {}({}))",
+					constants::GLOBAL_PROPS,
+					imports::ASSIGN.function,
+					imports::ASSIGN.function,
+					constants::GLOBAL_PROPS,
+					imports::EXCLUDE_INTERNAL_PROPS.function,
+					new_props
+				);
+				if self.uses_props {
+					content.push_str(&format!("\t{}({}, {});\n", constants::GLOBAL_INVALIDATE, indices[constants::GLOBAL_PROPS], merged));
+				} else {
+					content.push_str(&format!("\t{};\n", merged));
+				}
+				if self.uses_rest_props {
+					content.push_str(&format!("\t{}({}, {} = {}({}, {}));\n",
+						constants::GLOBAL_INVALIDATE,
+						indices[constants::GLOBAL_REST_PROPS],
+						constants::GLOBAL_REST_PROPS,
+						imports::COMPUTE_REST_PROPS.function,
+						constants::GLOBAL_PROPS,
+						constants::OMIT_PROPS_NAMES
+					));
+				}
+			}
+			for prop in &self.props {
+				content.push_str(&format!("\tif ('{}' in {}) {}({}, {} = {}.{});\n",
+					prop,
+					new_props,
+					constants::GLOBAL_INVALIDATE,
+					indices[prop],
+					prop,
+					new_props,
+					prop
+				));
+			}
+			if self.has_slots {
+				content.push_str(&format!("\tif ('{}' in {}) {}({}, {} = {}.{});\n",
+					constants::GLOBAL_SCOPE,
+					new_props,
+					constants::GLOBAL_INVALIDATE,
+					indices[constants::GLOBAL_SCOPE],
+					constants::GLOBAL_SCOPE,
+					new_props,
+					constants::GLOBAL_SCOPE
+				));
+			}
+			content.push_str("};");
+		}
+		if self.compiler.options.dev {
+			content.push_str(&self.render_dev_state(&indices));
+		}
+		if self.uses_props {
+			// the parent's internal props (like $$slots) aren't for the user
+			self.compiler.insert_import(&imports::EXCLUDE_INTERNAL_PROPS);
+			content.push_str(&format!("\n\n{} = {}({});",
+				constants::GLOBAL_PROPS,
+				imports::EXCLUDE_INTERNAL_PROPS.function,
+				constants::GLOBAL_PROPS
 			));
+		}
+		content.push_str(&format!("\n\nreturn [{}];", self.variables.join(", ")));
+
+		let mut result = String::new();
+		result.push_str(&format!("function {}({}, {}, {}) {{\n",
+			constants::INSTANCE,
+			constants::GLOBAL_SELF,
+			constants::GLOBAL_PROPS,
+			constants::GLOBAL_INVALIDATE,
+		));
+		result.push_str(&strings::indent_block(&content, 1));
+		result.push_str("\n}\n");
+		return result;
+	}
 
-			result.push_str(&strings::indent_block(&content, 1));
+	// Dev mode warns about props we expected but didn't get, and ones we don't know about
+	fn render_prop_warnings(&self, uses_all_props: bool) -> String {
+		let mut result = String::new();
+		let required = script::extract_declarations(&self.instance_script).into_iter()
+			.filter(|declaration| declaration.exported && !declaration.has_value && (declaration.kind == "let" || declaration.kind == "var"))
+			.map(|declaration| declaration.name)
+			.collect::<Vec<String>>();
+		if required.len() > 0 {
+			// bind:x on us counts, even if the parent's value starts out undefined
+			let mut checks = String::new();
+			for prop in &required {
+				checks.push_str(&format!("if ({} === undefined && !('{}' in {} || {}.$$.bound[{}.$$.props['{}']])) {{\n\tconsole.warn(\"<{}> was created without expected prop '{}'\");\n}}\n",
+					prop,
+					prop,
+					constants::GLOBAL_PROPS,
+					constants::GLOBAL_SELF,
+					constants::GLOBAL_SELF,
+					prop,
+					constants::COMPONENT,
+					prop
+				));
+			}
+			result.push_str(&format!("\n\n{}.$$.on_mount.push(function () {{\n{}\n}});",
+				constants::GLOBAL_SELF,
+				strings::indent_block(&checks, 1)
+			));
+		}
+		if !uses_all_props {
+			// anything else could be going into $$props, so only components that don't use it know what's unexpected
+			let props = self.props.iter().map(|prop| format!("'{}'", prop)).collect::<Vec<String>>();
+			result.push_str(&format!("\n\nconst writable_props = [{}];\n", props.join(", ")));
+			result.push_str(&format!("Object.keys({}).forEach(key => {{\n\tif (!~writable_props.indexOf(key) && key.slice(0, 2) !== '$$' && key !== 'slot') console.warn(`<{}> was created with unknown prop '${{key}}'`);\n}});",
+				constants::GLOBAL_PROPS,
+				constants::COMPONENT
+			));
+		}
+		return result;
+	}
 
-			// TODO: figure out the return value thing
+	// Lets devtools read and change our state, ex: `component.$inject_state({ count: 5 })`
+	fn render_dev_state(&self, indices: &HashMap<String, usize>) -> String {
+		let declarations = script::extract_declarations(&self.instance_script);
+		let names = declarations.iter().map(|declaration| declaration.name.clone()).collect::<Vec<String>>();
+		let mut result = format!("\n\n{}.$capture_state = () => ({{{}}});",
+			constants::GLOBAL_SELF,
+			if names.len() > 0 { format!(" {} ", names.join(", ")) } else { String::new() }
+		);
 
-			return result;
+		let mut inject = String::new();
+		for declaration in declarations.iter().filter(|declaration| declaration.kind == "let" || declaration.kind == "var") {
+			inject.push_str(&format!("\tif ('{}' in {}) {}({}, {} = {}.{});\n",
+				declaration.name,
+				constants::GLOBAL_PROPS,
+				constants::GLOBAL_INVALIDATE,
+				indices[&declaration.name],
+				declaration.name,
+				constants::GLOBAL_PROPS,
+				declaration.name
+			));
+		}
+		if inject.len() > 0 {
+			result.push_str(&format!("\n\n{}.$inject_state = {} => {{\n{}}};", constants::GLOBAL_SELF, constants::GLOBAL_PROPS, inject));
+			result.push_str(&format!("\n\nif ({} && \"$$inject\" in {}) {{\n\t{}.$inject_state({}.$$inject);\n}}",
+				constants::GLOBAL_PROPS,
+				constants::GLOBAL_PROPS,
+				constants::GLOBAL_SELF,
+				constants::GLOBAL_PROPS
+			));
 		}
+		return result;
 	}
 
 	fn get_exports_string(&mut self, fragments: &Vec<Fragment>) -> String {
@@ -108,110 +428,272 @@ impl DomRenderer<'_> {
 			return "{}".to_string();
 		}
 
-		let mut result = String::new();
-		result.push_str("{ ");
-		for export in exports {
-			result.push_str(&export);
-		}
-		result.push_str(" }");
-		return result;
+		let exports = exports.iter()
+			.map(|export| format!("{}: {}", export, self.variables.iter().position(|v| v == export).unwrap()))
+			.collect::<Vec<String>>();
+		return format!("{{ {} }}", exports.join(", "));
 	}
 
 	fn get_exports(&mut self, fragments: &Vec<Fragment>) -> Vec<String> {
-		let mut exports = vec![];
-		for fragment in fragments {
-			for fragment in fragment.into_iter() {
-				if fragment.ttype == FragmentType::Script {
-					if let Some(data) = &fragment.data {
-						let script_exports = script::extract_exports(data);
-						exports.extend(script_exports);
-					}
-				}
-			}
-		}
-		return exports;
+		return self.props.clone();
 	}
 
 	fn render_component(&mut self, fragments: &Vec<Fragment>, did_render_fragment: bool) -> String {
 		let mut result = String::new();
-		let component_name = "Component"; // TODO: is this configurable?
+		let component_name = constants::COMPONENT; // TODO: is this configurable?
 		let instance = if self.compiler.has_instance {
 			constants::INSTANCE
 		} else {
 			constants::NULL
 		};
+		let is_custom_element = self.compiler.options.custom_element;
 
-		self.compiler.insert_import(&imports::COMPONENT);
-		result.push_str(&format!("class {} extends {} {{\n", component_name, imports::COMPONENT.function));
+		let is_dev = self.compiler.options.dev && !is_custom_element;
+		let base = match (is_custom_element, is_dev) {
+			(true, _) => &*imports::CUSTOM_ELEMENT,
+			(false, true) => &*imports::COMPONENT_DEV,
+			(false, false) => &*imports::COMPONENT,
+		};
+		self.compiler.insert_import(base);
+		result.push_str(&format!("class {} extends {} {{\n", component_name, base.function));
 		result.push_str("\tconstructor(options) {\n");
-		result.push_str("\t\tsuper();\n");
+		if is_dev {
+			// SvelteComponentDev checks the options it's given
+			result.push_str("\t\tsuper(options);\n");
+		} else {
+			result.push_str("\t\tsuper();\n");
+		}
 
 		let exports = self.get_exports_string(fragments);
 
+		// custom elements render into their own shadow DOM, so their styles can go in as-is
+		let mut options = constants::OPTIONS.to_string();
+		if is_custom_element {
+			let styles = fragments.iter()
+				.filter(|fragment| fragment.ttype == FragmentType::Style)
+				.map(|fragment| fragment.data.as_deref().unwrap_or("").trim())
+				.collect::<Vec<&str>>()
+				.join("\n");
+			if styles.len() > 0 {
+				result.push_str(&format!("\t\tthis.shadowRoot.innerHTML = {};\n",
+					serde_json::to_string(&format!("<style>{}</style>", styles)).unwrap()
+				));
+			}
+
+			self.compiler.insert_import(&imports::ATTRIBUTE_TO_OBJECT);
+			options = format!("{{\n\t\t\ttarget: this.shadowRoot,\n\t\t\tprops: {}(this.attributes),\n\t\t\tcustomElement: true\n\t\t}}",
+				imports::ATTRIBUTE_TO_OBJECT.function
+			);
+		}
+
+		// immutable data can only change by reassignment, so objects don't need to count as always dirty
+		let not_equal = if self.compiler.options.immutable { &*imports::NOT_EQUAL } else { &*imports::SAFE_NOT_EQUAL };
 		self.compiler.insert_import(&imports::INIT);
-		self.compiler.insert_import(&imports::SAFE_NOT_EQUAL);
-		result.push_str(&format!("\t\t{}(this, options, {}, {}, {}, {});\n",
+		self.compiler.insert_import(not_equal);
+		// dirty starts out as a single word, so it needs to be told how many there are
+		let dirty = if self.dirty_words > 1 { format!(", {}, {}", constants::NULL, self.get_initial_dirty()) } else { String::new() };
+		result.push_str(&format!("\t\t{}(this, {}, {}, {}, {}, {}{});\n",
 			imports::INIT.function,
+			options,
 			instance,
 			if did_render_fragment { constants::CREATE_FRAGMENT } else { constants::NULL },
-			imports::SAFE_NOT_EQUAL.function,
+			not_equal.function,
 			exports,
+			dirty
 		));
+		if is_dev {
+			self.compiler.insert_import(&imports::DISPATCH_DEV);
+			result.push_str(&format!("\n\t\t{}(\"SvelteRegisterComponent\", {{\n\t\t\tcomponent: this,\n\t\t\ttagName: \"{}\",\n\t\t\toptions,\n\t\t\tid: {}\n\t\t}});\n",
+				imports::DISPATCH_DEV.function,
+				component_name,
+				if did_render_fragment { format!("{}.name", constants::CREATE_FRAGMENT) } else { constants::NULL.to_string() }
+			));
+		}
 
+		if is_custom_element {
+			// custom elements get created by the browser, so any options only apply when we construct them ourselves
+			self.compiler.insert_import(&imports::INSERT);
+			self.compiler.insert_import(&imports::FLUSH);
+			result.push_str("\n\t\tif (options) {\n");
+			result.push_str(&format!("\t\t\tif (options.target) {{\n\t\t\t\t{}(options.target, this, options.anchor);\n\t\t\t}}\n", imports::INSERT.function));
+			if self.props.len() > 0 {
+				result.push_str(&format!("\n\t\t\tif (options.props) {{\n\t\t\t\tthis.$set(options.props);\n\t\t\t\t{}();\n\t\t\t}}\n", imports::FLUSH.function));
+			}
+			result.push_str("\t\t}\n");
+		}
 		result.push_str("\t}\n");
+
+		if is_custom_element && self.props.len() > 0 {
+			let attributes = self.props.iter()
+				.map(|prop| format!("\"{}\"", prop))
+				.collect::<Vec<String>>();
+			result.push_str(&format!("\n\tstatic get observedAttributes() {{\n\t\treturn [{}];\n\t}}\n", attributes.join(", ")));
+		}
+
+		if self.compiler.options.accessors || is_custom_element {
+			self.compiler.insert_import(&imports::FLUSH);
+			for prop in self.props.clone() {
+				let index = self.variables.iter().position(|variable| *variable == prop).unwrap();
+				result.push_str(&format!("\n\tget {}() {{\n\t\treturn this.$$.{}[{}];\n\t}}\n", prop, constants::CTX, index));
+				result.push_str(&format!("\n\tset {}({}) {{\n\t\tthis.$$set({{ {} }});\n\t\t{}();\n\t}}\n",
+					prop,
+					prop,
+					prop,
+					imports::FLUSH.function
+				));
+			}
+		} else if is_dev {
+			// without accessors, props only go through $set, so catch anyone trying otherwise
+			for prop in &self.props {
+				result.push_str(&format!("\n\tget {}() {{\n\t\tthrow new Error(\"<{}>: Props cannot be read directly from the component instance unless compiling with 'accessors: true' or '<svelte:options accessors/>'\");\n\t}}\n", prop, component_name));
+				result.push_str(&format!("\n\tset {}(value) {{\n\t\tthrow new Error(\"<{}>: Props cannot be set directly on the component instance unless compiling with 'accessors: true' or '<svelte:options accessors/>'\");\n\t}}\n", prop, component_name));
+			}
+		}
 		result.push_str("}\n\n");
+
+		if is_custom_element {
+			match self.compiler.options.tag.clone() {
+				Some(tag) => result.push_str(&format!("customElements.define({}, {});\n", strings::to_js_string(&tag), component_name)),
+				None => self.compiler.warn("custom-element-no-tag", "No custom element 'tag' option was specified. To automatically register a custom element, specify a name with a hyphen in it, e.g. <svelte:options tag=\"my-thing\"/>"),
+			}
+		}
 		result.push_str(&format!("export default {};\n", component_name));
 
 		return result;
 	}
 
-	fn render_create_fragment(
-		&mut self,
-		fragments: &Vec<Fragment>,
-		variables: &str,
-		creator: &str,
-		mounter: &str,
-		p: &str,
-		transition_in: &str,
-		transition_out: &str,
-		detach: &str
-	) -> String {
+	fn render_block(&mut self, block: &Block) -> String {
 		let mut result = String::new();
-		if fragments.len() == 0 || (
-			variables.len() == 0 &&
-			creator.len() == 0 &&
-			mounter.len() == 0 &&
-			p.len() == 0 &&
-			transition_in.len() == 0 &&
-			transition_out.len() == 0
-		) {
+		if block.is_empty() {
 			// special case: nothing here, we can quit early
 			return result;
 		}
 
-		result.push_str(&format!("function {}(ctx) {{\n", constants::CREATE_FRAGMENT));
-		result.push_str(variables);
+		let mut variables = block.variables.clone();
+		let mut mounter = block.mounter.clone();
+		let mut destroy = block.destroy.clone();
+		if block.listeners.len() > 0 {
+			// listeners get added once, even if we're mounted again (ex: when moved by a keyed each)
+			self.dom_function(&imports::LISTEN);
+			variables.push_str(&format!("let {};\nlet {};\n", constants::MOUNTED, constants::DISPOSE));
+			let listeners = if block.listeners.len() == 1 {
+				block.listeners[0].clone()
+			} else {
+				format!("[\n{}\n]", strings::indent_block(&block.listeners.join(",\n"), 1))
+			};
+			mounter.push_str(&format!("if (!{}) {{\n\t{} = {};\n\t{} = true;\n}}\n",
+				constants::MOUNTED,
+				constants::DISPOSE,
+				strings::indent_block(&listeners, 1).trim_start(),
+				constants::MOUNTED
+			));
+			destroy.push_str(&format!("{} = false;\n", constants::MOUNTED));
+			if block.listeners.len() == 1 {
+				destroy.push_str(&format!("{}();\n", constants::DISPOSE));
+			} else {
+				self.compiler.insert_import(&imports::RUN_ALL);
+				destroy.push_str(&format!("{}({});\n", imports::RUN_ALL.function, constants::DISPOSE));
+			}
+		}
+		let mut transition_in = block.transition_in.clone();
+		let mut transition_out = block.transition_out.clone();
+		if block.has_transitions() {
+			// NOTE: let's assume we need current
+			variables.push_str(&format!("let {};\n", constants::CURRENT));
+			mounter.push_str(&format!("{} = true;\n", constants::CURRENT));
+			transition_in = format!("if ({}) return;\n{}{} = true;\n", constants::CURRENT, transition_in, constants::CURRENT);
+			transition_out = format!("{}{} = false;\n", transition_out, constants::CURRENT);
+		}
 
-		result.push_str("\n\treturn {\n");
+		if block.is_keyed {
+			result.push_str(&format!("function {}(key_1, {}) {{\n", block.name, constants::CTX));
+		} else {
+			result.push_str(&format!("function {}({}) {{\n", block.name, constants::CTX));
+		}
+		if variables.len() > 0 {
+			result.push_str(&strings::indent_block(&variables, 1));
+			result.push_str("\n\n");
+		}
+		if self.compiler.options.dev {
+			result.push_str(&format!("\tconst {} = {{\n", constants::BLOCK));
+		} else {
+			result.push_str("\treturn {\n");
+		}
 
 		let indent_level = 3;
 
+		if block.is_keyed {
+			result.push_str("\t\tkey: key_1,\n");
+			result.push_str("\t\tfirst: null,\n");
+		}
+
 		// creator
-		result.push_str("\t\tc() {\n");
-		result.push_str(&strings::indent_block(creator, indent_level));
-		result.push_str("\n\t\t},\n");
+		if block.creator.len() == 0 && !block.is_keyed {
+			self.compiler.insert_import(&imports::NOOP);
+			result.push_str(&format!("\t\tc: {},\n", imports::NOOP.function));
+		} else {
+			result.push_str("\t\tc() {\n");
+			result.push_str(&strings::indent_block(&block.creator, indent_level));
+			if block.is_keyed {
+				result.push_str(&format!("\n\t\t\tthis.first = {};", self.get_internal_name(constants::FIRST)));
+			}
+			result.push_str("\n\t\t},\n");
+		}
+
+		// claimer
+		if self.compiler.options.hydratable {
+			if block.claimer.len() == 0 {
+				self.compiler.insert_import(&imports::NOOP);
+				result.push_str(&format!("\t\tl: {},\n", imports::NOOP.function));
+			} else {
+				result.push_str(&format!("\t\tl({}) {{\n", constants::NODES));
+				result.push_str(&strings::indent_block(&block.claimer, indent_level));
+				if block.is_keyed {
+					result.push_str(&format!("\n\t\t\tthis.first = {};", self.get_internal_name(constants::FIRST)));
+				}
+				result.push_str("\n\t\t},\n");
+			}
+		}
 
 		// mounter
-		result.push_str("\t\tm(target, anchor) {\n");
-		result.push_str(&strings::indent_block(mounter, indent_level));
+		result.push_str(&format!("\t\tm({}, {}) {{\n", constants::TARGET, constants::ANCHOR));
+		result.push_str(&strings::indent_block(&mounter, indent_level));
 		result.push_str("\n\t\t},\n");
 
-		// whatever p is
-		if p.len() == 0 {
+		// update whatever changed
+		if block.p.len() == 0 {
 			self.compiler.insert_import(&imports::NOOP);
 			result.push_str(&format!("\t\tp: {},\n", imports::NOOP.function));
+		} else if block.is_root && self.dirty_words > 1 {
+			result.push_str(&format!("\t\tp({}, {}) {{\n", constants::CTX, constants::DIRTY));
+			result.push_str(&strings::indent_block(&block.p, indent_level));
+			result.push_str("\n\t\t},\n");
+		} else if block.is_root {
+			result.push_str(&format!("\t\tp({}, [{}]) {{\n", constants::CTX, constants::DIRTY));
+			result.push_str(&strings::indent_block(&block.p, indent_level));
+			result.push_str("\n\t\t},\n");
 		} else {
-			todo!();
+			// NOTE: child blocks hold onto ctx, so they always need the latest
+			result.push_str(&format!("\t\tp({}, {}) {{\n", constants::NEW_CTX, constants::DIRTY));
+			match &block.context_getter {
+				Some(getter) => result.push_str(&format!("\t\t\t{} = {}({});\n", constants::CTX, getter, constants::NEW_CTX)),
+				None => result.push_str(&format!("\t\t\t{} = {};\n", constants::CTX, constants::NEW_CTX)),
+			}
+			result.push_str(&strings::indent_block(&block.p, indent_level));
+			result.push_str("\n\t\t},\n");
+		}
+
+		// measure, fix and animate
+		if block.has_animation() {
+			result.push_str("\t\tr() {\n");
+			result.push_str(&strings::indent_block(&block.measure, indent_level));
+			result.push_str("\n\t\t},\n");
+			result.push_str("\t\tf() {\n");
+			result.push_str(&strings::indent_block(&block.fix, indent_level));
+			result.push_str("\n\t\t},\n");
+			result.push_str("\t\ta() {\n");
+			result.push_str(&strings::indent_block(&block.animate, indent_level));
+			result.push_str("\n\t\t},\n");
 		}
 
 		// transition in
@@ -219,8 +701,8 @@ impl DomRenderer<'_> {
 			self.compiler.insert_import(&imports::NOOP);
 			result.push_str(&format!("\t\ti: {},\n", imports::NOOP.function));
 		} else {
-			result.push_str("\t\ti(local) {\n");
-			result.push_str(&strings::indent_block(transition_in, indent_level));
+			result.push_str(&format!("\t\ti({}) {{\n", constants::LOCAL));
+			result.push_str(&strings::indent_block(&transition_in, indent_level));
 			result.push_str("\n\t\t},\n");
 		}
 
@@ -229,409 +711,2547 @@ impl DomRenderer<'_> {
 			self.compiler.insert_import(&imports::NOOP);
 			result.push_str(&format!("\t\to: {},\n", imports::NOOP.function));
 		} else {
-			result.push_str("\t\to(local) {\n");
-			result.push_str(&strings::indent_block(transition_out, indent_level));
+			result.push_str(&format!("\t\to({}) {{\n", constants::LOCAL));
+			result.push_str(&strings::indent_block(&transition_out, indent_level));
 			result.push_str("\n\t\t},\n");
 		}
 
 		// detach
+		let mut detach = String::new();
+		if block.detach.len() > 0 {
+			detach.push_str(&format!("if ({}) {{\n", constants::DETACHING));
+			detach.push_str(&strings::indent_block(&block.detach, 1));
+			detach.push_str("\n}\n");
+		}
+		detach.push_str(&destroy);
 		if detach.len() == 0 {
 			self.compiler.insert_import(&imports::NOOP);
-			result.push_str(&format!("\t\td: {},\n", imports::NOOP.function));
+			result.push_str(&format!("\t\td: {}\n", imports::NOOP.function));
 		} else {
 			result.push_str(&format!("\t\td({}) {{\n", constants::DETACHING));
-			result.push_str(&strings::indent_block(detach, indent_level));
+			result.push_str(&strings::indent_block(&detach, indent_level));
 			result.push_str("\n\t\t}\n");
 		}
 
 		result.push_str("\t};\n");
+		if self.compiler.options.dev {
+			// devtools keep track of every block, along with what it was made for
+			self.compiler.insert_import(&imports::DISPATCH_DEV);
+			result.push_str(&format!("\n\t{}(\"SvelteRegisterBlock\", {{\n\t\t{},\n\t\tid: {}.name,\n\t\ttype: \"{}\",\n\t\tsource: \"\",\n\t\t{}\n\t}});\n\n\treturn {};\n",
+				imports::DISPATCH_DEV.function,
+				constants::BLOCK,
+				block.name,
+				get_block_type(block),
+				constants::CTX,
+				constants::BLOCK
+			));
+		}
 		result.push_str("}\n");
 
 		return result;
 	}
 
-	fn attributes_to_props(&self, fragment: &Fragment) -> String {
-		let mut props = String::new();
-		for attribute in &fragment.attributes {
-			todo!();
-		}
-		return props;
-	}
-
-	fn render_mounter(&mut self, parent: Option<&Fragment>, fragments: &Vec<Fragment>) -> String {
-		let mut result = String::new();
-		for fragment in fragments {
-			for fragment in fragment.into_iter() {
-				match fragment.ttype {
-					FragmentType::Basic | FragmentType::Text => {
-						if fragment.has_parent && fragment.ttype == FragmentType::Text {
-							continue;
-						}
-						self.compiler.insert_import(&imports::INSERT);
-						result.push_str(&format!("{}({}, {}, {});\n",
-							imports::INSERT.function,
-							constants::TARGET,
-							fragment.short_name,
-							constants::ANCHOR,
-						));
-					}
-
-					FragmentType::Svelte => {
-						self.compiler.insert_import(&imports::MOUNT_COMPONENT);
-						result.push_str(&format!("{}({}, {}, {});\n",
-							imports::MOUNT_COMPONENT.function,
-							fragment.short_name,
-							constants::TARGET,
-							constants::ANCHOR,
-						));
-					}
-
-					_ => {} // no-op
+	// Rewrites an expression from the markup so it reads from ctx. Returns the new expression
+	// along with the instance variables it depends on.
+	fn render_expression(&mut self, expression: &str) -> (String, Vec<usize>) {
+		let mut dependencies = vec![];
+		let contexts = &self.contexts;
+		let code = script::rewrite_identifiers(expression, |name| {
+			for scope in contexts.iter().rev() {
+				if let Some(variable) = scope.get(name) {
+					dependencies.extend(variable.dependencies.iter().cloned());
+					return Some(format!("/*{}*/ {}[{}]", name, constants::CTX, variable.index));
 				}
 			}
-		}
+			return None;
+		});
+		dependencies.sort();
+		dependencies.dedup();
+		self.read_dependencies.extend(dependencies.iter().cloned());
+		return (code, dependencies);
+	}
 
-		if self.compiler.has_svelte_fragments {
-			result.push_str(&format!("{} = true;\n", constants::CURRENT));
+	// ex: `dirty & /*a, b*/ 3`, or `dirty[0] & /*a*/ 1 | dirty[1] & /*b*/ 2` once ctx overflows
+	fn render_dirty_check(&mut self, dependencies: &Vec<usize>) -> String {
+		self.read_dependencies.extend(dependencies.iter().cloned());
+		let mut checks = vec![];
+		for (word, mask) in get_dirty_masks(dependencies) {
+			let names = dependencies.iter()
+				.filter(|dependency| *dependency / 31 == word)
+				.map(|dependency| self.get_variable_name(*dependency))
+				.collect::<Vec<&str>>();
+			let dirty = if self.dirty_words > 1 { format!("{}[{}]", constants::DIRTY, word) } else { constants::DIRTY.to_string() };
+			checks.push(format!("{} & /*{}*/ {}", dirty, names.join(", "), mask));
 		}
-
-		return result;
+		return checks.join(" | ");
 	}
 
-	fn render_p(&mut self, parent: Option<&Fragment>, fragments: &Vec<Fragment>) -> String {
-		// What even is p?
-		// TODO
-		return format!("");
+	// What to pass as dirty when everything needs updating, ex: -1 or [-1, -1]
+	fn get_initial_dirty(&self) -> String {
+		if self.dirty_words == 1 {
+			return "-1".to_string();
+		}
+		return format!("[{}]", vec!["-1"; self.dirty_words].join(", "));
 	}
 
-	fn render_transition_in(&mut self, parent: Option<&Fragment>, fragments: &Vec<Fragment>) -> String {
-		let mut result = String::new();
-		if !self.compiler.has_svelte_fragments {
-			return result;
+	// Instance variables come first in ctx, followed by things like let: directives
+	fn get_variable_name(&self, index: usize) -> &str {
+		if index < self.variables.len() {
+			return &self.variables[index];
 		}
-		result.push_str(&format!("if ({}) return;\n", constants::CURRENT));
-
-		for fragment in fragments {
-			for fragment in fragment.into_iter() {
-				if fragment.renderable {
-					match fragment.ttype {
-						FragmentType::Svelte => {
-							self.compiler.insert_import(&imports::TRANSITION_IN);
-							result.push_str(&format!("{}({}.{}, {});\n",
-								imports::TRANSITION_IN.function,
-								fragment.short_name,
-								constants::GLOBAL_FRAGMENT,
-								constants::LOCAL
-							));
-						}
-
-						_ => {} // No-op
-					}
+		for scope in &self.contexts {
+			for (name, variable) in scope {
+				if variable.index == index {
+					return name;
 				}
 			}
 		}
-
-		result.push_str(&format!("{} = true;\n", constants::CURRENT));
-		return result;
+		return "?";
 	}
 
-	fn render_transition_out(&mut self, parent: Option<&Fragment>, fragments: &Vec<Fragment>) -> String {
-		let mut result = String::new();
-		if !self.compiler.has_svelte_fragments {
-			return result;
-		}
-
+	fn render_children(&mut self, block: &mut Block, parent: Option<&Fragment>, fragments: &Vec<Fragment>) {
 		for fragment in fragments {
-			for fragment in fragment.into_iter() {
-				if fragment.renderable {
-					match fragment.ttype {
-						FragmentType::Svelte => {
-							self.compiler.insert_import(&imports::TRANSITION_OUT);
-							result.push_str(&format!("{}({}.{}, {});\n",
-								imports::TRANSITION_OUT.function,
-								fragment.short_name,
-								constants::GLOBAL_FRAGMENT,
-								constants::LOCAL
-							));
-						}
-
-						_ => {} // No-op
-					}
-				}
-			}
+			self.render_fragment(block, parent, fragment);
 		}
-
-		result.push_str(&format!("{} = false;\n", constants::CURRENT));
-		return result;
 	}
 
-	fn render_detach(&mut self, parent: Option<&Fragment>, fragments: &Vec<Fragment>) -> String {
-		let mut complex_detach = String::new();
-		let mut basic_detach = String::new();
-
-		for fragment in fragments {
-			for fragment in fragment.into_iter() {
-				if fragment.renderable {
-					match fragment.ttype {
-						FragmentType::Basic | FragmentType::Text => {
-							// special case: fragment is text and has no parent, so we're probably hosting it
-							if fragment.has_parent && fragment.ttype == FragmentType::Text {
-								continue;
-							}
-
-							self.compiler.insert_import(&imports::DETACH);
-							basic_detach.push_str(&format!("{}({});\n",
-								imports::DETACH.function,
-								fragment.short_name,
-							));
-						}
-
-						FragmentType::Svelte => {
-							self.compiler.insert_import(&imports::DESTROY_COMPONENT);
-							complex_detach.push_str(&format!("{}({}, {});\n",
-								imports::DESTROY_COMPONENT.function,
-								fragment.short_name,
-								constants::DETACHING
-							));
-						}
+	fn render_fragment(&mut self, block: &mut Block, parent: Option<&Fragment>, fragment: &Fragment) {
+		if !fragment.renderable {
+			return;
+		}
 
-						_ => {} // No-op
-					}
+		match fragment.ttype {
+			FragmentType::Basic => self.render_basic_fragment(block, parent, fragment, None),
+			FragmentType::Svelte => self.render_svelte_fragment(block, parent, fragment),
+			FragmentType::Text => self.render_text_fragment(block, parent, fragment),
+			FragmentType::IfBlock => self.render_if_block(block, parent, fragment),
+			FragmentType::EachBlock => self.render_each_block(block, parent, fragment),
+			FragmentType::KeyBlock => self.render_key_block(block, parent, fragment),
+			FragmentType::Slot => self.render_slot(block, parent, fragment),
+			FragmentType::DynamicElement => self.render_dynamic_element(block, parent, fragment),
+			FragmentType::RawHtml => self.render_raw_html(block, parent, fragment),
+			FragmentType::Debug => self.render_debug(block, fragment),
+			FragmentType::Const => {
+				// these get handled by render_child_block, since they change the block's ctx
+				self.compiler.error("{@const} must be the immediate child of {#if}, {:else if}, {:else}, {#each}, <Component> or <svelte:fragment>");
+			}
+			FragmentType::Head => {
+				if !block.is_root || parent.is_some() {
+					self.compiler.error("<svelte:head> tags cannot be inside elements or blocks");
 				}
+				self.render_head(block, fragment);
+			}
+			FragmentType::Window | FragmentType::Body | FragmentType::Document => {
+				if !block.is_root || parent.is_some() {
+					self.compiler.error(&format!("<{}> tags cannot be inside elements or blocks", fragment.name));
+				}
+				self.render_special_fragment(block, fragment);
 			}
-		}
 
-		let mut result = String::new();
-		if basic_detach.len() > 0 {
-			result.push_str(&format!("if ({}) {{\n", constants::DETACHING));
-			result.push_str(&strings::indent_block(&basic_detach, 1));
-			result.push_str("\n}\n");
-		}
-		if complex_detach.len() > 0 {
-			result.push_str(&complex_detach);
-			result.push_str("\n");
+			FragmentType::SlotTemplate => self.compiler.error("<svelte:fragment> must be the direct child of a component"),
+			FragmentType::Options => unreachable!(),
+			FragmentType::Style => todo!(),
+			FragmentType::Script => todo!(),
+			FragmentType::Comment => self.render_comment(block, parent, fragment),
+			FragmentType::Tag => unreachable!(),
 		}
-		return result;
 	}
 
-	fn render_variables(&mut self, fragments: &Vec<Fragment>) -> String {
-		let mut result = String::new();
+	// DOM helpers have dev versions, which also tell devtools what they did, ex: insert_dev
+	fn dom_function(&mut self, import: &'static Import) -> String {
+		let import = match &*import.function {
+			_ if !self.compiler.options.dev => import,
+			"insert" => &*imports::INSERT_DEV,
+			"append" => &*imports::APPEND_DEV,
+			"detach" => &*imports::DETACH_DEV,
+			"attr" => &*imports::ATTR_DEV,
+			"listen" => &*imports::LISTEN_DEV,
+			"set_data" => &*imports::SET_DATA_DEV,
+			_ => import,
+		};
+		self.compiler.insert_import(import);
+		return import.function.clone();
+	}
 
-		// basic variable definitions
-		for fragment in fragments {
-			for fragment in fragment.into_iter() {
-				if fragment.renderable {
-					// Special case: only parentless text-like elements get their own separate variable
-					if fragment.ttype == FragmentType::Text && fragment.has_parent {
-						continue;
-					}
+	// Mounts a DOM node into either its parent or the target, if it's at the top of this block
+	fn render_mount(&mut self, block: &mut Block, parent: Option<&Fragment>, name: &str) {
+		match parent {
+			Some(parent) => {
+				block.mounter.push_str(&format!("{}({}, {});\n",
+					self.dom_function(&imports::APPEND),
+					parent.short_name,
+					name
+				));
 
-					match fragment.ttype {
-						FragmentType::Basic |
-						FragmentType::Svelte |
-						FragmentType::Text
-						=> {
-							// TODO: which fragments should we exclude?
-							result.push_str(&format!("\tlet {};\n", fragment.short_name));
-							
-							if let Some(extra_names) = self.extra_variables.get(&fragment.short_name) {
-								for name in extra_names {
-									result.push_str(&format!("\tlet {};\n", name));
-								}
-							}
-						}
-						_ => {}
-					}
+				// document.head sticks around, so whatever we put in it has to be taken out again
+				if parent.ttype == FragmentType::Head {
+					block.destroy.push_str(&format!("{}({});\n", self.dom_function(&imports::DETACH), name));
 				}
 			}
-		}
-
-		if self.compiler.has_svelte_fragments {
-			// NOTE: let's assume we need current
-			result.push_str(&format!("\tlet {};\n", constants::CURRENT));
-		}
-
-		// svelte variable definitions
-		for fragment in fragments {
-			for fragment in fragment.into_iter() {
-				if fragment.ttype == FragmentType::Svelte {
-					// TODO: what should this be?
-					let mut args = String::new();
-					let props = self.attributes_to_props(fragment);
-					if props.len() > 0 {
-						args.push_str(&format!(" {}: {} ", constants::PROPS, props));
-					}
+			None => {
+				block.mounter.push_str(&format!("{}({}, {}, {});\n",
+					self.dom_function(&imports::INSERT),
+					constants::TARGET,
+					name,
+					constants::ANCHOR,
+				));
 
-					// TODO: do we need extra names or anything here?
-					result.push_str(&format!("\t{} = new {}({{{}}});\n",
-						fragment.short_name,
-						fragment.name,
-						args
-					));
-				}
+				// children get removed along with their parent, so only top-level nodes need detaching
+				block.detach.push_str(&format!("{}({});\n",
+					self.dom_function(&imports::DETACH),
+					name,
+				));
 			}
 		}
+	}
 
-		return result;
+	// Names like the `i` in each blocks' loops only get allocated once something uses them,
+	// so they dodge whatever the markup is already called instead of renaming it, ex: <i>
+	fn get_internal_name(&mut self, name: &str) -> String {
+		if let Some(allocated) = self.internal_names.get(name) {
+			return allocated.clone();
+		}
+		let allocated = self.compiler.generate_short_name(name);
+		self.internal_names.insert(name.to_string(), allocated.clone());
+		return allocated;
 	}
 
-	fn render_fragments_creator(&mut self, parent: Option<&Fragment>, fragments: &Vec<Fragment>) -> String {
-		let mut result: String = String::new();
-		for fragment in fragments {
-			if let Some(fragment) = self.render_fragment_creator(parent, fragment) {
-				result.push_str(&fragment);
-			}
+	// What to pass along when destroying a block. Nodes in an element go along with it,
+	// but nodes in <svelte:head> always need removing.
+	fn get_detaching(&self, parent: Option<&Fragment>) -> &'static str {
+		match parent {
+			Some(parent) if parent.ttype == FragmentType::Head => "1",
+			Some(_) => "",
+			None => constants::DETACHING,
 		}
-		return result;
 	}
 
-	fn render_fragment_creator(&mut self, parent: Option<&Fragment>, fragment: &Fragment) -> Option<String> {
-		if !fragment.renderable {
-			return None;
+	// Where a block should mount itself, ex: `target, anchor` or `div, null`
+	fn get_mount_target(&self, parent: Option<&Fragment>) -> String {
+		match parent {
+			Some(parent) => format!("{}, {}", parent.short_name, constants::NULL),
+			None => format!("{}, {}", constants::TARGET, constants::ANCHOR),
 		}
+	}
 
-		match fragment.ttype {
-			FragmentType::Basic => Some(self.render_basic_fragment_creator(parent, fragment)),
-			FragmentType::Svelte => Some(self.render_svelte_fragment_creator(parent, fragment)),
-			FragmentType::Text => Some(self.render_text_fragment_creator(parent, fragment)),
+	// Where l(nodes) finds the server's version of a node, ex: `nodes` or `div_nodes`
+	fn get_claim_nodes(&self, parent: Option<&Fragment>) -> String {
+		match parent {
+			Some(parent) if parent.ttype == FragmentType::Head => constants::HEAD_NODES.to_string(),
+			Some(parent) => format!("{}_{}", parent.short_name, constants::NODES),
+			None => constants::NODES.to_string(),
+		}
+	}
 
-			FragmentType::Style => todo!(),
-			FragmentType::Script => todo!(),
+	// claim_* functions only get imported when we're hydratable, since l(nodes) is left out otherwise
+	fn claim_function(&mut self, import: &Import) -> String {
+		if self.compiler.options.hydratable {
+			self.compiler.insert_import(import);
 		}
+		return import.function.clone();
 	}
 
-	fn render_basic_fragment_creator(&mut self, parent: Option<&Fragment>, fragment: &Fragment) -> String {
-		let mut result = String::new();
-		self.compiler.insert_import(&imports::ELEMENT);
-		result.push_str(&format!("{} = {}(\"{}\");\n",
-			fragment.short_name,
-			imports::ELEMENT.function,
-			fragment.name
-		));
+	// `node` is where to find an element its parent already built from html, ex: `div.childNodes[1]`
+	fn render_basic_fragment(&mut self, block: &mut Block, parent: Option<&Fragment>, fragment: &Fragment, node: Option<&str>) {
+		if fragment.attributes.iter().any(|attribute| attribute.key == "slot") {
+			self.compiler.error("Element with a slot='...' attribute must be a child of a component");
+		}
 
-		if fragment.attributes.len() > 0 {
-			self.compiler.insert_import(&imports::ATTR);
-			for attribute in &fragment.attributes {
-				let mut setter = "\"\"".to_string();
-				if let Some(value) = &attribute.value {
-					if strings::is_bind(&attribute.key) {
-						todo!();
-					} else if strings::contains_handlebars(&value) {
-						// add the extra property
-						let name = format!("{}_{}_{}",
+		block.variables.push_str(&format!("let {};\n", fragment.short_name));
+
+		// <svelte:element> is the same as any other element, once we know its tag
+		let tag = match fragment.ttype {
+			FragmentType::DynamicElement => self.render_expression(fragment.data.as_deref().unwrap()).0,
+			_ => strings::to_js_string(&fragment.name),
+		};
+		let creator = match fragment.namespace.as_deref() {
+			Some(namespaces::SVG) => {
+				self.compiler.insert_import(&imports::SVG_ELEMENT);
+				format!("{}({})", imports::SVG_ELEMENT.function, tag)
+			}
+			Some(namespace) if namespace != namespaces::HTML && namespace != namespaces::FOREIGN => {
+				format!("document.createElementNS({}, {})", strings::to_js_string(namespace), tag)
+			}
+			_ => {
+				self.compiler.insert_import(&imports::ELEMENT);
+				format!("{}({})", imports::ELEMENT.function, tag)
+			}
+		};
+		let claim = match fragment.namespace.as_deref() {
+			Some(namespaces::SVG) => format!("{}({}, {}", self.claim_function(&imports::CLAIM_SVG_ELEMENT), self.get_claim_nodes(parent), tag),
+			Some(namespace) if namespace != namespaces::HTML && namespace != namespaces::FOREIGN => {
+				format!("{}({}, {}", self.claim_function(&imports::CLAIM_ELEMENT), self.get_claim_nodes(parent), tag)
+			}
+			// html nodes have uppercase names, ex: DIV
+			_ => {
+				let tag = match fragment.ttype {
+					FragmentType::DynamicElement => format!("({} || 'null').toUpperCase()", tag),
+					_ => strings::to_js_string(&fragment.name.to_uppercase()),
+				};
+				format!("{}({}, {}", self.claim_function(&imports::CLAIM_ELEMENT), self.get_claim_nodes(parent), tag)
+			}
+		};
+		// any attributes we don't know about get removed, ex: ones only the server rendered
+		let is_svg = fragment.namespace.as_deref() == Some(namespaces::SVG);
+		let attributes = fragment.attributes.iter()
+			.filter(|attribute| !attribute.key.starts_with("..."))
+			.map(|attribute| {
+				let key = if is_svg { fix_attribute_casing(&attribute.key) } else { attribute.key.clone() };
+				format!("{}: true", strings::to_property_key(&key))
+			})
+			.collect::<Vec<String>>();
+		let attributes = if attributes.len() > 0 { format!("{{ {} }}", attributes.join(", ")) } else { "{}".to_string() };
+		match node {
+			Some(node) => block.create(&format!("{} = {};\n", fragment.short_name, node)),
+			None => block.create_node(
+				&format!("{} = {};\n", fragment.short_name, creator),
+				&format!("{} = {}, {});\n", fragment.short_name, claim, attributes)
+			),
+		}
+
+		self.render_attributes(block, fragment);
+		if self.compiler.options.dev {
+			// lets devtools point back at the markup, ex: add_location(h1, file, 4, 1, 60)
+			let (line, column) = strings::locate(&self.compiler.source, fragment.start);
+			let start = self.compiler.source[..fragment.start].encode_utf16().count();
+			self.compiler.insert_import(&imports::ADD_LOCATION);
+			block.create(&format!("{}({}, {}, {}, {}, {});\n",
+				imports::ADD_LOCATION.function,
+				fragment.short_name,
+				constants::FILE,
+				line,
+				column,
+				start
+			));
+		}
+		self.render_listeners(block, fragment, &fragment.short_name.clone());
+		if fragment.directives.iter().any(|directive| directive.ttype == DirectiveType::Binding) {
+			self.compiler.error("bind: is only supported on <svelte:window> for now");
+		}
+		if node.is_none() {
+			self.render_mount(block, parent, &fragment.short_name);
+		}
+
+		// special case: only static text inside, so we can skip creating text nodes
+		// NOTE: neither works when hydrating, since the server's text nodes need claiming
+		let hydratable = self.compiler.options.hydratable;
+		let is_static_text = !hydratable && fragment.children.len() > 0 && fragment.children.iter()
+			.all(|child| child.ttype == FragmentType::Text && !child.contains_code);
+		if is_static_text {
+			let text = fragment.children.iter()
+				.map(|child| child.data.as_deref().unwrap_or(""))
+				.collect::<String>();
+			block.create(&format!("{}.{} = {};\n",
+				fragment.short_name,
+				constants::TEXT_CONTENT,
+				strings::to_js_string(&text)
+			));
+		} else if !hydratable && fragment.children.len() > 0 && fragment.namespace.is_none() && fragment.children.iter().all(is_static) {
+			// nothing inside ever changes, so the browser can build it all from one string
+			let mut html = String::new();
+			for child in &fragment.children {
+				push_static_html(&mut html, child);
+			}
+			block.create(&format!("{}.{} = {};\n",
+				fragment.short_name,
+				constants::INNER_HTML,
+				strings::to_js_string(&html)
+			));
+		} else if !hydratable && has_static_skeleton(fragment) {
+			// only some of the elements inside change, so build everything from one string
+			// and then grab the ones that change, ex: p = div.childNodes[1]
+			let mut html = String::new();
+			for child in &fragment.children {
+				if is_static(child) {
+					push_static_html(&mut html, child);
+				} else {
+					html.push_str(&format!("<{}>", child.name));
+					if !parser::is_void(&child.name) {
+						html.push_str(&format!("</{}>", child.name));
+					}
+				}
+			}
+			block.create(&format!("{}.{} = {};\n",
+				fragment.short_name,
+				constants::INNER_HTML,
+				strings::to_js_string(&html)
+			));
+			// the browser drops empty text and joins up neighbouring text, so those don't count towards the index
+			let mut index = 0;
+			let mut after_text = false;
+			for child in &fragment.children {
+				if child.ttype == FragmentType::Text {
+					if child.data.as_deref().is_some_and(|data| data.len() > 0) && !after_text {
+						index += 1;
+						after_text = true;
+					}
+					continue;
+				}
+				if !is_static(child) {
+					let node = format!("{}.childNodes[{}]", fragment.short_name, index);
+					self.render_basic_fragment(block, Some(fragment), child, Some(&node));
+				}
+				index += 1;
+				after_text = false;
+			}
+		} else if fragment.children.len() > 0 {
+			let nodes = self.get_claim_nodes(Some(fragment));
+			block.claimer.push_str(&format!("var {} = {}({});\n", nodes, self.claim_function(&imports::CHILDREN), fragment.short_name));
+			self.render_children(block, Some(fragment), &fragment.children);
+			block.claimer.push_str(&format!("{}.forEach({});\n", nodes, self.claim_function(&imports::DETACH)));
+		}
+
+		self.render_transitions(block, fragment);
+		self.render_animation(block, fragment);
+	}
+
+	fn render_attributes(&mut self, block: &mut Block, fragment: &Fragment) {
+		if fragment.attributes.len() == 0 {
+			return;
+		}
+		if fragment.attributes.iter().any(|attribute| attribute.key.starts_with("...")) {
+			self.render_spread_attributes(block, fragment);
+			return;
+		}
+
+		let is_svg = fragment.namespace.as_deref() == Some(namespaces::SVG);
+		let is_custom_element = is_custom_element(fragment);
+		for attribute in &fragment.attributes {
+			// svg is picky about casing, and xlink:href and friends live in their own namespace
+			let key = if is_svg { fix_attribute_casing(&attribute.key) } else { attribute.key.clone() };
+			let property = match fragment.ttype {
+				FragmentType::Basic if fragment.namespace.is_none() && !is_custom_element => {
+					attribute_lookup::get_property(&fragment.name, &key)
+				}
+				_ => None,
+			};
+			if let Some(property) = property {
+				self.render_property(block, fragment, attribute, property);
+				continue;
+			}
+
+			let function = if key.starts_with("xlink:") {
+				&*imports::XLINK_ATTR
+			} else if is_custom_element {
+				// custom elements can take their props as properties, which can be more than strings
+				&*imports::SET_CUSTOM_ELEMENT_DATA
+			} else {
+				&*imports::ATTR
+			};
+			let function = self.dom_function(function);
+
+			let mut setter = "\"\"".to_string();
+			if let Some(value) = &attribute.value {
+				if strings::contains_handlebars(value) {
+					// add the extra property
+					let name = format!("{}_{}_{}",
+						fragment.short_name,
+						strings::to_identifier(&attribute.key),
+						"value" // TODO: can this be anything else?
+					);
+					block.variables.push_str(&format!("let {};\n", name));
+
+					let (dynamic_setter, dependencies) = self.render_attribute_value(value);
+					setter = format!("{} = {}", name, dynamic_setter);
+					if dependencies.len() > 0 {
+						block.p.push_str(&format!("if ({} && {} !== ({})) {{\n\t{}({}, {}, {});\n}}\n",
+							self.render_dirty_check(&dependencies),
+							name,
+							setter,
+							function,
 							fragment.short_name,
-							&attribute.key,
-							"value" // TODO: can this be anything else?
-						);
-						let extras = self.extra_variables.entry(fragment.short_name.clone()).or_default();
-						extras.push(name.clone());
-
-						let pieces = strings::split_handlebars_into_pieces(&value);
-						let mut dynamic_setter = String::new();
-						for (i, piece) in pieces.iter().enumerate() {
-							match piece.ttype {
-								HandlebarType::Text => {
-									dynamic_setter.push_str(&format!("\"{}\"", piece.value));
-								}
-								HandlebarType::Code => {
-									// TODO: validate that these code portions are correct
-									dynamic_setter.push_str(&format!("{}", piece.value));
-								}
-							}
-
-							if i < pieces.len()-1 {
-								dynamic_setter.push_str(" + ");
-							}
-						}
+							strings::to_js_string(&key),
+							name
+						));
+					}
+				} else {
+					setter = strings::to_js_string(&entities::decode_character_references(value, true));
+				}
+			}
+			block.create(&format!("{}({}, {}, {});\n",
+				function,
+				fragment.short_name,
+				strings::to_js_string(&key),
+				setter
+			));
+		}
+	}
+
+	// Sets an attribute as a property instead, ex: `input.checked = true`
+	fn render_property(&mut self, block: &mut Block, fragment: &Fragment, attribute: &Attribute, property: &AttributeLookup) {
+		let target = format!("{}.{}", fragment.short_name, property.property_name);
+		let value = match &attribute.value {
+			// being there at all is what counts, ex: disabled="false" still disables
+			None => "true".to_string(),
+			Some(value) if property.is_boolean && !strings::contains_handlebars(value) => "true".to_string(),
+			Some(value) if !strings::contains_handlebars(value) => {
+				strings::to_js_string(&entities::decode_character_references(value, true))
+			}
+			Some(value) => {
+				let name = format!("{}_{}_value", fragment.short_name, strings::to_identifier(&attribute.key));
+				block.variables.push_str(&format!("let {};\n", name));
+
+				let (setter, dependencies) = self.render_attribute_value(value);
+				if dependencies.len() > 0 {
+					block.p.push_str(&format!("if ({} && {} !== ({} = {})) {{\n\t{} = {};\n}}\n",
+						self.render_dirty_check(&dependencies),
+						name,
+						name,
+						setter,
+						target,
+						name
+					));
+				}
+				format!("{} = {}", name, setter)
+			}
+		};
+		block.create(&format!("{} = {};\n", target, value));
+	}
+
+	// Spreads can set any attribute, so everything gets merged together and set in one go
+	fn render_spread_attributes(&mut self, block: &mut Block, fragment: &Fragment) {
+		let name = &fragment.short_name;
+		let levels_name = format!("{}_levels", name);
+		let data = format!("{}_data", name);
+
+		let mut levels = vec![];
+		let mut updates = vec![];
+		let mut dependencies = vec![];
+		for (i, attribute) in fragment.attributes.iter().enumerate() {
+			let (level, level_dependencies) = match attribute.key.strip_prefix("...") {
+				Some(spread) => self.render_expression(spread),
+				None => {
+					let key = match fragment.namespace.as_deref() {
+						Some(namespaces::SVG) => fix_attribute_casing(&attribute.key),
+						_ => attribute.key.clone(),
+					};
+					let (value, value_dependencies) = match &attribute.value {
+						Some(value) => self.render_attribute_value(value),
+						None => ("\"\"".to_string(), vec![]),
+					};
+					(format!("{{ {}: {} }}", strings::to_property_key(&key), value), value_dependencies)
+				}
+			};
+			if level_dependencies.len() > 0 {
+				updates.push(format!("{} && {}", self.render_dirty_check(&level_dependencies), level));
+			} else {
+				updates.push(format!("{}[{}]", levels_name, i));
+			}
+			dependencies.extend(level_dependencies);
+			levels.push(level);
+		}
+		dependencies.sort();
+		dependencies.dedup();
+
+		self.compiler.insert_import(&imports::ASSIGN);
+		// svg attributes can't be set as properties, so they always go through setAttribute
+		let set_attributes = match fragment.namespace.as_deref() {
+			Some(namespaces::SVG) => &*imports::SET_SVG_ATTRIBUTES,
+			_ if is_custom_element(fragment) => &*imports::SET_CUSTOM_ELEMENT_DATA_MAP,
+			_ => &*imports::SET_ATTRIBUTES,
+		};
+		self.compiler.insert_import(set_attributes);
+		block.variables.push_str(&format!("let {} = [\n{}\n];\n", levels_name, strings::indent_block(&levels.join(",\n"), 1)));
+		block.variables.push_str(&format!("let {} = {{}};\n", data));
+		block.variables.push_str(&format!("for (let i = 0; i < {}.length; i += 1) {{\n\t{} = {}({}, {}[i]);\n}}\n",
+			levels_name,
+			data,
+			imports::ASSIGN.function,
+			data,
+			levels_name
+		));
+		block.create(&format!("{}({}, {});\n", set_attributes.function, name, data));
+
+		if dependencies.len() > 0 {
+			self.compiler.insert_import(&imports::GET_SPREAD_UPDATE);
+			block.p.push_str(&format!("{}({}, {} = {}({}, [\n{}\n]));\n",
+				set_attributes.function,
+				name,
+				data,
+				imports::GET_SPREAD_UPDATE.function,
+				levels_name,
+				strings::indent_block(&updates.join(",\n"), 1)
+			));
+		}
+	}
+
+	// Returns the function that handles an event, hoisting it into the instance if it needs to be
+	fn render_handler(&mut self, block: &mut Block, directive: &Directive) -> String {
+		let name = match &directive.handler {
+			Some(name) => name,
+			None => {
+				// just a reference to a function, ex: on:click={handle}
+				let value = directive.value.as_deref().unwrap();
+				return self.render_expression(strings::strip_mustache(value).unwrap()).0;
+			}
+		};
+		let index = self.variables.iter().position(|variable| variable == name).unwrap();
+		let reference = format!("/*{}*/ {}[{}]", name, constants::CTX, index);
+
+		let value = match &directive.value {
+			Some(value) => value,
+			None => {
+				// on:click without a value forwards the event to whoever's using us
+				self.compiler.insert_import(&imports::BUBBLE);
+				self.handlers.push(format!("function {}(event) {{\n\t{}.call(this, {}, event);\n}}",
+					name,
+					imports::BUBBLE.function,
+					constants::GLOBAL_SELF
+				));
+				return reference;
+			}
+		};
+		let expression = match strings::strip_mustache(value) {
+			Some(expression) => expression,
+			None => self.compiler.error(&format!("Event handler must be a JavaScript expression enclosed in curly braces: {}", value)),
+		};
+
+		// handlers inside of blocks might use values that only exist there (ex: the item in an each),
+		// so the instance takes those as arguments and the block passes them along
+		let mut locals: Vec<String> = vec![];
+		let contexts = &self.contexts;
+		script::rewrite_identifiers(expression, |identifier| {
+			let is_local = contexts.iter().skip(1).any(|scope| scope.contains_key(identifier));
+			if is_local && !locals.iter().any(|local| local == identifier) {
+				locals.push(identifier.to_string());
+			}
+			return None;
+		});
+		if locals.len() == 0 {
+			self.handlers.push(format!("const {} = {};", name, expression));
+			return reference;
+		}
+
+		self.handlers.push(format!("const {} = ({}) => {};", name, locals.join(", "), expression));
+		let arguments = locals.iter()
+			.map(|local| self.render_expression(local).0)
+			.collect::<Vec<String>>();
+		block.variables.push_str(&format!("function {}(...args) {{\n\treturn {}({}).apply(this, args);\n}}\n",
+			name,
+			reference,
+			arguments.join(", ")
+		));
+		return name.clone();
+	}
+
+	// Adds a listener for each on: directive, ex: `listen(button, "click", prevent_default(ctx[0]))`
+	fn render_listeners(&mut self, block: &mut Block, fragment: &Fragment, target: &str) {
+		for directive in &fragment.directives {
+			if directive.ttype != DirectiveType::On {
+				continue;
+			}
+
+			let mut handler = self.render_handler(block, directive);
+			let mut options = vec![];
+			for modifier in &directive.modifiers {
+				let wrapper = match modifier.as_str() {
+					"preventDefault" => &*imports::PREVENT_DEFAULT,
+					"stopPropagation" => &*imports::STOP_PROPAGATION,
+					"self" => &*imports::SELF,
+					"trusted" => &*imports::TRUSTED,
+					"once" | "capture" | "passive" => {
+						options.push(format!("{}: true", modifier));
+						continue;
+					}
+					"nonpassive" => {
+						options.push("passive: false".to_string());
+						continue;
+					}
+					_ => self.compiler.error(&format!("Valid event modifiers are preventDefault, stopPropagation, capture, once, passive, nonpassive, self or trusted, not '{}'", modifier)),
+				};
+				self.compiler.insert_import(wrapper);
+				handler = format!("{}({})", wrapper.function, handler);
+			}
+			if directive.has_modifier("passive") && directive.has_modifier("nonpassive") {
+				self.compiler.error("The 'passive' and 'nonpassive' modifiers cannot be used together");
+			}
+
+			let options = match options.len() {
+				0 => String::new(),
+				1 if directive.has_modifier("capture") => ", true".to_string(),
+				_ => format!(", {{ {} }}", options.join(", ")),
+			};
+			block.listeners.push(format!("{}({}, {}, {}{})",
+				self.dom_function(&imports::LISTEN),
+				target,
+				strings::to_js_string(&directive.name),
+				handler,
+				options
+			));
+		}
+	}
+
+	// <svelte:head>, whose children get mounted into document.head
+	fn render_head(&mut self, block: &mut Block, fragment: &Fragment) {
+		// the server marks where our part of the head starts and ends, since other components add to it too
+		let claims_nodes = fragment.children.iter()
+			.any(|child| !(child.is_whitespace() || (child.ttype == FragmentType::Basic && child.name == "title")));
+		if claims_nodes {
+			block.claimer.push_str(&format!("const {} = {}(\"svelte-{}\", document.head);\n",
+				constants::HEAD_NODES,
+				self.claim_function(&imports::HEAD_SELECTOR),
+				self.compiler.hash
+			));
+		}
+		for child in &fragment.children {
+			if child.is_whitespace() {
+				continue;
+			}
+			if child.ttype == FragmentType::Basic && child.name == "title" {
+				self.render_title(block, child);
+			} else {
+				self.render_fragment(block, Some(fragment), child);
+			}
+		}
+		if claims_nodes {
+			block.claimer.push_str(&format!("{}.forEach({});\n", constants::HEAD_NODES, self.claim_function(&imports::DETACH)));
+		}
+	}
+
+	// <title> just sets document.title
+	fn render_title(&mut self, block: &mut Block, fragment: &Fragment) {
+		let mut pieces = vec![];
+		let mut dependencies = vec![];
+		for child in &fragment.children {
+			let text = child.data.as_deref().unwrap_or("");
+			match child.ttype {
+				FragmentType::Text if child.contains_code => {
+					let (code, code_dependencies) = self.render_expression(text);
+					pieces.push(code);
+					dependencies.extend(code_dependencies);
+				}
+				FragmentType::Text => pieces.push(strings::to_js_string(text)),
+				FragmentType::Comment => {}
+				_ => self.compiler.error("<title> can only contain text and {tags}"),
+			}
+		}
+		if pieces.len() == 0 {
+			pieces.push("\"\"".to_string());
+		}
+		let value = pieces.join(" + ");
+		dependencies.sort();
+		dependencies.dedup();
+
+		if dependencies.len() == 0 {
+			block.variables.push_str(&format!("document.title = {};\n", value));
+			return;
+		}
+		let name = format!("{}_value", fragment.short_name);
+		block.variables.push_str(&format!("let {};\n", name));
+		block.variables.push_str(&format!("document.title = {} = {};\n", name, value));
+		block.p.push_str(&format!("if ({} && {} !== ({} = {})) {{\n\tdocument.title = {};\n}}\n",
+			self.render_dirty_check(&dependencies),
+			name,
+			name,
+			value,
+			name
+		));
+	}
+
+	// <svelte:window>, <svelte:body> and <svelte:document> just listen to events on the globals they stand for
+	fn render_special_fragment(&mut self, block: &mut Block, fragment: &Fragment) {
+		let target = match fragment.ttype {
+			FragmentType::Window => "window",
+			FragmentType::Body => "document.body",
+			_ => "document",
+		};
+		self.render_listeners(block, fragment, target);
+
+		// bindings are grouped by the event that updates them, ex: resize for innerWidth and innerHeight
+		let mut groups: Vec<BindingGroup> = vec![];
+		for directive in &fragment.directives {
+			if directive.ttype != DirectiveType::Binding {
+				continue;
+			}
+			let binding = match fragment.ttype {
+				FragmentType::Window => get_window_binding(&directive.name),
+				_ => None,
+			};
+			let (handler, events, value) = match binding {
+				Some(binding) => binding,
+				None => self.compiler.error(&format!("'{}' is not a valid binding on <{}>", directive.name, fragment.name)),
+			};
+			let variable = match &directive.value {
+				Some(value) => match strings::strip_mustache(value) {
+					Some(variable) => variable.to_string(),
+					None => self.compiler.error(&format!("Can only bind to an identifier, not {}", value)),
+				},
+				None => directive.name.clone(),
+			};
+			if !self.variables.contains(&variable) {
+				self.compiler.error(&format!("'{}' is not defined", variable));
+			}
 
-						setter = format!("{} = {}", name, dynamic_setter);
+			match groups.iter_mut().find(|(existing, _, _)| *existing == handler) {
+				Some((_, _, bindings)) => bindings.push((variable, value)),
+				None => groups.push((handler, events, vec![(variable, value)])),
+			}
+		}
+
+		for (handler, events, bindings) in groups {
+			let assignments = bindings.iter()
+				.map(|(variable, value)| format!("\t{} = {};", variable, value))
+				.collect::<Vec<String>>();
+			self.handlers.push(format!("function {}() {{\n{}\n}}", handler, assignments.join("\n")));
+
+			// grab the initial values once we're mounted
+			let reference = self.render_expression(handler).0;
+			self.compiler.insert_import(&imports::ADD_RENDER_CALLBACK);
+			block.variables.push_str(&format!("{}({});\n", imports::ADD_RENDER_CALLBACK.function, reference));
+
+			if handler != "onwindowscroll" {
+				for event in events {
+					let listen = self.dom_function(&imports::LISTEN);
+					block.listeners.push(format!("{}({}, \"{}\", {})", listen, target, event, reference));
+				}
+				continue;
+			}
+
+			// scrolling is two-way, but we shouldn't scroll the window while the user is scrolling it
+			block.variables.push_str("let scrolling = false;\nlet clear_scrolling = () => {\n\tscrolling = false;\n};\nlet scrolling_timeout;\n");
+			block.listeners.push(format!("{}({}, \"scroll\", () => {{\n\tscrolling = true;\n\tclearTimeout(scrolling_timeout);\n\tscrolling_timeout = setTimeout(clear_scrolling, 100);\n\t{}();\n}})",
+				self.dom_function(&imports::LISTEN),
+				target,
+				reference
+			));
+
+			let x = bindings.iter().find(|(_, value)| value.ends_with("pageXOffset")).map(|(variable, _)| self.render_expression(variable));
+			let y = bindings.iter().find(|(_, value)| value.ends_with("pageYOffset")).map(|(variable, _)| self.render_expression(variable));
+			let mut dependencies = vec![];
+			for (_, variable_dependencies) in x.iter().chain(y.iter()) {
+				dependencies.extend(variable_dependencies.iter().cloned());
+			}
+			block.p.push_str(&format!("if ({} && !scrolling) {{\n\tscrolling = true;\n\tclearTimeout(scrolling_timeout);\n\tscrollTo({}, {});\n\tscrolling_timeout = setTimeout(clear_scrolling, 100);\n}}\n",
+				self.render_dirty_check(&dependencies),
+				x.map(|(code, _)| code).unwrap_or("window.pageXOffset".to_string()),
+				y.map(|(code, _)| code).unwrap_or("window.pageYOffset".to_string())
+			));
+		}
+	}
+
+	// Turns an attribute's value into a js expression, ex: `a {b}` becomes `"a " + /*b*/ ctx[0]`
+	fn render_attribute_value(&mut self, value: &str) -> (String, Vec<usize>) {
+		let pieces = strings::split_handlebars_into_pieces(value);
+		let mut result = String::new();
+		let mut dependencies = vec![];
+		for (i, piece) in pieces.iter().enumerate() {
+			match piece.ttype {
+				HandlebarType::Text => {
+					result.push_str(&strings::to_js_string(&entities::decode_character_references(&piece.value, true)));
+				}
+				HandlebarType::Code => {
+					// TODO: validate that these code portions are correct
+					let (code, piece_dependencies) = self.render_expression(piece.value.trim());
+					if pieces.len() > 1 && !strings::is_identifier(piece.value.trim()) {
+						// keep the expression together, ex: "a" + (b || c)
+						result.push_str(&format!("({})", code));
 					} else {
-						// TODO: handle non-strings, bools and ints etc.
-						setter = format!("\"{}\"", value);
+						result.push_str(&code);
 					}
+					dependencies.extend(piece_dependencies);
 				}
-				result.push_str(&format!("{}({}, \"{}\", {});\n",
-					imports::ATTR.function,
+			}
+
+			if i < pieces.len()-1 {
+				result.push_str(" + ");
+			}
+		}
+		if pieces.len() == 0 {
+			result.push_str("\"\"");
+		}
+
+		dependencies.sort();
+		dependencies.dedup();
+		return (result, dependencies);
+	}
+
+	// Directive values are expressions, ex: the `{ y: 200 }` in `in:fly={{ y: 200 }}`
+	fn render_directive_value(&mut self, directive: &Directive) -> Option<String> {
+		let value = directive.value.as_ref()?;
+		let expression = match strings::strip_mustache(value) {
+			Some(expression) => expression,
+			None => self.compiler.error(&format!("Directive value must be a JavaScript expression enclosed in curly braces: {}", value)),
+		};
+		return Some(self.render_expression(expression).0);
+	}
+
+	fn render_transitions(&mut self, block: &mut Block, fragment: &Fragment) {
+		let mut transition = None;
+		let mut intro = None;
+		let mut outro = None;
+		for directive in &fragment.directives {
+			let slot = match directive.ttype {
+				DirectiveType::Transition => &mut transition,
+				DirectiveType::In => &mut intro,
+				DirectiveType::Out => &mut outro,
+				_ => continue,
+			};
+			if slot.is_some() {
+				self.compiler.error(&format!("An element can only have one '{}' directive", directive.name));
+			}
+			*slot = Some(directive);
+		}
+
+		if transition.is_some() && (intro.is_some() || outro.is_some()) {
+			self.compiler.error(&format!("An element cannot have both a 'transition' directive and an '{}' directive",
+				if intro.is_some() { "in" } else { "out" }
+			));
+		}
+
+		let name = &fragment.short_name;
+		if let Some(transition) = transition {
+			let variable = format!("{}_transition", name);
+			let function = self.render_expression(&transition.name).0;
+			let params = self.render_directive_value(transition).unwrap_or("{}".to_string());
+			block.variables.push_str(&format!("let {};\n", variable));
+
+			self.compiler.insert_import(&imports::ADD_RENDER_CALLBACK);
+			self.compiler.insert_import(&imports::CREATE_BIDIRECTIONAL_TRANSITION);
+			let mut transition_in = format!("{}(() => {{\n\tif (!{}) {} = {}({}, {}, {}, true);\n\t{}.run(1);\n}});\n",
+				imports::ADD_RENDER_CALLBACK.function,
+				variable,
+				variable,
+				imports::CREATE_BIDIRECTIONAL_TRANSITION.function,
+				name,
+				function,
+				params,
+				variable
+			);
+			let mut transition_out = format!("if (!{}) {} = {}({}, {}, {}, false);\n{}.run(0);\n",
+				variable,
+				variable,
+				imports::CREATE_BIDIRECTIONAL_TRANSITION.function,
+				name,
+				function,
+				params,
+				variable
+			);
+			if transition.has_modifier("local") {
+				transition_in = self.render_local_only(&transition_in);
+				transition_out = self.render_local_only(&transition_out);
+			}
+			block.transition_in.push_str(&transition_in);
+			block.transition_out.push_str(&transition_out);
+			block.destroy.push_str(&format!("if ({} && {}) {}.end();\n", constants::DETACHING, variable, variable));
+			block.has_intros = true;
+			block.has_outros = true;
+			return;
+		}
+
+		let intro_variable = format!("{}_intro", name);
+		let outro_variable = format!("{}_outro", name);
+		if let Some(intro) = intro {
+			let function = self.render_expression(&intro.name).0;
+			let params = self.render_directive_value(intro).unwrap_or("{}".to_string());
+			block.variables.push_str(&format!("let {};\n", intro_variable));
+
+			self.compiler.insert_import(&imports::ADD_RENDER_CALLBACK);
+			self.compiler.insert_import(&imports::CREATE_IN_TRANSITION);
+			let mut transition_in = if outro.is_some() {
+				// the outro might still be running, so end it before we start
+				format!("{}(() => {{\n\tif ({}) {}.end(1);\n\t{} = {}({}, {}, {});\n\t{}.start();\n}});\n",
+					imports::ADD_RENDER_CALLBACK.function,
+					outro_variable,
+					outro_variable,
+					intro_variable,
+					imports::CREATE_IN_TRANSITION.function,
+					name,
+					function,
+					params,
+					intro_variable
+				)
+			} else {
+				// without an outro we only ever need to intro once
+				format!("if (!{}) {{\n\t{}(() => {{\n\t\t{} = {}({}, {}, {});\n\t\t{}.start();\n\t}});\n}}\n",
+					intro_variable,
+					imports::ADD_RENDER_CALLBACK.function,
+					intro_variable,
+					imports::CREATE_IN_TRANSITION.function,
+					name,
+					function,
+					params,
+					intro_variable
+				)
+			};
+			if intro.has_modifier("local") {
+				transition_in = self.render_local_only(&transition_in);
+			}
+			block.transition_in.push_str(&transition_in);
+			block.has_intros = true;
+		}
+
+		if let Some(outro) = outro {
+			let function = self.render_expression(&outro.name).0;
+			let params = self.render_directive_value(outro).unwrap_or("{}".to_string());
+			block.variables.push_str(&format!("let {};\n", outro_variable));
+
+			self.compiler.insert_import(&imports::CREATE_OUT_TRANSITION);
+			if intro.is_none() {
+				// if we're brought back before the outro finishes, cut it short
+				block.transition_in.push_str(&format!("if ({}) {}.end(1);\n", outro_variable, outro_variable));
+			}
+
+			let mut transition_out = String::new();
+			if intro.is_some() {
+				transition_out.push_str(&format!("if ({}) {}.invalidate();\n", intro_variable, intro_variable));
+			}
+			transition_out.push_str(&format!("{} = {}({}, {}, {});\n",
+				outro_variable,
+				imports::CREATE_OUT_TRANSITION.function,
+				name,
+				function,
+				params
+			));
+			if outro.has_modifier("local") {
+				transition_out = self.render_local_only(&transition_out);
+			}
+			block.transition_out.push_str(&transition_out);
+			block.destroy.push_str(&format!("if ({} && {}) {}.end();\n", constants::DETACHING, outro_variable, outro_variable));
+			block.has_intros = true;
+			block.has_outros = true;
+		}
+	}
+
+	// |local transitions only play when their own block is added or removed, not when a parent is
+	fn render_local_only(&self, code: &str) -> String {
+		return format!("if ({}) {{\n{}\n}}\n", constants::LOCAL, strings::indent_block(code, 1));
+	}
+
+	fn render_animation(&mut self, block: &mut Block, fragment: &Fragment) {
+		let animations = fragment.directives.iter()
+			.filter(|directive| directive.ttype == DirectiveType::Animate)
+			.collect::<Vec<&Directive>>();
+		if animations.len() == 0 {
+			return;
+		}
+		if animations.len() > 1 {
+			self.compiler.error("An element can only have one 'animate' directive");
+		}
+		if self.animated_element.as_deref() != Some(&fragment.short_name) {
+			self.compiler.error("An element that uses the animate directive must be the immediate child of a keyed each block");
+		}
+
+		let animation = animations[0];
+		let name = &fragment.short_name;
+		let function = self.render_expression(&animation.name).0;
+		let params = self.render_directive_value(animation).unwrap_or("{}".to_string());
+		let rect = self.get_internal_name(constants::RECT);
+		let stop_animation = self.get_internal_name(constants::STOP_ANIMATION);
+
+		self.compiler.insert_import(&imports::NOOP);
+		block.variables.push_str(&format!("let {};\n", rect));
+		block.variables.push_str(&format!("let {} = {};\n", stop_animation, imports::NOOP.function));
+
+		block.measure.push_str(&format!("{} = {}.getBoundingClientRect();\n", rect, name));
+
+		self.compiler.insert_import(&imports::FIX_POSITION);
+		block.fix.push_str(&format!("{}({});\n{}();\n", imports::FIX_POSITION.function, name, stop_animation));
+		let has_outro = fragment.directives.iter()
+			.any(|directive| directive.ttype == DirectiveType::Transition || directive.ttype == DirectiveType::Out);
+		if has_outro {
+			// keep the element where it was while it outros
+			self.compiler.insert_import(&imports::ADD_TRANSFORM);
+			block.fix.push_str(&format!("{}({}, {});\n", imports::ADD_TRANSFORM.function, name, rect));
+		}
+
+		self.compiler.insert_import(&imports::CREATE_ANIMATION);
+		block.animate.push_str(&format!("{}();\n{} = {}({}, {}, {}, {});\n",
+			stop_animation,
+			stop_animation,
+			imports::CREATE_ANIMATION.function,
+			name,
+			rect,
+			function,
+			params
+		));
+	}
+
+	fn render_text_fragment(&mut self, block: &mut Block, parent: Option<&Fragment>, fragment: &Fragment) {
+		let text = match &fragment.data {
+			Some(text) => text,
+			None => return,
+		};
+
+		// the whitespace between elements gets its own helper, ex: <b>a</b> <i>b</i>
+		let is_space = text == " " && !fragment.contains_code;
+		let import = if is_space { &*imports::SPACE } else { &*imports::TEXT };
+		self.compiler.insert_import(import);
+		let function = &import.function;
+		block.variables.push_str(&format!("let {};\n", fragment.short_name));
+
+		let value = if text.len() == 0 || is_space {
+			String::new()
+		} else if fragment.contains_code {
+			let (code, dependencies) = self.render_expression(text);
+			if dependencies.len() > 0 {
+				block.p.push_str(&format!("if ({}) {}({}, {});\n",
+					self.render_dirty_check(&dependencies),
+					self.dom_function(&imports::SET_DATA),
 					fragment.short_name,
-					attribute.key,
-					setter
+					code
 				));
 			}
-		}
+			code
+		} else {
+			strings::to_js_string(text)
+		};
 
-		result.push_str(&self.render_fragments_creator(Some(&fragment), &fragment.children));
-		return result;
+		let claim = if is_space {
+			format!("{}({})", self.claim_function(&imports::CLAIM_SPACE), self.get_claim_nodes(parent))
+		} else {
+			let value = if value.len() > 0 { &value } else { "\"\"" };
+			format!("{}({}, {})", self.claim_function(&imports::CLAIM_TEXT), self.get_claim_nodes(parent), value)
+		};
+		block.create_node(
+			&format!("{} = {}({});\n", fragment.short_name, function, value),
+			&format!("{} = {};\n", fragment.short_name, claim)
+		);
+
+		self.render_mount(block, parent, &fragment.short_name);
 	}
 
-	fn render_text_fragment_creator(&mut self, parent: Option<&Fragment>, fragment: &Fragment) -> String {
-		let mut result = String::new();
-		if let Some(text) = &fragment.data {
-			if let Some(parent) = parent {
-				let text = if strings::contains_handlebars(&text) {
-					let mut new_text = String::new();
-					new_text.push('`');
-					let pieces = strings::split_handlebars_into_pieces(&text);
-					for (i, piece) in pieces.iter().enumerate() {
-						match piece.ttype {
-							HandlebarType::Text => {
-								new_text.push_str(&format!("{}", piece.value));
-							}
-							HandlebarType::Code => {
-								// TODO: validate that these code portions are correct
-								new_text.push_str(&format!("${{{}}}", piece.value));
-							}
+	// Only happens with preserveComments
+	fn render_comment(&mut self, block: &mut Block, parent: Option<&Fragment>, fragment: &Fragment) {
+		block.variables.push_str(&format!("let {};\n", fragment.short_name));
+		let data = strings::to_js_string(fragment.data.as_deref().unwrap_or(""));
+		block.create_node(
+			&format!("{} = document.createComment({});\n", fragment.short_name, data),
+			&format!("{} = {}({}, {});\n",
+				fragment.short_name,
+				self.claim_function(&imports::CLAIM_COMMENT),
+				self.get_claim_nodes(parent),
+				data
+			)
+		);
+		self.render_mount(block, parent, &fragment.short_name);
+	}
+
+	// Returns each prop we pass to a component, in order, along with what it depends on.
+	// Spreads, ex: {...props}, don't have a key.
+	fn attributes_to_props(&mut self, fragment: &Fragment) -> Vec<(Option<String>, String, Vec<usize>)> {
+		let mut props = vec![];
+		for attribute in &fragment.attributes {
+			if let Some(spread) = attribute.key.strip_prefix("...") {
+				let (value, dependencies) = self.render_expression(spread);
+				props.push((None, value, dependencies));
+				continue;
+			}
+			let (value, dependencies) = match &attribute.value {
+				Some(value) => self.render_attribute_value(value),
+				None => ("true".to_string(), vec![]),
+			};
+			props.push((Some(attribute.key.clone()), value, dependencies));
+		}
+		return props;
+	}
+
+	fn render_svelte_fragment(&mut self, block: &mut Block, parent: Option<&Fragment>, fragment: &Fragment) {
+		let name = &fragment.short_name;
+		let is_dynamic = fragment.name == constants::SVELTE_COMPONENT;
+
+		let props = self.attributes_to_props(fragment);
+		let has_spread = props.iter().any(|(key, _, _)| key.is_none());
+		// slot contents only need our ctx when something they read changes, not counting their own let:s
+		let first_slot_context = self.context_count;
+		let outer_dependencies = std::mem::take(&mut self.read_dependencies);
+		let slots = self.render_slot_definitions(fragment);
+		let slot_dependencies = std::mem::replace(&mut self.read_dependencies, outer_dependencies).into_iter()
+			.filter(|dependency| *dependency < first_slot_context)
+			.collect::<Vec<usize>>();
+		let mut internal_props = vec![];
+		if slots.len() > 0 {
+			internal_props.push(format!("{}: {{\n{}\n}}", constants::GLOBAL_SLOTS, strings::indent_block(&slots.join(",\n"), 1)));
+			internal_props.push(format!("{}: {{ {} }}", constants::GLOBAL_SCOPE, constants::CTX));
+		}
+
+		block.variables.push_str(&format!("let {};\n", name));
+		let spread_levels = format!("{}_spread_levels", name);
+		let mut args = String::new();
+		let mut props_setup = String::new(); // dynamic components need their props built again for each new instance
+		if has_spread {
+			// spreads can set anything, so the props get merged together in order
+			let levels = props.iter()
+				.map(|(key, value, _)| match key {
+					Some(key) => format!("{{ {}: {} }}", strings::to_property_key(key), value),
+					None => value.clone(),
+				})
+				.collect::<Vec<String>>();
+			let component_props = format!("{}_props", name);
+			self.compiler.insert_import(&imports::ASSIGN);
+			block.variables.push_str(&format!("const {} = [\n{}\n];\n", spread_levels, strings::indent_block(&levels.join(",\n"), 1)));
+			if internal_props.len() > 0 {
+				props_setup.push_str(&format!("let {} = {{\n{}\n}};\n", component_props, strings::indent_block(&internal_props.join(",\n"), 1)));
+			} else {
+				props_setup.push_str(&format!("let {} = {{}};\n", component_props));
+			}
+			props_setup.push_str(&format!("for (let i = 0; i < {}.length; i += 1) {{\n\t{} = {}({}, {}[i]);\n}}\n",
+				spread_levels,
+				component_props,
+				imports::ASSIGN.function,
+				component_props,
+				spread_levels
+			));
+			args.push_str(&format!(" {}: {} ", constants::PROPS, component_props));
+		} else {
+			let mut entries = props.iter()
+				.map(|(key, value, _)| format!("{}: {}", strings::to_property_key(key.as_ref().unwrap()), value))
+				.collect::<Vec<String>>();
+			entries.extend(internal_props);
+			if entries.len() > 0 {
+				args.push_str(&format!("\n\t{}: {{\n{}\n\t}}\n", constants::PROPS, strings::indent_block(&entries.join(",\n"), 2)));
+			}
+		}
+
+		// events from components don't need cleaning up, they go away with the component
+		let mut events = String::new();
+		for directive in &fragment.directives {
+			match directive.ttype {
+				DirectiveType::On => {
+					if directive.modifiers.len() > 0 {
+						self.compiler.error("Event modifiers can only be used on DOM elements");
+					}
+					let handler = self.render_handler(block, directive);
+					events.push_str(&format!("{}.$on({}, {});\n", name, strings::to_js_string(&directive.name), handler));
+				}
+				DirectiveType::Binding => self.compiler.error("bind: is only supported on <svelte:window> for now"),
+				_ => {}
+			}
+		}
+
+		self.compiler.insert_import(&imports::CREATE_COMPONENT);
+		self.compiler.insert_import(&imports::MOUNT_COMPONENT);
+		self.compiler.insert_import(&imports::TRANSITION_IN);
+		self.compiler.insert_import(&imports::TRANSITION_OUT);
+		self.compiler.insert_import(&imports::DESTROY_COMPONENT);
+		let create = format!("{}({}.{});\n", imports::CREATE_COMPONENT.function, name, constants::GLOBAL_FRAGMENT);
+		let claim = format!("{}({}.{}, {});\n",
+			self.claim_function(&imports::CLAIM_COMPONENT),
+			name,
+			constants::GLOBAL_FRAGMENT,
+			self.get_claim_nodes(parent)
+		);
+		let mount = format!("{}({}, {});\n", imports::MOUNT_COMPONENT.function, name, self.get_mount_target(parent));
+		let transition_in = format!("{}({}.{}, {});\n", imports::TRANSITION_IN.function, name, constants::GLOBAL_FRAGMENT, constants::LOCAL);
+		let transition_out = format!("{}({}.{}, {});\n", imports::TRANSITION_OUT.function, name, constants::GLOBAL_FRAGMENT, constants::LOCAL);
+		let destroy = match parent {
+			Some(_) => format!("{}({});\n", imports::DESTROY_COMPONENT.function, name),
+			None => format!("{}({}, {});\n", imports::DESTROY_COMPONENT.function, name, constants::DETACHING),
+		};
+
+		// <svelte:component this={x}> gets swapped out whenever x changes
+		let mut switch = None;
+		if is_dynamic {
+			let (constructor, _) = self.render_expression(fragment.data.as_deref().unwrap());
+			let switch_value = self.compiler.generate_short_name("switch_value");
+			let switch_props = self.compiler.generate_short_name("switch_props");
+			block.variables.push_str(&format!("var {} = {};\n", switch_value, constructor));
+			block.variables.push_str(&format!("function {}({}) {{\n{}\n}}\n",
+				switch_props,
+				constants::CTX,
+				strings::indent_block(&format!("{}return {{{}}};", props_setup, args), 1)
+			));
+			block.variables.push_str(&format!("if ({}) {{\n\t{} = new {}({}({}));\n{}}}\n",
+				switch_value,
+				name,
+				switch_value,
+				switch_props,
+				constants::CTX,
+				if events.len() > 0 { format!("{}\n", strings::indent_block(events.trim_end(), 1)) } else { String::new() }
+			));
+
+			block.create_node(&format!("if ({}) {}", name, create), &format!("if ({}) {}", name, claim));
+			block.mounter.push_str(&format!("if ({}) {}", name, mount));
+			block.transition_in.push_str(&format!("if ({}) {}", name, transition_in));
+			block.transition_out.push_str(&format!("if ({}) {}", name, transition_out));
+			block.destroy.push_str(&format!("if ({}) {}", name, destroy));
+			switch = Some((switch_value, switch_props, constructor));
+		} else {
+			let constructor = if fragment.name == constants::SVELTE_SELF { constants::COMPONENT } else { &fragment.name };
+			block.variables.push_str(&props_setup);
+			block.variables.push_str(&format!("{} = new {}({{{}}});\n", name, constructor, args));
+			block.variables.push_str(&events);
+
+			block.create_node(&create, &claim);
+			block.mounter.push_str(&mount);
+			block.transition_in.push_str(&transition_in);
+			block.transition_out.push_str(&transition_out);
+			block.destroy.push_str(&destroy);
+		}
+
+		// components can always have transitions of their own
+		block.has_intros = true;
+		block.has_outros = true;
+
+		// pass along whatever changed
+		let changes = format!("{}_changes", name);
+		let mut update = String::new();
+		let mut is_declared = false; // spreads declare the changes themselves
+		if has_spread {
+			let mut dependencies = props.iter()
+				.flat_map(|(_, _, dependencies)| dependencies.iter().cloned())
+				.collect::<Vec<usize>>();
+			dependencies.sort();
+			dependencies.dedup();
+			if dependencies.len() > 0 {
+				self.compiler.insert_import(&imports::GET_SPREAD_UPDATE);
+				let mut levels = vec![];
+				for (i, (key, value, dependencies)) in props.iter().enumerate() {
+					if dependencies.len() == 0 {
+						levels.push(format!("{}[{}]", spread_levels, i));
+						continue;
+					}
+					let check = self.render_dirty_check(dependencies);
+					match key {
+						Some(key) => levels.push(format!("{} && {{ {}: {} }}", check, strings::to_property_key(key), value)),
+						None => {
+							self.compiler.insert_import(&imports::GET_SPREAD_OBJECT);
+							levels.push(format!("{} && {}({})", check, imports::GET_SPREAD_OBJECT.function, value));
 						}
 					}
-					new_text.push('`');
-					new_text
-				} else {
-					// TODO: is this a necessary operation?
-					format!("\"{}\"", text)
-				};
+				}
+				update.push_str(&format!("const {} = ({})\n\t? {}({}, [\n{}\n\t])\n\t: {{}};\n",
+					changes,
+					self.render_dirty_check(&dependencies),
+					imports::GET_SPREAD_UPDATE.function,
+					spread_levels,
+					strings::indent_block(&levels.join(",\n"), 2)
+				));
+				is_declared = true;
+			}
+		} else {
+			for (key, value, dependencies) in &props {
+				if dependencies.len() > 0 {
+					update.push_str(&format!("if ({}) {}{} = {};\n",
+						self.render_dirty_check(dependencies),
+						changes,
+						strings::to_property_access(key.as_ref().unwrap()),
+						value
+					));
+				}
+			}
+		}
 
-				result.push_str(&format!("{}.{} = {};",
-					parent.short_name,
-					constants::TEXT_CONTENT,
-					text
+		// slot contents are rendered with our ctx, so hand it over whenever it changes
+		let mut dependencies = slot_dependencies;
+		dependencies.sort();
+		dependencies.dedup();
+		if slots.len() > 0 && dependencies.len() > 0 {
+			update.push_str(&format!("if ({}) {{\n\t{}.{} = {{ {}, {} }};\n}}\n",
+				self.render_dirty_check(&dependencies),
+				changes,
+				constants::GLOBAL_SCOPE,
+				constants::DIRTY,
+				constants::CTX
+			));
+		}
+
+		if update.len() > 0 && !is_declared {
+			block.p.push_str(&format!("const {} = {{}};\n", changes));
+		}
+		block.p.push_str(&update);
+		let set = format!("{}.$set({});\n", name, changes);
+		match switch {
+			Some((switch_value, switch_props, constructor)) => {
+				// the old component outros while the new one intros
+				let anchor = self.render_anchor(block, parent, name);
+				self.compiler.insert_import(&imports::GROUP_OUTROS);
+				self.compiler.insert_import(&imports::CHECK_OUTROS);
+				block.p.push_str(&format!("if ({} !== ({} = {})) {{\n", switch_value, switch_value, constructor));
+				block.p.push_str(&format!("\tif ({}) {{\n", name));
+				block.p.push_str(&format!("\t\t{}();\n", imports::GROUP_OUTROS.function));
+				block.p.push_str(&format!("\t\tconst old_component = {};\n", name));
+				block.p.push_str(&format!("\t\t{}(old_component.{}, 1, 0, () => {{\n\t\t\t{}(old_component, 1);\n\t\t}});\n",
+					imports::TRANSITION_OUT.function,
+					constants::GLOBAL_FRAGMENT,
+					imports::DESTROY_COMPONENT.function
 				));
+				block.p.push_str(&format!("\t\t{}();\n", imports::CHECK_OUTROS.function));
+				block.p.push_str("\t}\n");
+				block.p.push_str(&format!("\tif ({}) {{\n", switch_value));
+				block.p.push_str(&format!("\t\t{} = new {}({}({}));\n", name, switch_value, switch_props, constants::CTX));
+				if events.len() > 0 {
+					block.p.push_str(&strings::indent_block(&events, 2));
+					block.p.push_str("\n");
+				}
+				block.p.push_str(&format!("\t\t{}", create));
+				block.p.push_str(&format!("\t\t{}({}.{}, 1);\n", imports::TRANSITION_IN.function, name, constants::GLOBAL_FRAGMENT));
+				block.p.push_str(&format!("\t\t{}({}, {}.parentNode, {});\n", imports::MOUNT_COMPONENT.function, name, anchor, anchor));
+				block.p.push_str("\t} else {\n");
+				block.p.push_str(&format!("\t\t{} = null;\n", name));
+				block.p.push_str("\t}\n");
+				if update.len() > 0 {
+					block.p.push_str(&format!("}} else if ({}) {{\n\t{}", switch_value, set));
+				}
+				block.p.push_str("}\n");
+				self.render_mount(block, parent, &anchor);
+			}
+			None => {
+				if update.len() > 0 {
+					block.p.push_str(&set);
+				}
+			}
+		}
+	}
+
+	// Renders a block for each slot a component's children fill. Returns the entries of $$slots,
+	// ex: `default: [create_default_slot, ({ item }) => ({ 2: item }), ({ item }) => item ? 4 : 0]`
+	fn render_slot_definitions(&mut self, fragment: &Fragment) -> Vec<String> {
+		// gather up the contents of each slot, along with the let: directives it can use
+		let mut slots: Vec<(String, Vec<Fragment>, Vec<Directive>)> = vec![];
+		let default_content = fragment.children.iter()
+			.filter(|child| child.slot.is_none())
+			.cloned()
+			.collect::<Vec<Fragment>>();
+		if default_content.iter().any(|child| !child.is_whitespace()) {
+			slots.push((constants::DEFAULT_SLOT.to_string(), default_content, fragment.directives.clone()));
+		}
+		for child in &fragment.children {
+			let name = match &child.slot {
+				Some(name) => name.clone(),
+				None => continue,
+			};
+			if slots.iter().any(|(slot, _, _)| *slot == name) {
+				self.compiler.error(&format!("Duplicate slot name \"{}\" in <{}>", name, fragment.name));
+			}
+			let content = if child.ttype == FragmentType::SlotTemplate {
+				child.children.clone()
 			} else {
-				self.compiler.insert_import(&imports::TEXT);
-				let function = &imports::TEXT.function;
+				vec![child.clone()]
+			};
+			slots.push((name, content, child.directives.clone()));
+		}
 
-				if text.len() == 0 {
-					result.push_str(&format!("{} = {}();\n",
-						fragment.short_name,
-						function,
-					));
+		let mut definitions = vec![];
+		for (name, content, directives) in slots {
+			// let: directives get their own spots in ctx, filled in by the slot
+			let mut scope = HashMap::new();
+			let mut params = vec![];
+			let mut context = vec![];
+			let mut names = vec![];
+			let mut changes = vec![];
+			for directive in directives.iter().filter(|directive| directive.ttype == DirectiveType::Let) {
+				let pattern = match &directive.value {
+					Some(value) => match strings::strip_mustache(value) {
+						Some(pattern) => pattern.to_string(),
+						None => self.compiler.error(&format!("let: directive value must be a JavaScript expression enclosed in curly braces: {}", value)),
+					},
+					None => directive.name.clone(),
+				};
+
+				let mut indices = vec![];
+				for (binding, _) in strings::parse_each_context(&pattern) {
+					scope.insert(binding.clone(), ContextVariable {
+						index: self.context_count,
+						dependencies: vec![self.context_count],
+					});
+					context.push(format!("{}: {}", self.context_count, binding));
+					indices.push(self.context_count);
+					self.context_count += 1;
+				}
+				if pattern == directive.name {
+					params.push(pattern);
 				} else {
-					if fragment.contains_code {
-						result.push_str(&format!("{} = {}({});\n",
-							fragment.short_name,
-							function,
-							text
-						));
-					} else {
-						result.push_str(&format!("{} = {}(\"{}\");\n",
-							fragment.short_name,
-							function,
-							text
-						));
+					params.push(format!("{}: {}", directive.name, pattern));
+				}
+				names.push(directive.name.clone());
+				changes.push((directive.name.clone(), get_dirty_masks(&indices)));
+			}
+
+			let creator = self.compiler.generate_short_name(&format!("create_{}_slot", strings::to_identifier(&name)));
+			self.contexts.push(scope);
+			self.render_child_block(&creator, &content, false);
+			self.contexts.pop();
+
+			let mut definition = creator;
+			if params.len() > 0 {
+				// which bits of dirty each let: sets when it changes, with one entry per word once dirty is split up
+				let words = (0..self.dirty_words).map(|word| {
+					let changes = changes.iter()
+						.flat_map(|(name, masks)| masks.iter()
+							.filter(|(mask_word, _)| *mask_word == word)
+							.map(move |(_, mask)| format!("{} ? {} : 0", name, mask)))
+						.collect::<Vec<String>>();
+					match changes.len() {
+						0 => "0".to_string(),
+						1 => changes.join(""),
+						_ => changes.iter().map(|change| format!("({})", change)).collect::<Vec<String>>().join(" | "),
 					}
+				}).collect::<Vec<String>>();
+				let changes = if words.len() == 1 { words.join("") } else { format!("[{}]", words.join(", ")) };
+				definition = format!("{}, ({{ {} }}) => ({{ {} }}), ({{ {} }}) => {}",
+					definition,
+					params.join(", "),
+					context.join(", "),
+					names.join(", "),
+					changes
+				);
+			}
+			definitions.push(format!("{}: [{}]", strings::to_property_key(&name), definition));
+		}
+		return definitions;
+	}
+
+	// <slot>, which renders whatever the parent handed us, or its own contents if it didn't
+	fn render_slot(&mut self, block: &mut Block, parent: Option<&Fragment>, fragment: &Fragment) {
+		let slot_name = match fragment.attributes.iter().find(|attribute| attribute.key == "name") {
+			Some(attribute) => match &attribute.value {
+				Some(value) if !strings::contains_handlebars(value) => value.clone(),
+				_ => self.compiler.error("slot name cannot be dynamic"),
+			},
+			None => constants::DEFAULT_SLOT.to_string(),
+		};
+		let identifier = strings::to_identifier(&slot_name);
+		let name = self.compiler.generate_short_name(&format!("{}_slot", identifier));
+		let template = format!("{}_template", name);
+		let slots_index = self.variables.iter().position(|variable| variable == constants::SLOTS).unwrap();
+		let scope_index = self.variables.iter().position(|variable| variable == constants::GLOBAL_SCOPE).unwrap();
+		let scope = format!("/*{}*/ {}[{}]", constants::GLOBAL_SCOPE, constants::CTX, scope_index);
+
+		// slot props, ex: <slot item={x}>
+		let mut dependencies = vec![scope_index];
+		let mut context = vec![];
+		let mut changes = vec![];
+		for attribute in fragment.attributes.iter().filter(|attribute| attribute.key != "name") {
+			let (value, value_dependencies) = match &attribute.value {
+				Some(value) => self.render_attribute_value(value),
+				None => ("true".to_string(), vec![]),
+			};
+			let key = strings::to_property_key(&attribute.key);
+			context.push(format!("{}: {}", key, value));
+			if value_dependencies.len() > 0 {
+				changes.push(format!("{}: {}", key, self.render_dirty_check(&value_dependencies)));
+			}
+			dependencies.extend(value_dependencies);
+		}
+		dependencies.sort();
+		dependencies.dedup();
+
+		let (get_context, get_changes) = if context.len() > 0 {
+			let get_context = self.compiler.generate_short_name(&format!("get_{}_slot_context", identifier));
+			let get_changes = self.compiler.generate_short_name(&format!("get_{}_slot_changes", identifier));
+			self.blocks.push(format!("const {} = {} => ({{ {} }});\nconst {} = {} => ({{ {} }});\n",
+				get_changes,
+				constants::DIRTY,
+				changes.join(", "),
+				get_context,
+				constants::CTX,
+				context.join(", ")
+			));
+			(get_context, get_changes)
+		} else {
+			(constants::NULL.to_string(), constants::NULL.to_string())
+		};
+
+		self.compiler.insert_import(&imports::CREATE_SLOT);
+		block.variables.push_str(&format!("const {} = /*#{}*/ {}[{}]{};\n",
+			template,
+			constants::SLOTS,
+			constants::CTX,
+			slots_index,
+			strings::to_property_access(&slot_name)
+		));
+		block.variables.push_str(&format!("const {} = {}({}, {}, {}, {});\n",
+			name,
+			imports::CREATE_SLOT.function,
+			template,
+			constants::CTX,
+			scope,
+			get_context
+		));
+
+		// fallback content, for when nothing gets passed in
+		let has_fallback = fragment.children.len() > 0;
+		let mut target = name.clone();
+		let mut fallback_updates = false;
+		let mut fallback_dependencies = vec![];
+		if has_fallback {
+			let fallback = self.compiler.generate_short_name(constants::FALLBACK_BLOCK);
+			let outer_dependencies = std::mem::take(&mut self.read_dependencies);
+			let child = self.render_child_block(&fallback, &fragment.children, false);
+			fallback_dependencies = std::mem::replace(&mut self.read_dependencies, outer_dependencies);
+			fallback_dependencies.sort();
+			fallback_dependencies.dedup();
+			fallback_updates = child.p.len() > 0;
+			target = format!("{}_or_fallback", name);
+			block.variables.push_str(&format!("const {} = {} || {}({});\n", target, name, fallback, constants::CTX));
+		}
+
+		block.create_node(
+			&format!("if ({}) {}.c();\n", target, target),
+			&format!("if ({}) {}.l({});\n", target, target, self.get_claim_nodes(parent))
+		);
+		block.mounter.push_str(&format!("if ({}) {{\n\t{}.m({});\n}}\n", target, target, self.get_mount_target(parent)));
+
+		self.compiler.insert_import(&imports::UPDATE_SLOT_BASE);
+		self.compiler.insert_import(&imports::GET_SLOT_CHANGES);
+		self.compiler.insert_import(&imports::GET_ALL_DIRTY_FROM_SCOPE);
+		block.p.push_str(&format!("if ({}) {{\n", name));
+		block.p.push_str(&format!("\tif ({}.p && (!{} || {})) {{\n", name, constants::CURRENT, self.render_dirty_check(&dependencies)));
+		block.p.push_str(&format!("\t\t{}({}, {}, {}, {}, !{} ? {}({}) : {}({}, {}, {}, {}), {});\n",
+			imports::UPDATE_SLOT_BASE.function,
+			name,
+			template,
+			constants::CTX,
+			scope,
+			constants::CURRENT,
+			imports::GET_ALL_DIRTY_FROM_SCOPE.function,
+			scope,
+			imports::GET_SLOT_CHANGES.function,
+			template,
+			scope,
+			constants::DIRTY,
+			get_changes,
+			get_context
+		));
+		block.p.push_str("\t}\n");
+		if fallback_updates && fallback_dependencies.len() > 0 {
+			let dependencies = fallback_dependencies;
+			block.p.push_str("} else {\n");
+			block.p.push_str(&format!("\tif ({} && {}.p && (!{} || {})) {{\n",
+				target,
+				target,
+				constants::CURRENT,
+				self.render_dirty_check(&dependencies)
+			));
+			block.p.push_str(&format!("\t\t{}.p({}, !{} ? {} : {});\n", target, constants::CTX, constants::CURRENT, self.get_initial_dirty(), constants::DIRTY));
+			block.p.push_str("\t}\n");
+		}
+		block.p.push_str("}\n");
+
+		self.compiler.insert_import(&imports::TRANSITION_IN);
+		self.compiler.insert_import(&imports::TRANSITION_OUT);
+		block.transition_in.push_str(&format!("{}({}, {});\n", imports::TRANSITION_IN.function, target, constants::LOCAL));
+		block.transition_out.push_str(&format!("{}({}, {});\n", imports::TRANSITION_OUT.function, target, constants::LOCAL));
+		block.has_intros = true;
+		block.has_outros = true;
+
+		block.destroy.push_str(&format!("if ({}) {}.d({});\n",
+			target,
+			target,
+			self.get_detaching(parent)
+		));
+	}
+
+	// Renders the given fragments into their own block, which gets hoisted up with the others
+	fn render_child_block(&mut self, name: &str, fragments: &Vec<Fragment>, is_keyed: bool) -> Block {
+		let mut block = Block::new(name);
+		block.is_keyed = is_keyed;
+		if is_keyed {
+			// keyed blocks get moved around, so they need a handle on their first node
+			let first = self.get_internal_name(constants::FIRST);
+			self.compiler.insert_import(&imports::EMPTY);
+			block.variables.push_str(&format!("let {};\n", first));
+			block.create(&format!("{} = {}();\n", first, imports::EMPTY.function));
+			self.render_mount(&mut block, None, &first);
+		}
+
+		let has_consts = fragments.iter().any(|fragment| fragment.ttype == FragmentType::Const);
+		if has_consts {
+			self.render_consts(&mut block, fragments);
+		}
+		for fragment in fragments {
+			if fragment.ttype != FragmentType::Const {
+				self.render_fragment(&mut block, None, fragment);
+			}
+		}
+		if has_consts {
+			self.contexts.pop();
+		}
+
+		let rendered = self.render_block(&block);
+		self.blocks.push(rendered);
+		return block;
+	}
+
+	// {@const}s get their own spots in ctx, which get recalculated whenever the block gets a new ctx.
+	// NOTE: this leaves a new scope on the stack, which the caller needs to pop.
+	fn render_consts(&mut self, block: &mut Block, fragments: &Vec<Fragment>) {
+		let getter = format!("get_{}_context", block.name.trim_start_matches("create_"));
+		let mut body = format!("{} = {}.slice();\n", constants::CTX, constants::CTX);
+		self.contexts.push(HashMap::new());
+		for fragment in fragments.iter().filter(|fragment| fragment.ttype == FragmentType::Const) {
+			let (left, right) = strings::split_assignment(fragment.data.as_deref().unwrap()).unwrap();
+			let (value, dependencies) = self.render_expression(right);
+			body.push_str(&format!("const {} = {};\n", left, value));
+			let bindings = strings::parse_each_context(left);
+
+			for (binding, _) in bindings {
+				if self.contexts.last().unwrap().contains_key(&binding) {
+					self.compiler.error(&format!("'{}' has already been declared", binding));
 				}
+				body.push_str(&format!("{}[{}] = {};\n", constants::CTX, self.context_count, binding));
+				self.contexts.last_mut().unwrap().insert(binding, ContextVariable {
+					index: self.context_count,
+					dependencies: dependencies.clone(),
+				});
+				self.context_count += 1;
 			}
 		}
-		return result;
+		body.push_str(&format!("return {};", constants::CTX));
+
+		self.blocks.push(format!("function {}({}) {{\n{}\n}}\n", getter, constants::CTX, strings::indent_block(&body, 1)));
+		block.variables.push_str(&format!("{} = {}({});\n", constants::CTX, getter, constants::CTX));
+		block.context_getter = Some(getter);
 	}
 
-	fn render_svelte_fragment_creator(&mut self, parent: Option<&Fragment>, fragment: &Fragment) -> String {
-		self.compiler.insert_import(&imports::CREATE_COMPONENT);
+	// {@html x}, which gets parsed and inserted before an anchor
+	fn render_raw_html(&mut self, block: &mut Block, parent: Option<&Fragment>, fragment: &Fragment) {
+		let name = &fragment.short_name;
+		let (html, dependencies) = self.render_expression(fragment.data.as_deref().unwrap());
+
+		self.compiler.insert_import(&imports::HTML_TAG);
+		block.variables.push_str(&format!("let {};\n", name));
+		let is_svg = fragment.namespace.as_deref() == Some(namespaces::SVG);
+		block.create_node(
+			&format!("{} = new {}({});\n", name, imports::HTML_TAG.function, is_svg),
+			&format!("{} = {}({}, {});\n", name, self.claim_function(&imports::CLAIM_HTML_TAG), self.get_claim_nodes(parent), is_svg)
+		);
+		let anchor = self.render_anchor(block, parent, name);
+		block.create(&format!("{}.a = {};\n", name, anchor));
+		match parent {
+			Some(parent) => block.mounter.push_str(&format!("{}.m({}, {});\n", name, html, parent.short_name)),
+			None => block.mounter.push_str(&format!("{}.m({}, {}, {});\n", name, html, constants::TARGET, constants::ANCHOR)),
+		}
+		if dependencies.len() > 0 {
+			block.p.push_str(&format!("if ({}) {}.p({});\n", self.render_dirty_check(&dependencies), name, html));
+		}
 
-		let mut result = String::new();
-		result.push_str(&format!("{}({}.{});\n",
-			imports::CREATE_COMPONENT.function,
-			fragment.short_name,
-			constants::GLOBAL_FRAGMENT,
+		// like any other node, the parent takes care of removing it
+		match parent {
+			Some(parent) if parent.ttype == FragmentType::Head => block.destroy.push_str(&format!("{}.d();\n", name)),
+			Some(_) => {}
+			None => block.detach.push_str(&format!("{}.d();\n", name)),
+		}
+		self.render_mount(block, parent, &anchor);
+	}
+
+	// {@debug x, y} logs x and y and pauses whenever they change, but only in dev mode
+	fn render_debug(&mut self, block: &mut Block, fragment: &Fragment) {
+		if !self.compiler.options.dev {
+			return;
+		}
+
+		let names = fragment.data.as_deref().unwrap().split(',')
+			.map(|name| name.trim())
+			.filter(|name| name.len() > 0)
+			.collect::<Vec<&str>>();
+		if names.len() == 0 {
+			// no arguments means pausing on every change
+			block.create("debugger;\n");
+			block.p.push_str("debugger;\n");
+			return;
+		}
+
+		let mut body = String::new();
+		let mut dependencies = vec![];
+		for name in &names {
+			let (value, value_dependencies) = self.render_expression(name);
+			body.push_str(&format!("const {} = {};\n", name, value));
+			dependencies.extend(value_dependencies);
+		}
+		dependencies.sort();
+		dependencies.dedup();
+		body.push_str(&format!("console.log({{ {} }});\ndebugger;", names.join(", ")));
+
+		let body = strings::indent_block(&body, 1);
+		block.create(&format!("{{\n{}\n}}\n", body));
+		if dependencies.len() > 0 {
+			block.p.push_str(&format!("if ({}) {{\n{}\n}}\n", self.render_dirty_check(&dependencies), body));
+		}
+	}
+
+	// <svelte:element> gets its own block, which is thrown away and rebuilt whenever the tag changes
+	fn render_dynamic_element(&mut self, block: &mut Block, parent: Option<&Fragment>, fragment: &Fragment) {
+		let name = &fragment.short_name;
+		let creator = self.compiler.generate_short_name(constants::CREATE_DYNAMIC_ELEMENT);
+		let mut child = Block::new(&creator);
+		self.render_basic_fragment(&mut child, None, fragment, None);
+		let rendered = self.render_block(&child);
+		self.blocks.push(rendered);
+
+		let (tag, dependencies) = self.render_expression(fragment.data.as_deref().unwrap());
+
+		// dev mode makes sure the tag is usable, and that void elements aren't given children
+		let mut validate = String::new();
+		if self.compiler.options.dev {
+			self.compiler.insert_import(&imports::VALIDATE_DYNAMIC_ELEMENT);
+			validate.push_str(&format!("{}({});\n", imports::VALIDATE_DYNAMIC_ELEMENT.function, tag));
+			if fragment.children.iter().any(|child| !child.is_whitespace()) {
+				self.compiler.insert_import(&imports::VALIDATE_VOID_DYNAMIC_ELEMENT);
+				validate.push_str(&format!("{}({});\n", imports::VALIDATE_VOID_DYNAMIC_ELEMENT.function, tag));
+			}
+		}
+		block.variables.push_str(&validate);
+		block.variables.push_str(&format!("let {} = {} && {}({});\n", name, tag, creator, constants::CTX));
+		block.create_node(
+			&format!("if ({}) {}.c();\n", name, name),
+			&format!("if ({}) {}.l({});\n", name, name, self.get_claim_nodes(parent))
+		);
+		let anchor = self.render_anchor(block, parent, name);
+		let anchor_parent = format!("{}.parentNode, {}", anchor, anchor);
+		block.mounter.push_str(&format!("if ({}) {}.m({});\n", name, name, self.get_mount_target(parent)));
+
+		if dependencies.len() == 0 {
+			// the tag never changes, so this is just an element in a block of its own
+			block.p.push_str(&format!("if ({}) {}.p({}, {});\n", name, name, constants::CTX, constants::DIRTY));
+		} else {
+			// a different tag means a different element, so everything gets set up again
+			let previous_tag = format!("previous_{}_tag", name);
+			block.variables.push_str(&format!("let {} = {};\n", previous_tag, tag));
+			let mut recreate = format!("{}{} = {}({});\n{}.c();\n", validate, name, creator, constants::CTX, name);
+			if child.has_intros {
+				self.compiler.insert_import(&imports::TRANSITION_IN);
+				recreate.push_str(&format!("{}({}, 1);\n", imports::TRANSITION_IN.function, name));
+			}
+			recreate.push_str(&format!("{}.m({});\n", name, anchor_parent));
+			self.compiler.insert_import(&imports::SAFE_NOT_EQUAL);
+			block.p.push_str(&format!("if ({}) {{\n", tag));
+			block.p.push_str(&format!("\tif (!{}) {{\n{}\n", previous_tag, strings::indent_block(recreate.trim_end(), 2)));
+			block.p.push_str(&format!("\t}} else if ({}({}, {})) {{\n", imports::SAFE_NOT_EQUAL.function, previous_tag, tag));
+			block.p.push_str(&format!("\t\t{}.d(1);\n{}\n", name, strings::indent_block(recreate.trim_end(), 2)));
+			block.p.push_str("\t} else {\n");
+			block.p.push_str(&format!("\t\t{}.p({}, {});\n", name, constants::CTX, constants::DIRTY));
+			block.p.push_str("\t}\n");
+			block.p.push_str(&format!("}} else if ({}) {{\n", previous_tag));
+			block.p.push_str(&format!("\t{}.d(1);\n\t{} = null;\n", name, name));
+			block.p.push_str("}\n");
+			block.p.push_str(&format!("{} = {};\n", previous_tag, tag));
+		}
+
+		if child.has_intros {
+			block.transition_in.push_str(&format!("{}({});\n", imports::TRANSITION_IN.function, name));
+			block.has_intros = true;
+		}
+		if child.has_outros {
+			self.compiler.insert_import(&imports::TRANSITION_OUT);
+			block.transition_out.push_str(&format!("{}({});\n", imports::TRANSITION_OUT.function, name));
+			block.has_outros = true;
+		}
+		block.destroy.push_str(&format!("if ({}) {}.d({});\n", name, name, self.get_detaching(parent)));
+		self.render_mount(block, parent, &anchor);
+	}
+
+	// Every block that can come and go gets an anchor, so we know where to put it back
+	fn render_anchor(&mut self, block: &mut Block, parent: Option<&Fragment>, name: &str) -> String {
+		let anchor = format!("{}_anchor", name);
+		block.variables.push_str(&format!("let {};\n", anchor));
+		self.compiler.insert_import(&imports::EMPTY);
+		block.create(&format!("{} = {}();\n", anchor, imports::EMPTY.function));
+		return anchor;
+	}
+
+	fn render_if_block(&mut self, block: &mut Block, parent: Option<&Fragment>, fragment: &Fragment) {
+		// flatten {#if a}...{:else if b}...{:else}...{/if} into a list of branches
+		let mut branches: Vec<(Option<&str>, &Vec<Fragment>)> = vec![];
+		let mut current = fragment;
+		loop {
+			branches.push((current.data.as_deref(), &current.children));
+			let is_else_if = current.else_children.len() == 1
+				&& current.else_children[0].ttype == FragmentType::IfBlock;
+			if is_else_if {
+				current = &current.else_children[0];
+			} else {
+				if current.else_children.len() > 0 {
+					branches.push((None, &current.else_children));
+				}
+				break;
+			}
+		}
+		let has_else = branches.last().is_some_and(|(condition, _)| condition.is_none());
+
+		// render each branch as its own block
+		let mut creators = vec![];
+		let mut conditions = vec![];
+		let mut has_intros = false;
+		let mut has_outros = false;
+		for (condition, children) in &branches {
+			let creator = match condition {
+				Some(_) => self.compiler.generate_short_name(constants::CREATE_IF_BLOCK),
+				None => self.compiler.generate_short_name(constants::CREATE_ELSE_BLOCK),
+			};
+			let child = self.render_child_block(&creator, children, false);
+			has_intros |= child.has_intros;
+			has_outros |= child.has_outros;
+			creators.push(creator);
+			conditions.push(condition.map(|condition| self.render_expression(condition).0));
+		}
+
+		let name = &fragment.short_name;
+		let select_block_type = self.compiler.generate_short_name(constants::SELECT_BLOCK_TYPE);
+		let anchor = self.render_anchor(block, parent, name);
+		let anchor_parent = format!("{}.parentNode, {}", anchor, anchor);
+
+		if has_outros {
+			self.compiler.insert_import(&imports::GROUP_OUTROS);
+			self.compiler.insert_import(&imports::CHECK_OUTROS);
+			self.compiler.insert_import(&imports::TRANSITION_IN);
+			self.compiler.insert_import(&imports::TRANSITION_OUT);
+
+			// outros need the old block to stick around until it's done, so we keep them all in a list
+			let index = format!("{}_index", name);
+			let blocks = format!("{}s", name);
+			let block_creators = format!("{}_creators", name);
+			let previous = format!("previous_{}_index", name);
+
+			let mut select = format!("function {}({}, {}) {{\n", select_block_type, constants::CTX, constants::DIRTY);
+			for (i, condition) in conditions.iter().enumerate() {
+				match condition {
+					Some(condition) => select.push_str(&format!("\tif ({}) return {};\n", condition, i)),
+					None => select.push_str(&format!("\treturn {};\n", i)),
+				}
+			}
+			if !has_else {
+				select.push_str("\treturn -1;\n");
+			}
+			select.push_str("}\n");
+
+			block.variables.push_str(&format!("let {};\n", index));
+			block.variables.push_str(&format!("let {};\n", name));
+			block.variables.push_str(&format!("const {} = [{}];\n", block_creators, creators.join(", ")));
+			block.variables.push_str(&format!("const {} = [];\n", blocks));
+			block.variables.push_str(&select);
+			block.variables.push_str(&format!("if (~({} = {}({}, {}))) {{\n\t{} = {}[{}] = {}[{}]({});\n}}\n",
+				index,
+				select_block_type,
+				constants::CTX,
+				self.get_initial_dirty(),
+				name,
+				blocks,
+				index,
+				block_creators,
+				index,
+				constants::CTX
+			));
+
+			block.create_node(
+				&format!("if ({}) {}.c();\n", name, name),
+				&format!("if ({}) {}.l({});\n", name, name, self.get_claim_nodes(parent))
+			);
+			block.mounter.push_str(&format!("if (~{}) {{\n\t{}[{}].m({});\n}}\n", index, blocks, index, self.get_mount_target(parent)));
+
+			block.p.push_str(&format!("let {} = {};\n", previous, index));
+			block.p.push_str(&format!("{} = {}({}, {});\n", index, select_block_type, constants::CTX, constants::DIRTY));
+			block.p.push_str(&format!("if ({} === {}) {{\n", index, previous));
+			block.p.push_str(&format!("\tif (~{}) {}[{}].p({}, {});\n", index, blocks, index, constants::CTX, constants::DIRTY));
+			block.p.push_str("} else {\n");
+			block.p.push_str(&format!("\tif ({}) {{\n", name));
+			block.p.push_str(&format!("\t\t{}();\n", imports::GROUP_OUTROS.function));
+			block.p.push_str(&format!("\t\t{}({}[{}], 1, 1, () => {{\n\t\t\t{}[{}] = null;\n\t\t}});\n",
+				imports::TRANSITION_OUT.function,
+				blocks,
+				previous,
+				blocks,
+				previous
+			));
+			block.p.push_str(&format!("\t\t{}();\n", imports::CHECK_OUTROS.function));
+			block.p.push_str("\t}\n");
+			block.p.push_str(&format!("\tif (~{}) {{\n", index));
+			block.p.push_str(&format!("\t\t{} = {}[{}];\n", name, blocks, index));
+			block.p.push_str(&format!("\t\tif (!{}) {{\n", name));
+			block.p.push_str(&format!("\t\t\t{} = {}[{}] = {}[{}]({});\n", name, blocks, index, block_creators, index, constants::CTX));
+			block.p.push_str(&format!("\t\t\t{}.c();\n", name));
+			block.p.push_str("\t\t} else {\n");
+			block.p.push_str(&format!("\t\t\t{}.p({}, {});\n", name, constants::CTX, constants::DIRTY));
+			block.p.push_str("\t\t}\n");
+			block.p.push_str(&format!("\t\t{}({}, 1);\n", imports::TRANSITION_IN.function, name));
+			block.p.push_str(&format!("\t\t{}.m({});\n", name, anchor_parent));
+			block.p.push_str("\t} else {\n");
+			block.p.push_str(&format!("\t\t{} = null;\n", name));
+			block.p.push_str("\t}\n");
+			block.p.push_str("}\n");
+
+			block.transition_in.push_str(&format!("{}({});\n", imports::TRANSITION_IN.function, name));
+			block.transition_out.push_str(&format!("{}({});\n", imports::TRANSITION_OUT.function, name));
+			block.destroy.push_str(&format!("if (~{}) {{\n\t{}[{}].d({});\n}}\n",
+				index,
+				blocks,
+				index,
+				self.get_detaching(parent)
+			));
+			block.has_intros = true;
+			block.has_outros = true;
+		} else {
+			// without outros we can just swap blocks out whenever the condition changes
+			let current_block_type = format!("current_{}_type", name);
+
+			let mut select = format!("function {}({}, {}) {{\n", select_block_type, constants::CTX, constants::DIRTY);
+			for (condition, creator) in conditions.iter().zip(creators.iter()) {
+				match condition {
+					Some(condition) => select.push_str(&format!("\tif ({}) return {};\n", condition, creator)),
+					None => select.push_str(&format!("\treturn {};\n", creator)),
+				}
+			}
+			select.push_str("}\n");
+
+			block.variables.push_str(&select);
+			block.variables.push_str(&format!("let {} = {}({}, {});\n", current_block_type, select_block_type, constants::CTX, self.get_initial_dirty()));
+			block.variables.push_str(&format!("let {} = {} && {}({});\n", name, current_block_type, current_block_type, constants::CTX));
+
+			block.create_node(
+				&format!("if ({}) {}.c();\n", name, name),
+				&format!("if ({}) {}.l({});\n", name, name, self.get_claim_nodes(parent))
+			);
+			block.mounter.push_str(&format!("if ({}) {}.m({});\n", name, name, self.get_mount_target(parent)));
+
+			block.p.push_str(&format!("if ({} === ({} = {}({}, {})) && {}) {{\n",
+				current_block_type,
+				current_block_type,
+				select_block_type,
+				constants::CTX,
+				constants::DIRTY,
+				name
+			));
+			block.p.push_str(&format!("\t{}.p({}, {});\n", name, constants::CTX, constants::DIRTY));
+			block.p.push_str("} else {\n");
+			block.p.push_str(&format!("\tif ({}) {}.d(1);\n", name, name));
+			block.p.push_str(&format!("\t{} = {} && {}({});\n", name, current_block_type, current_block_type, constants::CTX));
+			block.p.push_str(&format!("\tif ({}) {{\n", name));
+			block.p.push_str(&format!("\t\t{}.c();\n", name));
+			if has_intros {
+				self.compiler.insert_import(&imports::TRANSITION_IN);
+				block.p.push_str(&format!("\t\t{}({}, 1);\n", imports::TRANSITION_IN.function, name));
+			}
+			block.p.push_str(&format!("\t\t{}.m({});\n", name, anchor_parent));
+			block.p.push_str("\t}\n");
+			block.p.push_str("}\n");
+
+			if has_intros {
+				block.transition_in.push_str(&format!("{}({});\n", imports::TRANSITION_IN.function, name));
+				block.has_intros = true;
+			}
+			block.destroy.push_str(&format!("if ({}) {}.d({});\n",
+				name,
+				name,
+				self.get_detaching(parent)
+			));
+		}
+
+		self.render_mount(block, parent, &anchor);
+	}
+
+	fn render_key_block(&mut self, block: &mut Block, parent: Option<&Fragment>, fragment: &Fragment) {
+		let name = &fragment.short_name;
+		let creator = self.compiler.generate_short_name(constants::CREATE_KEY_BLOCK);
+		let child = self.render_child_block(&creator, &fragment.children, false);
+		if child.is_empty() {
+			// nothing to rebuild
+			return;
+		}
+		let (key, dependencies) = self.render_expression(fragment.data.as_deref().unwrap());
+
+		let previous_key = format!("previous_{}_key", name);
+		block.variables.push_str(&format!("let {} = {};\n", previous_key, key));
+		block.variables.push_str(&format!("let {} = {}({});\n", name, creator, constants::CTX));
+		block.create_node(
+			&format!("{}.c();\n", name),
+			&format!("{}.l({});\n", name, self.get_claim_nodes(parent))
+		);
+		let anchor = self.render_anchor(block, parent, name);
+		block.mounter.push_str(&format!("{}.m({});\n", name, self.get_mount_target(parent)));
+
+		if dependencies.len() > 0 {
+			// a new key means starting over from scratch, transitions and all
+			self.compiler.insert_import(&imports::SAFE_NOT_EQUAL);
+			block.p.push_str(&format!("if ({} && {}({}, {} = {})) {{\n",
+				self.render_dirty_check(&dependencies),
+				imports::SAFE_NOT_EQUAL.function,
+				previous_key,
+				previous_key,
+				key
+			));
+			if child.has_outros {
+				self.compiler.insert_import(&imports::GROUP_OUTROS);
+				self.compiler.insert_import(&imports::CHECK_OUTROS);
+				self.compiler.insert_import(&imports::TRANSITION_OUT);
+				self.compiler.insert_import(&imports::NOOP);
+				block.p.push_str(&format!("\t{}();\n", imports::GROUP_OUTROS.function));
+				block.p.push_str(&format!("\t{}({}, 1, 1, {});\n", imports::TRANSITION_OUT.function, name, imports::NOOP.function));
+				block.p.push_str(&format!("\t{}();\n", imports::CHECK_OUTROS.function));
+			} else {
+				block.p.push_str(&format!("\t{}.d(1);\n", name));
+			}
+			block.p.push_str(&format!("\t{} = {}({});\n", name, creator, constants::CTX));
+			block.p.push_str(&format!("\t{}.c();\n", name));
+			if child.has_intros {
+				self.compiler.insert_import(&imports::TRANSITION_IN);
+				block.p.push_str(&format!("\t{}({}, 1);\n", imports::TRANSITION_IN.function, name));
+			}
+			block.p.push_str(&format!("\t{}.m({}.parentNode, {});\n", name, anchor, anchor));
+			block.p.push_str("} else {\n");
+			block.p.push_str(&format!("\t{}.p({}, {});\n", name, constants::CTX, constants::DIRTY));
+			block.p.push_str("}\n");
+		} else {
+			block.p.push_str(&format!("{}.p({}, {});\n", name, constants::CTX, constants::DIRTY));
+		}
+
+		if child.has_intros {
+			self.compiler.insert_import(&imports::TRANSITION_IN);
+			block.transition_in.push_str(&format!("{}({});\n", imports::TRANSITION_IN.function, name));
+			block.has_intros = true;
+		}
+		if child.has_outros {
+			self.compiler.insert_import(&imports::TRANSITION_OUT);
+			block.transition_out.push_str(&format!("{}({});\n", imports::TRANSITION_OUT.function, name));
+			block.has_outros = true;
+		}
+		block.destroy.push_str(&format!("{}.d({});\n", name, self.get_detaching(parent)));
+		self.render_mount(block, parent, &anchor);
+	}
+
+	fn render_each_block(&mut self, block: &mut Block, parent: Option<&Fragment>, fragment: &Fragment) {
+		let each = fragment.each.as_ref().unwrap();
+		let name = &fragment.short_name;
+		let (list, list_dependencies) = self.render_expression(fragment.data.as_deref().unwrap());
+
+		// the context (and index) get their own spots in ctx
+		let mut scope = HashMap::new();
+		let mut assignments = vec![];
+		for (binding, accessor) in strings::parse_each_context(&each.context) {
+			scope.insert(binding, ContextVariable {
+				index: self.context_count,
+				dependencies: list_dependencies.clone(),
+			});
+			assignments.push(format!("{}[{}] = list[i]{};", constants::CHILD_CTX, self.context_count, accessor));
+			self.context_count += 1;
+		}
+		if let Some(index) = &each.index {
+			scope.insert(index.clone(), ContextVariable {
+				index: self.context_count,
+				dependencies: list_dependencies.clone(),
+			});
+			assignments.push(format!("{}[{}] = i;", constants::CHILD_CTX, self.context_count));
+			self.context_count += 1;
+		}
+
+		let get_each_context = self.compiler.generate_short_name(constants::GET_EACH_CONTEXT);
+		self.blocks.push(format!("function {}({}, list, i) {{\n\tconst {} = {}.slice();\n{}\n\treturn {};\n}}\n",
+			get_each_context,
+			constants::CTX,
+			constants::CHILD_CTX,
+			constants::CTX,
+			strings::indent_block(&assignments.join("\n"), 1),
+			constants::CHILD_CTX
 		));
-		return result;
+
+		// animations only work on the sole element in a keyed each block
+		let content = fragment.children.iter()
+			.filter(|child| !child.is_whitespace())
+			.collect::<Vec<&Fragment>>();
+		let has_animation = fragment.children.iter()
+			.any(|child| child.directives.iter().any(|directive| directive.ttype == DirectiveType::Animate));
+		if has_animation {
+			if each.key.is_none() {
+				self.compiler.error("An element that uses the animate directive must be the immediate child of a keyed each block");
+			}
+			if content.len() != 1 {
+				self.compiler.error("An element that uses the animate directive must be the sole child of a keyed each block");
+			}
+			self.animated_element = Some(content[0].short_name.clone());
+		}
+
+		// keep track of what our blocks read, so we only update them when that changes
+		let outer_dependencies = std::mem::take(&mut self.read_dependencies);
+		self.contexts.push(scope);
+		let key = each.key.as_ref().map(|key| self.render_expression(key).0);
+		let creator = self.compiler.generate_short_name(constants::CREATE_EACH_BLOCK);
+		let child = self.render_child_block(&creator, &fragment.children, key.is_some());
+		self.contexts.pop();
+		self.animated_element = None;
+
+		let else_block = if fragment.else_children.len() > 0 {
+			let creator = self.compiler.generate_short_name(constants::CREATE_ELSE_BLOCK);
+			Some((creator.clone(), self.render_child_block(&creator, &fragment.else_children, false)))
+		} else {
+			None
+		};
+		let child_dependencies = std::mem::replace(&mut self.read_dependencies, outer_dependencies);
+
+		let value = format!("{}_value", name);
+		let blocks = format!("{}_blocks", name);
+		let lookup = format!("{}_lookup", name);
+		let get_key = format!("{}_{}", name, constants::GET_KEY);
+		let else_name = format!("{}_else", name);
+		let anchor = self.render_anchor(block, parent, name);
+		let anchor_parent = format!("{}.parentNode, {}", anchor, anchor);
+		let i = self.get_internal_name(constants::INDEX);
+
+		// dev mode checks that we were given something we can loop over, with unique keys
+		let mut validate = String::new();
+		if self.compiler.options.dev {
+			self.compiler.insert_import(&imports::VALIDATE_EACH_ARGUMENT);
+			validate.push_str(&format!("{}({});\n", imports::VALIDATE_EACH_ARGUMENT.function, value));
+			if key.is_some() {
+				self.compiler.insert_import(&imports::VALIDATE_EACH_KEYS);
+				validate.push_str(&format!("{}({}, {}, {}, {});\n",
+					imports::VALIDATE_EACH_KEYS.function,
+					constants::CTX,
+					value,
+					get_each_context,
+					get_key
+				));
+			}
+		}
+
+		block.variables.push_str(&format!("let {} = {};\n", value, list));
+		if key.is_none() {
+			block.variables.push_str(&validate);
+		}
+		block.variables.push_str(&format!("let {} = [];\n", blocks));
+		if let Some(key) = &key {
+			block.variables.push_str(&format!("let {} = new Map();\n", lookup));
+			block.variables.push_str(&format!("const {} = {} => {};\n", get_key, constants::CTX, key));
+			block.variables.push_str(&validate);
+			block.variables.push_str(&format!("for (let {} = 0; {} < {}.length; {} += 1) {{\n", i, i, value, i));
+			block.variables.push_str(&format!("\tlet {} = {}({}, {}, {});\n", constants::CHILD_CTX, get_each_context, constants::CTX, value, i));
+			block.variables.push_str(&format!("\tlet key = {}({});\n", get_key, constants::CHILD_CTX));
+			block.variables.push_str(&format!("\t{}.set(key, {}[{}] = {}(key, {}));\n", lookup, blocks, i, creator, constants::CHILD_CTX));
+			block.variables.push_str("}\n");
+		} else {
+			block.variables.push_str(&format!("for (let {} = 0; {} < {}.length; {} += 1) {{\n", i, i, value, i));
+			block.variables.push_str(&format!("\t{}[{}] = {}({}({}, {}, {}));\n", blocks, i, creator, get_each_context, constants::CTX, value, i));
+			block.variables.push_str("}\n");
+			if child.has_outros {
+				self.compiler.insert_import(&imports::TRANSITION_OUT);
+				block.variables.push_str(&format!("const out = i => {}({}[i], 1, 1, () => {{\n\t{}[i] = null;\n}});\n",
+					imports::TRANSITION_OUT.function,
+					blocks,
+					blocks
+				));
+			}
+		}
+		if let Some((else_creator, _)) = &else_block {
+			block.variables.push_str(&format!("let {} = null;\n", else_name));
+			block.variables.push_str(&format!("if (!{}.length) {{\n\t{} = {}({});\n}}\n", value, else_name, else_creator, constants::CTX));
+		}
+
+		block.create_node(
+			&format!("for (let {} = 0; {} < {}.length; {} += 1) {{\n\t{}[{}].c();\n}}\n", i, i, blocks, i, blocks, i),
+			&format!("for (let {} = 0; {} < {}.length; {} += 1) {{\n\t{}[{}].l({});\n}}\n", i, i, blocks, i, blocks, i, self.get_claim_nodes(parent))
+		);
+		block.mounter.push_str(&format!("for (let {} = 0; {} < {}.length; {} += 1) {{\n\t{}[{}].m({});\n}}\n", i, i, blocks, i, blocks, i, self.get_mount_target(parent)));
+		if else_block.is_some() {
+			block.create_node(
+				&format!("if ({}) {{\n\t{}.c();\n}}\n", else_name, else_name),
+				&format!("if ({}) {{\n\t{}.l({});\n}}\n", else_name, else_name, self.get_claim_nodes(parent))
+			);
+			block.mounter.push_str(&format!("if ({}) {{\n\t{}.m({});\n}}\n", else_name, else_name, self.get_mount_target(parent)));
+		}
+
+		// update
+		let mut update = String::new();
+		update.push_str(&format!("{} = {};\n", value, list));
+		update.push_str(&validate);
+		if key.is_some() {
+			let destroy = match (child.has_animation(), child.has_outros) {
+				(true, true) => &*imports::FIX_AND_OUTRO_AND_DESTROY_BLOCK,
+				(true, false) => &*imports::FIX_AND_DESTROY_BLOCK,
+				(false, true) => &*imports::OUTRO_AND_DESTROY_BLOCK,
+				(false, false) => &*imports::DESTROY_BLOCK,
+			};
+			self.compiler.insert_import(destroy);
+			self.compiler.insert_import(&imports::UPDATE_KEYED_EACH);
+
+			if child.has_outros {
+				self.compiler.insert_import(&imports::GROUP_OUTROS);
+				update.push_str(&format!("{}();\n", imports::GROUP_OUTROS.function));
+			}
+			if child.has_animation() {
+				update.push_str(&format!("for (let {} = 0; {} < {}.length; {} += 1) {}[{}].r();\n", i, i, blocks, i, blocks, i));
+			}
+			update.push_str(&format!("{} = {}({}, {}, {}, 1, {}, {}, {}, {}.parentNode, {}, {}, {}, {});\n",
+				blocks,
+				imports::UPDATE_KEYED_EACH.function,
+				blocks,
+				constants::DIRTY,
+				get_key,
+				constants::CTX,
+				value,
+				lookup,
+				anchor,
+				destroy.function,
+				creator,
+				anchor,
+				get_each_context
+			));
+			if child.has_animation() {
+				update.push_str(&format!("for (let {} = 0; {} < {}.length; {} += 1) {}[{}].a();\n", i, i, blocks, i, blocks, i));
+			}
+			if child.has_outros {
+				self.compiler.insert_import(&imports::CHECK_OUTROS);
+				update.push_str(&format!("{}();\n", imports::CHECK_OUTROS.function));
+			}
+		} else {
+			update.push_str(&format!("let {};\n", i));
+			update.push_str(&format!("for ({} = 0; {} < {}.length; {} += 1) {{\n", i, i, value, i));
+			update.push_str(&format!("\tconst {} = {}({}, {}, {});\n", constants::CHILD_CTX, get_each_context, constants::CTX, value, i));
+			update.push_str(&format!("\tif ({}[{}]) {{\n", blocks, i));
+			update.push_str(&format!("\t\t{}[{}].p({}, {});\n", blocks, i, constants::CHILD_CTX, constants::DIRTY));
+			if child.has_intros {
+				self.compiler.insert_import(&imports::TRANSITION_IN);
+				update.push_str(&format!("\t\t{}({}[{}], 1);\n", imports::TRANSITION_IN.function, blocks, i));
+			}
+			update.push_str("\t} else {\n");
+			update.push_str(&format!("\t\t{}[{}] = {}({});\n", blocks, i, creator, constants::CHILD_CTX));
+			update.push_str(&format!("\t\t{}[{}].c();\n", blocks, i));
+			if child.has_intros {
+				update.push_str(&format!("\t\t{}({}[{}], 1);\n", imports::TRANSITION_IN.function, blocks, i));
+			}
+			update.push_str(&format!("\t\t{}[{}].m({});\n", blocks, i, anchor_parent));
+			update.push_str("\t}\n");
+			update.push_str("}\n");
+			if child.has_outros {
+				self.compiler.insert_import(&imports::GROUP_OUTROS);
+				self.compiler.insert_import(&imports::CHECK_OUTROS);
+				update.push_str(&format!("{}();\n", imports::GROUP_OUTROS.function));
+				update.push_str(&format!("for ({} = {}.length; {} < {}.length; {} += 1) {{\n\tout({});\n}}\n", i, value, i, blocks, i, i));
+				update.push_str(&format!("{}();\n", imports::CHECK_OUTROS.function));
+			} else {
+				update.push_str(&format!("for (; {} < {}.length; {} += 1) {{\n\t{}[{}].d(1);\n}}\n", i, blocks, i, blocks, i));
+				update.push_str(&format!("{}.length = {}.length;\n", blocks, value));
+			}
+		}
+		if let Some((else_creator, else_child)) = &else_block {
+			update.push_str(&format!("if (!{}.length && {}) {{\n", value, else_name));
+			update.push_str(&format!("\t{}.p({}, {});\n", else_name, constants::CTX, constants::DIRTY));
+			update.push_str(&format!("}} else if (!{}.length) {{\n", value));
+			update.push_str(&format!("\t{} = {}({});\n", else_name, else_creator, constants::CTX));
+			update.push_str(&format!("\t{}.c();\n", else_name));
+			update.push_str(&format!("\t{}.m({});\n", else_name, anchor_parent));
+			update.push_str(&format!("}} else if ({}) {{\n", else_name));
+			update.push_str(&format!("\t{}.d(1);\n", else_name));
+			update.push_str(&format!("\t{} = null;\n", else_name));
+			update.push_str("}\n");
+		}
+
+		let mut dependencies = list_dependencies.clone();
+		dependencies.extend(child_dependencies);
+		dependencies.sort();
+		dependencies.dedup();
+		if dependencies.len() > 0 {
+			block.p.push_str(&format!("if ({}) {{\n{}\n}}\n", self.render_dirty_check(&dependencies), strings::indent_block(&update, 1)));
+		}
+
+		// transitions
+		if child.has_intros {
+			self.compiler.insert_import(&imports::TRANSITION_IN);
+			block.transition_in.push_str(&format!("for (let {} = 0; {} < {}.length; {} += 1) {{\n\t{}({}[{}]);\n}}\n",
+				i,
+				i,
+				value,
+				i,
+				imports::TRANSITION_IN.function,
+				blocks,
+				i
+			));
+			block.has_intros = true;
+		}
+		if child.has_outros {
+			self.compiler.insert_import(&imports::TRANSITION_OUT);
+			if key.is_none() {
+				block.transition_out.push_str(&format!("{} = {}.filter(Boolean);\n", blocks, blocks));
+			}
+			block.transition_out.push_str(&format!("for (let {} = 0; {} < {}.length; {} += 1) {{\n\t{}({}[{}]);\n}}\n",
+				i,
+				i,
+				blocks,
+				i,
+				imports::TRANSITION_OUT.function,
+				blocks,
+				i
+			));
+			block.has_outros = true;
+		}
+
+		// detach
+		let detaching = self.get_detaching(parent);
+		if key.is_some() {
+			block.destroy.push_str(&format!("for (let {} = 0; {} < {}.length; {} += 1) {{\n\t{}[{}].d({});\n}}\n", i, i, blocks, i, blocks, i, detaching));
+		} else {
+			self.compiler.insert_import(&imports::DESTROY_EACH);
+			block.destroy.push_str(&format!("{}({}, {});\n", imports::DESTROY_EACH.function, blocks, detaching));
+		}
+		if else_block.is_some() {
+			block.destroy.push_str(&format!("if ({}) {}.d({});\n", else_name, else_name, detaching));
+		}
+
+		self.render_mount(block, parent, &anchor);
+	}
+}
+
+// Elements with a hyphen in their name are custom elements, ex: <my-widget>
+fn is_custom_element(fragment: &Fragment) -> bool {
+	return fragment.ttype == FragmentType::Basic
+		&& fragment.name.contains('-')
+		&& fragment.namespace.is_none();
+}
+
+// Whether this never changes after it's created, so it can be written out as plain html
+fn is_static(fragment: &Fragment) -> bool {
+	return match fragment.ttype {
+		FragmentType::Text => !fragment.contains_code,
+		FragmentType::Comment => true,
+		FragmentType::Basic => {
+			!is_custom_element(fragment)
+				&& fragment.name.to_lowercase() != "noscript" // its content is only text while scripts run
+				&& fragment.name.to_lowercase() != "script" // scripts added through innerHTML never run
+				&& fragment.name.to_lowercase() != "style" // its text isn't html, so it can't be escaped
+				&& fragment.directives.len() == 0
+				&& fragment.attributes.iter().all(|attribute| {
+					attribute.key != "slot"
+						&& !attribute.key.starts_with("...")
+						&& !attribute.value.as_deref().is_some_and(strings::contains_handlebars)
+				})
+				&& fragment.children.iter().all(is_static)
+		}
+		_ => false,
+	};
+}
+
+// Whether an element's children are static html apart from some elements we can look up afterwards,
+// ex: <div><h1>title</h1><p>{text}</p></div>. Things like blocks, components and dynamic text
+// don't have a fixed spot in the html, so those get rendered the usual way.
+fn has_static_skeleton(fragment: &Fragment) -> bool {
+	// the browser moves things around in tables and selects, so the indices wouldn't line up
+	let name = fragment.name.to_lowercase();
+	if fragment.namespace.is_some() || ["table", "thead", "tbody", "tfoot", "tr", "colgroup", "select"].contains(&name.as_str()) {
+		return false;
+	}
+	let is_dynamic_element = |child: &Fragment| {
+		child.ttype == FragmentType::Basic
+			&& child.renderable
+			&& child.namespace.is_none()
+			&& !is_custom_element(child)
+			&& !child.attributes.iter().any(|attribute| attribute.key == "slot")
+	};
+	let has_static_element = fragment.children.iter()
+		.any(|child| child.ttype == FragmentType::Basic && is_static(child));
+	return has_static_element
+		&& fragment.children.iter().any(|child| !is_static(child))
+		&& fragment.children.iter().all(|child| is_static(child) || is_dynamic_element(child));
+}
+
+// Writes out a static fragment as html, ex: <a href="/">home</a>
+fn push_static_html(html: &mut String, fragment: &Fragment) {
+	let data = fragment.data.as_deref().unwrap_or("");
+	match fragment.ttype {
+		FragmentType::Text => html.push_str(&entities::escape_html(data, false)),
+		FragmentType::Comment => html.push_str(&format!("<!--{}-->", data)),
+		_ => {
+			html.push_str(&format!("<{}", fragment.name));
+			for attribute in &fragment.attributes {
+				match &attribute.value {
+					Some(value) => {
+						let value = entities::decode_character_references(value, true);
+						html.push_str(&format!(" {}=\"{}\"", attribute.key, entities::escape_html(&value, true)));
+					}
+					None => html.push_str(&format!(" {}", attribute.key)),
+				}
+			}
+			html.push('>');
+			if parser::is_void(&fragment.name) {
+				return;
+			}
+
+			// the html parser drops a newline right after <pre>, so a real one needs doubling up
+			let name = fragment.name.to_lowercase();
+			let first_text = fragment.children.first()
+				.filter(|child| child.ttype == FragmentType::Text)
+				.and_then(|child| child.data.as_deref());
+			if (name == "pre" || name == "textarea") && first_text.is_some_and(|text| text.starts_with('\n')) {
+				html.push('\n');
+			}
+			for child in &fragment.children {
+				push_static_html(html, child);
+			}
+			html.push_str(&format!("</{}>", fragment.name));
+		}
 	}
-}
\ No newline at end of file
+}
+
+// Which bits each word of dirty needs checking for, ex: [(0, 3)] for the first two variables
+fn get_dirty_masks(dependencies: &Vec<usize>) -> Vec<(usize, u32)> {
+	let mut masks: Vec<(usize, u32)> = vec![];
+	for dependency in dependencies {
+		let (word, bit) = (dependency / 31, dependency % 31);
+		match masks.iter_mut().find(|(existing, _)| *existing == word) {
+			Some((_, mask)) => *mask |= 1 << bit,
+			None => masks.push((word, 1 << bit)),
+		}
+	}
+	masks.sort();
+	return masks;
+}
+
+// How many spots in ctx the markup adds on top of the instance's variables,
+// ex: 2 for {#each items as item, i}. See render_each_block, render_consts and let: directives.
+fn count_markup_contexts(fragments: &Vec<Fragment>) -> usize {
+	let mut count = 0;
+	visit_markup(fragments, &mut |fragment| {
+		if let Some(each) = &fragment.each {
+			count += strings::parse_each_context(&each.context).len() + each.index.iter().count();
+		}
+		if fragment.ttype == FragmentType::Const {
+			if let Some((left, _)) = fragment.data.as_deref().and_then(strings::split_assignment) {
+				count += strings::parse_each_context(left).len();
+			}
+		}
+		for directive in fragment.directives.iter().filter(|directive| directive.ttype == DirectiveType::Let) {
+			let pattern = directive.value.as_deref().and_then(strings::strip_mustache).unwrap_or(&directive.name);
+			count += strings::parse_each_context(pattern).len();
+		}
+	});
+	return count;
+}
+
+// Calls `visit` on every fragment in the markup, including the ones nested in blocks
+fn visit_markup(fragments: &Vec<Fragment>, visit: &mut dyn FnMut(&Fragment)) {
+	for fragment in fragments {
+		visit(fragment);
+		visit_markup(&fragment.children, visit);
+		visit_markup(&fragment.else_children, visit);
+	}
+}
+
+// A handler, the events that trigger it, and the variables it updates along with where their values come from
+type BindingGroup<'a> = (&'a str, &'a [&'a str], Vec<(String, &'a str)>);
+
+// For each binding on <svelte:window>: the handler that updates it, the events that trigger
+// that handler and where the value comes from
+fn get_window_binding(name: &str) -> Option<(&'static str, &'static [&'static str], &'static str)> {
+	let binding = match name {
+		"innerWidth" => ("onwindowresize", &["resize"][..], "window.innerWidth"),
+		"innerHeight" => ("onwindowresize", &["resize"][..], "window.innerHeight"),
+		"outerWidth" => ("onwindowresize", &["resize"][..], "window.outerWidth"),
+		"outerHeight" => ("onwindowresize", &["resize"][..], "window.outerHeight"),
+		"devicePixelRatio" => ("onwindowresize", &["resize"][..], "window.devicePixelRatio"),
+		"scrollX" => ("onwindowscroll", &["scroll"][..], "window.pageXOffset"),
+		"scrollY" => ("onwindowscroll", &["scroll"][..], "window.pageYOffset"),
+		"online" => ("onlinestatuschanged", &["online", "offline"][..], "navigator.onLine"),
+		_ => return None,
+	};
+	return Some(binding);
+}
+
+// What devtools call a block, based on what we named it, ex: create_if_block_1 is an "if" block
+fn get_block_type(block: &Block) -> &'static str {
+	let name = &block.name;
+	if block.is_root {
+		return "component";
+	}
+	for (prefix, ttype) in [
+		(constants::CREATE_IF_BLOCK, "if"),
+		(constants::CREATE_ELSE_BLOCK, "else"),
+		(constants::CREATE_EACH_BLOCK, "each"),
+		(constants::CREATE_KEY_BLOCK, "key"),
+		(constants::CREATE_DYNAMIC_ELEMENT, "element"),
+		(constants::FALLBACK_BLOCK, "fallback"),
+	] {
+		if name.starts_with(prefix) {
+			return ttype;
+		}
+	}
+	if name.ends_with("_slot") {
+		return "slot";
+	}
+	return "block";
+}
@@ -1,2 +1,4 @@
 pub mod renderer;
-pub mod dom_renderer;
\ No newline at end of file
+pub mod dom_renderer;
+pub mod ssr_renderer;
+pub mod block;
\ No newline at end of file
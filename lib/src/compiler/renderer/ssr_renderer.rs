@@ -0,0 +1,585 @@
+// Renders components for servers. Instead of building DOM nodes, the whole component becomes one
+// big template literal of html, ex: `<h1>Hello ${escape(name)}!</h1>`. Nothing ever updates, so
+// expressions can use the instance's variables as-is.
+use super::renderer::Renderer;
+use crate::compiler::compiler::{Attribute, CompileResult, Compiler, Directive, DirectiveType, Fragment, FragmentType};
+use crate::compiler::{constants, constants::imports, parser};
+use crate::compiler::utils::{attribute_lookup, entities, namespaces, script, strings, strings::HandlebarType};
+use crate::compiler::utils::fix_attribute_casing::fix_attribute_casing;
+
+#[derive(Debug)]
+pub struct SsrRenderer<'a> {
+	pub compiler: &'a mut Compiler,
+	user_imports: Vec<String>,
+	module_script: String,
+	instance_script: String,
+	styles: String,
+	props: Vec<String>,
+	head: String, // whatever <svelte:head> holds, which goes into $$result.head instead of the html
+	title: String, // <title>, which svelte keeps apart from the rest of the head
+}
+
+impl Renderer for SsrRenderer<'_> {
+	fn render_fragments(&mut self, fragments: &Vec<Fragment>) -> CompileResult {
+		self.extract_scripts(fragments);
+		let html = self.render_children(fragments, true);
+		let body = self.render_body(fragments, &html);
+
+		self.compiler.insert_import(&imports::CREATE_SSR_COMPONENT);
+		let mut code = String::new();
+		code.push_str(&constants::LITHE_COMMENT);
+		code.push_str("\n");
+		code.push_str(&self.compiler.render_imports());
+		for import in &self.user_imports {
+			code.push_str(import);
+			code.push_str("\n");
+		}
+		code.push_str("\n");
+		if self.module_script.len() > 0 {
+			code.push_str(&self.module_script);
+			code.push_str("\n\n");
+		}
+		if self.styles.len() > 0 {
+			code.push_str(&format!("const {} = {{\n\tcode: {},\n\tmap: null\n}};\n\n",
+				constants::CSS,
+				strings::to_js_string(&self.styles)
+			));
+		}
+		code.push_str(&format!("const {} = {}(({}, {}, {}, {}) => {{\n{}\n}});\n\n",
+			constants::COMPONENT,
+			imports::CREATE_SSR_COMPONENT.function,
+			constants::GLOBAL_RESULT,
+			constants::GLOBAL_PROPS,
+			constants::GLOBAL_BINDINGS,
+			constants::SLOTS,
+			strings::indent_block(&body, 1)
+		));
+		code.push_str(&format!("export default {};\n", constants::COMPONENT));
+
+		let mut result = CompileResult::default();
+		result.js.code = Some(code);
+		if self.styles.len() > 0 {
+			result.css.code = Some(self.styles.clone());
+		}
+		return result;
+	}
+}
+
+impl SsrRenderer<'_> {
+	pub fn new(compiler: &mut Compiler) -> SsrRenderer<'_> {
+		SsrRenderer {
+			compiler,
+			user_imports: vec![],
+			module_script: String::new(),
+			instance_script: String::new(),
+			styles: String::new(),
+			props: vec![],
+			head: String::new(),
+			title: String::new(),
+		}
+	}
+
+	fn extract_scripts(&mut self, fragments: &Vec<Fragment>) {
+		let mut styles = vec![];
+		for fragment in fragments {
+			let data = fragment.data.as_deref().unwrap_or("");
+			match fragment.ttype {
+				FragmentType::Script => {
+					let (imports, body) = script::extract_imports(data);
+					self.user_imports.extend(imports);
+
+					let is_module = fragment.attributes.iter()
+						.any(|attribute| attribute.key == "context" && attribute.value.as_deref() == Some("module"));
+					if is_module {
+						self.module_script = body.trim().to_string();
+					} else {
+						if script::has_reactive_statements(&body) {
+							self.compiler.error("$: statements aren't supported yet when generating ssr code");
+						}
+						self.instance_script = body;
+					}
+				}
+				FragmentType::Style => styles.push(data.trim()),
+				_ => {}
+			}
+		}
+		self.styles = styles.join("\n");
+		self.props = script::extract_exports(&self.instance_script);
+	}
+
+	// Everything inside create_ssr_component's callback: the instance script, then the html
+	fn render_body(&mut self, fragments: &Vec<Fragment>, html: &str) -> String {
+		let mut body = String::new();
+		if self.uses_global(fragments, constants::GLOBAL_REST_PROPS) {
+			self.compiler.insert_import(&imports::COMPUTE_REST_PROPS);
+			let names = self.props.iter().map(|prop| format!("\"{}\"", prop)).collect::<Vec<String>>();
+			body.push_str(&format!("let {} = {}({}, [{}]);\n",
+				constants::GLOBAL_REST_PROPS,
+				imports::COMPUTE_REST_PROPS.function,
+				constants::GLOBAL_PROPS,
+				names.join(", ")
+			));
+		}
+		if self.uses_global(fragments, constants::GLOBAL_SLOTS) {
+			self.compiler.insert_import(&imports::COMPUTE_SLOTS);
+			body.push_str(&format!("const {} = {}({});\n", constants::GLOBAL_SLOTS, imports::COMPUTE_SLOTS.function, constants::SLOTS));
+		}
+
+		let script = script::rewrite_exports(&self.instance_script, constants::GLOBAL_PROPS);
+		let script = script.trim();
+		if script.len() > 0 {
+			body.push_str(script);
+			body.push_str("\n");
+		}
+
+		// bind:x={y} on us hands us a callback, so the parent finds out what we did with x
+		for prop in &self.props {
+			body.push_str(&format!("if ({}.{} === void 0 && {}.{} && {} !== void 0) {}.{}({});\n",
+				constants::GLOBAL_PROPS,
+				prop,
+				constants::GLOBAL_BINDINGS,
+				prop,
+				prop,
+				constants::GLOBAL_BINDINGS,
+				prop,
+				prop
+			));
+		}
+		if self.styles.len() > 0 {
+			body.push_str(&format!("{}.css.add({});\n", constants::GLOBAL_RESULT, constants::CSS));
+		}
+		if self.title.len() > 0 {
+			body.push_str(&format!("{}.title = `{}`;\n", constants::GLOBAL_RESULT, self.title));
+		}
+		if self.head.len() > 0 {
+			// hydrating finds our part of the head between these, see DomRenderer::render_head
+			let head = if self.compiler.options.hydratable {
+				format!("<!-- HEAD_svelte-{}_START -->{}<!-- HEAD_svelte-{}_END -->", self.compiler.hash, self.head, self.compiler.hash)
+			} else {
+				self.head.clone()
+			};
+			body.push_str(&format!("{}.head += `{}`;\n", constants::GLOBAL_RESULT, head));
+		}
+		body.push_str(&format!("return `{}`;", html));
+		return body;
+	}
+
+	// Whether the script or markup mention one of svelte's globals, ex: $$restProps
+	fn uses_global(&self, fragments: &Vec<Fragment>, name: &str) -> bool {
+		let mut found = script::tokenize(&self.instance_script).iter()
+			.any(|token| token.ttype == script::TokenType::Identifier && token.value == name);
+		visit_fragments(fragments, &mut |fragment| {
+			found = found
+				|| fragment.ttype != FragmentType::Script && fragment.data.as_deref().is_some_and(|data| data.contains(name))
+				|| fragment.attributes.iter().any(|attribute| attribute.key.contains(name)
+					|| attribute.value.as_deref().is_some_and(|value| value.contains(name)));
+		});
+		return found;
+	}
+
+	// Renders fragments as the inside of a template literal
+	fn render_children(&mut self, fragments: &Vec<Fragment>, is_root: bool) -> String {
+		let mut html = String::new();
+		for fragment in fragments {
+			if fragment.renderable {
+				html.push_str(&self.render_fragment(fragment, is_root));
+			}
+		}
+		return html;
+	}
+
+	// Renders the contents of a block as an expression. {@const} needs somewhere to live,
+	// so those get a function of their own, ex: (() => { const x = y; return `${x}`; })()
+	fn render_content(&mut self, fragments: &Vec<Fragment>) -> String {
+		let consts = fragments.iter()
+			.filter(|fragment| fragment.ttype == FragmentType::Const)
+			.map(|fragment| format!("const {};", fragment.data.as_deref().unwrap()))
+			.collect::<Vec<String>>();
+		let content = fragments.iter()
+			.filter(|fragment| fragment.ttype != FragmentType::Const)
+			.cloned()
+			.collect::<Vec<Fragment>>();
+		let html = self.render_children(&content, false);
+		if consts.len() == 0 {
+			return format!("`{}`", html);
+		}
+		return format!("(() => {{ {} return `{}`; }})()", consts.join(" "), html);
+	}
+
+	fn render_fragment(&mut self, fragment: &Fragment, is_root: bool) -> String {
+		let data = fragment.data.as_deref().unwrap_or("");
+		return match fragment.ttype {
+			FragmentType::Text if fragment.contains_code => {
+				self.compiler.insert_import(&imports::ESCAPE);
+				format!("${{{}({})}}", imports::ESCAPE.function, data.trim())
+			}
+			FragmentType::Text => to_template_html(data, false),
+			FragmentType::Comment => format!("<!--{}-->", strings::to_template_literal_text(data)),
+			FragmentType::Basic | FragmentType::DynamicElement => self.render_element(fragment),
+			FragmentType::Svelte => self.render_component(fragment),
+			FragmentType::IfBlock => format!("${{{}}}", self.render_if_block(fragment)),
+			FragmentType::EachBlock => self.render_each_block(fragment),
+			FragmentType::KeyBlock => self.render_children(&fragment.children, false),
+			FragmentType::Slot => self.render_slot(fragment),
+			// hydrating can't tell where raw html starts and ends without markers
+			FragmentType::RawHtml if self.compiler.options.hydratable => {
+				format!("<!-- HTML_TAG_START -->${{{}}}<!-- HTML_TAG_END -->", data.trim())
+			}
+			FragmentType::RawHtml => format!("${{{}}}", data.trim()),
+			FragmentType::Debug => {
+				if !self.compiler.options.dev {
+					return String::new();
+				}
+				let names = data.split(',')
+					.map(|name| name.trim())
+					.filter(|name| name.len() > 0)
+					.collect::<Vec<&str>>();
+				let log = if names.len() > 0 { format!("console.log({{ {} }}); ", names.join(", ")) } else { String::new() };
+				format!("${{(() => {{ {}debugger; return \"\"; }})()}}", log)
+			}
+			FragmentType::Head => {
+				if !is_root {
+					self.compiler.error("<svelte:head> tags cannot be inside elements or blocks");
+				}
+				for child in &fragment.children {
+					if child.ttype == FragmentType::Basic && child.name == "title" {
+						let title = self.render_element(child);
+						self.title.push_str(&title);
+					} else if child.renderable {
+						let head = self.render_fragment(child, false);
+						self.head.push_str(&head);
+					}
+				}
+				String::new()
+			}
+			FragmentType::Const => {
+				self.compiler.error("{@const} must be the immediate child of {#if}, {:else if}, {:else}, {#each}, <Component> or <svelte:fragment>")
+			}
+			FragmentType::SlotTemplate => self.compiler.error("<svelte:fragment> must be the direct child of a component"),
+			// these only do anything in the browser
+			FragmentType::Window | FragmentType::Body | FragmentType::Document => {
+				if !is_root {
+					self.compiler.error(&format!("<{}> tags cannot be inside elements or blocks", fragment.name));
+				}
+				String::new()
+			}
+			FragmentType::Options | FragmentType::Script | FragmentType::Style => String::new(),
+			FragmentType::Tag => unreachable!(),
+		};
+	}
+
+	fn render_element(&mut self, fragment: &Fragment) -> String {
+		if fragment.attributes.iter().any(|attribute| attribute.key == "slot") {
+			self.compiler.error("Element with a slot='...' attribute must be a child of a component");
+		}
+
+		// a <textarea>'s value is its content, ex: <textarea value={v}> is <textarea>${escape(v)}</textarea>
+		if fragment.ttype == FragmentType::Basic && fragment.namespace.is_none() && fragment.name.to_lowercase() == "textarea" {
+			let value = fragment.attributes.iter()
+				.find(|attribute| attribute.key.to_lowercase() == "value")
+				.map(|attribute| attribute.value.clone())
+				.or_else(|| fragment.directives.iter()
+					.find(|directive| directive.ttype == DirectiveType::Binding && directive.name == "value")
+					.map(|directive| Some(directive.value.clone().unwrap_or_else(|| format!("{{{}}}", directive.name)))));
+			if let Some(value) = value {
+				let mut textarea = fragment.clone();
+				textarea.attributes.retain(|attribute| attribute.key.to_lowercase() != "value");
+				textarea.directives.retain(|directive| directive.ttype != DirectiveType::Binding || directive.name != "value");
+				let attributes = self.render_attributes(&textarea);
+				let value = self.render_value(value.as_deref());
+				self.compiler.insert_import(&imports::ESCAPE);
+				let name = strings::to_template_literal_text(&fragment.name);
+				return format!("<{}{}>${{{}({})}}</{}>", name, attributes, imports::ESCAPE.function, value, name);
+			}
+		}
+
+		let attributes = self.render_attributes(fragment);
+		let children = match &*fragment.name.to_lowercase() {
+			// their text isn't html, so it can't be escaped, ex: a > b in css
+			"script" | "style" => fragment.children.iter()
+				.map(|child| strings::to_template_literal_text(child.data.as_deref().unwrap_or("")))
+				.collect::<String>(),
+			_ => self.render_children(&fragment.children, false),
+		};
+		if fragment.ttype == FragmentType::DynamicElement {
+			// we only find out the tag when rendering, ex: <svelte:element this={tag}>
+			self.compiler.insert_import(&imports::IS_VOID);
+			return format!("${{((tag) => tag ? `<${{tag}}{}>${{{}(tag) ? \"\" : `{}</${{tag}}>`}}` : \"\")({})}}",
+				attributes,
+				imports::IS_VOID.function,
+				children,
+				fragment.data.as_deref().unwrap()
+			);
+		}
+
+		let name = strings::to_template_literal_text(&fragment.name);
+		if parser::is_void(&fragment.name) {
+			return format!("<{}{}>", name, attributes);
+		}
+		return format!("<{}{}>{}</{}>", name, attributes, children, name);
+	}
+
+	// Attributes as they go in an element's opening tag, ex: ` class="a ${escape(b, true)}"`
+	fn render_attributes(&mut self, fragment: &Fragment) -> String {
+		let is_svg = fragment.namespace.as_deref() == Some(namespaces::SVG);
+		let fix_casing = |key: &str| if is_svg { fix_attribute_casing(key) } else { key.to_string() };
+
+		// bind:value={x} starts out the same as value={x}
+		let mut attributes = fragment.attributes.clone();
+		for directive in fragment.directives.iter().filter(|directive| directive.ttype == DirectiveType::Binding) {
+			if directive.name != "this" && directive.name != "group" {
+				attributes.push(Attribute {
+					key: directive.name.clone(),
+					value: Some(directive.value.clone().unwrap_or_else(|| format!("{{{}}}", directive.name))),
+				});
+			}
+		}
+
+		if attributes.iter().any(|attribute| attribute.key.starts_with("...")) {
+			// spreads can set anything, so everything gets merged together at runtime
+			self.compiler.insert_import(&imports::SPREAD);
+			let levels = attributes.iter()
+				.map(|attribute| match attribute.key.strip_prefix("...") {
+					Some(spread) => {
+						self.compiler.insert_import(&imports::ESCAPE_OBJECT);
+						format!("{}({})", imports::ESCAPE_OBJECT.function, spread.trim())
+					}
+					None => format!("{{ {}: {} }}", strings::to_property_key(&fix_casing(&attribute.key)), self.render_value(attribute.value.as_deref())),
+				})
+				.collect::<Vec<String>>();
+			return format!("${{{}([{}], {{}})}}", imports::SPREAD.function, levels.join(", "));
+		}
+
+		let mut result = String::new();
+		for attribute in &attributes {
+			let key = fix_casing(&attribute.key);
+			let value = match &attribute.value {
+				Some(value) => value,
+				None => {
+					result.push_str(&format!(" {}", strings::to_template_literal_text(&key)));
+					continue;
+				}
+			};
+
+			if let Some(expression) = strings::strip_mustache(value) {
+				// null and undefined (and false, for boolean attributes) leave the attribute out entirely
+				let is_boolean = fragment.namespace.is_none()
+					&& attribute_lookup::get_property(&fragment.name, &key).is_some_and(|property| property.is_boolean);
+				self.compiler.insert_import(&imports::ADD_ATTRIBUTE);
+				result.push_str(&format!("${{{}({}, {}, {})}}",
+					imports::ADD_ATTRIBUTE.function,
+					strings::to_js_string(&key),
+					expression,
+					if is_boolean { 1 } else { 0 }
+				));
+				continue;
+			}
+
+			result.push_str(&format!(" {}=\"", strings::to_template_literal_text(&key)));
+			for piece in strings::split_handlebars_into_pieces(value) {
+				match piece.ttype {
+					HandlebarType::Text => {
+						let text = entities::decode_character_references(&piece.value, true);
+						result.push_str(&to_template_html(&text, true));
+					}
+					HandlebarType::Code => {
+						self.compiler.insert_import(&imports::ESCAPE);
+						result.push_str(&format!("${{{}({}, true)}}", imports::ESCAPE.function, piece.value.trim()));
+					}
+				}
+			}
+			result.push('"');
+		}
+		return result;
+	}
+
+	// An attribute's value as a js expression, ex: `a {b}` becomes `"a " + (b)`
+	fn render_value(&mut self, value: Option<&str>) -> String {
+		let value = match value {
+			Some(value) => value,
+			None => return "true".to_string(),
+		};
+		if let Some(expression) = strings::strip_mustache(value) {
+			return expression.to_string();
+		}
+
+		let pieces = strings::split_handlebars_into_pieces(value);
+		if pieces.len() == 0 {
+			return "\"\"".to_string();
+		}
+		return pieces.iter()
+			.map(|piece| match piece.ttype {
+				HandlebarType::Text => strings::to_js_string(&entities::decode_character_references(&piece.value, true)),
+				HandlebarType::Code => format!("({})", piece.value.trim()),
+			})
+			.collect::<Vec<String>>()
+			.join(" + ");
+	}
+
+	fn render_component(&mut self, fragment: &Fragment) -> String {
+		let (constructor, name) = match &*fragment.name {
+			constants::SVELTE_COMPONENT => {
+				self.compiler.insert_import(&imports::MISSING_COMPONENT);
+				(format!("{} || {}", fragment.data.as_deref().unwrap(), imports::MISSING_COMPONENT.function), fragment.name.clone())
+			}
+			constants::SVELTE_SELF => (constants::COMPONENT.to_string(), constants::COMPONENT.to_string()),
+			_ => (fragment.name.clone(), fragment.name.clone()),
+		};
+
+		// props get merged together in order if there are spreads, ex: Object.assign({}, props, { a: 1 })
+		let mut levels = vec![];
+		let mut props = vec![];
+		for attribute in &fragment.attributes {
+			match attribute.key.strip_prefix("...") {
+				Some(spread) => {
+					if props.len() > 0 {
+						levels.push(format!("{{ {} }}", props.join(", ")));
+						props.clear();
+					}
+					levels.push(spread.trim().to_string());
+				}
+				None => {
+					let value = self.render_value(attribute.value.as_deref());
+					props.push(format!("{}: {}", strings::to_property_key(&attribute.key), value));
+				}
+			}
+		}
+		let props = if levels.len() > 0 {
+			if props.len() > 0 {
+				levels.push(format!("{{ {} }}", props.join(", ")));
+			}
+			format!("Object.assign({{}}, {})", levels.join(", "))
+		} else if props.len() > 0 {
+			format!("{{ {} }}", props.join(", "))
+		} else {
+			"{}".to_string()
+		};
+
+		let slots = self.render_slot_definitions(fragment);
+		let slots = if slots.len() > 0 { format!("{{ {} }}", slots.join(", ")) } else { "{}".to_string() };
+
+		self.compiler.insert_import(&imports::VALIDATE_COMPONENT);
+		return format!("${{{}({}, {}).$$render({}, {}, {{}}, {})}}",
+			imports::VALIDATE_COMPONENT.function,
+			constructor,
+			strings::to_js_string(&name),
+			constants::GLOBAL_RESULT,
+			props,
+			slots
+		);
+	}
+
+	// The functions that fill each slot of a child component, ex: `default: ({ item }) => `${item}``
+	fn render_slot_definitions(&mut self, fragment: &Fragment) -> Vec<String> {
+		let mut slots: Vec<(String, Vec<Fragment>, Vec<Directive>)> = vec![];
+		let default_content = fragment.children.iter()
+			.filter(|child| child.slot.is_none())
+			.cloned()
+			.collect::<Vec<Fragment>>();
+		if default_content.iter().any(|child| !child.is_whitespace()) {
+			slots.push((constants::DEFAULT_SLOT.to_string(), default_content, fragment.directives.clone()));
+		}
+		for child in &fragment.children {
+			let name = match &child.slot {
+				Some(name) => name.clone(),
+				None => continue,
+			};
+			if slots.iter().any(|(slot, _, _)| *slot == name) {
+				self.compiler.error(&format!("Duplicate slot name \"{}\" in <{}>", name, fragment.name));
+			}
+			let content = if child.ttype == FragmentType::SlotTemplate {
+				child.children.clone()
+			} else {
+				vec![child.clone()]
+			};
+			slots.push((name, content, child.directives.clone()));
+		}
+
+		let mut definitions = vec![];
+		for (name, content, directives) in slots {
+			let params = directives.iter()
+				.filter(|directive| directive.ttype == DirectiveType::Let)
+				.map(|directive| match directive.value.as_deref().and_then(strings::strip_mustache) {
+					Some(pattern) if pattern != directive.name => format!("{}: {}", directive.name, pattern),
+					_ => directive.name.clone(),
+				})
+				.collect::<Vec<String>>();
+			let params = if params.len() > 0 { format!("{{ {} }}", params.join(", ")) } else { String::new() };
+			let content = self.render_content(&content);
+			definitions.push(format!("{}: ({}) => {}", strings::to_property_key(&name), params, content));
+		}
+		return definitions;
+	}
+
+	// <slot>, which renders whatever the parent handed us, or its own contents if it didn't
+	fn render_slot(&mut self, fragment: &Fragment) -> String {
+		let slot_name = match fragment.attributes.iter().find(|attribute| attribute.key == "name") {
+			Some(attribute) => match &attribute.value {
+				Some(value) if !strings::contains_handlebars(value) => value.clone(),
+				_ => self.compiler.error("slot name cannot be dynamic"),
+			},
+			None => constants::DEFAULT_SLOT.to_string(),
+		};
+		let slot_props = fragment.attributes.iter()
+			.filter(|attribute| attribute.key != "name")
+			.map(|attribute| format!("{}: {}", strings::to_property_key(&attribute.key), self.render_value(attribute.value.as_deref())))
+			.collect::<Vec<String>>();
+		let slot = format!("{}{}", constants::SLOTS, strings::to_property_access(&slot_name));
+		let fallback = self.render_content(&fragment.children);
+		return format!("${{{} ? {}({{ {} }}) : {}}}", slot, slot, slot_props.join(", "), fallback);
+	}
+
+	// ex: `a ? `x` : b ? `y` : ``
+	fn render_if_block(&mut self, fragment: &Fragment) -> String {
+		let condition = wrap_expression(fragment.data.as_deref().unwrap());
+		let content = self.render_content(&fragment.children);
+		let is_else_if = fragment.else_children.len() == 1
+			&& fragment.else_children[0].ttype == FragmentType::IfBlock;
+		let otherwise = if is_else_if {
+			self.render_if_block(&fragment.else_children[0])
+		} else {
+			self.render_content(&fragment.else_children)
+		};
+		return format!("{} ? {} : {}", condition, content, otherwise);
+	}
+
+	fn render_each_block(&mut self, fragment: &Fragment) -> String {
+		let each = fragment.each.as_ref().unwrap();
+		let list = wrap_expression(fragment.data.as_deref().unwrap());
+		let params = match &each.index {
+			Some(index) => format!("({}, {})", each.context, index),
+			None => format!("({})", each.context),
+		};
+		let content = self.render_content(&fragment.children);
+
+		self.compiler.insert_import(&imports::EACH);
+		let each = format!("{}({}, {} => {})", imports::EACH.function, list, params, content);
+		if fragment.else_children.len() == 0 {
+			return format!("${{{}}}", each);
+		}
+		let otherwise = self.render_content(&fragment.else_children);
+		return format!("${{{}.length ? {} : {}}}", list, each, otherwise);
+	}
+}
+
+// Escapes text for html, then for the template literal it's going into
+fn to_template_html(text: &str, is_attribute: bool) -> String {
+	return strings::to_template_literal_text(&entities::escape_html(text, is_attribute));
+}
+
+// Keeps an expression together when it's used as part of a bigger one, ex: (a, b) ? x : y
+fn wrap_expression(expression: &str) -> String {
+	let expression = expression.trim();
+	if strings::is_identifier(expression) {
+		return expression.to_string();
+	}
+	return format!("({})", expression);
+}
+
+fn visit_fragments(fragments: &Vec<Fragment>, visit: &mut dyn FnMut(&Fragment)) {
+	for fragment in fragments {
+		visit(fragment);
+		visit_fragments(&fragment.children, visit);
+		visit_fragments(&fragment.else_children, visit);
+	}
+}
@@ -0,0 +1,60 @@
+// Attributes that are better set as DOM properties, since the attribute only holds the
+// initial value, ex: `input.checked = true` rather than `attr(input, "checked", "")`
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+pub struct AttributeLookup {
+	pub property_name: &'static str,
+	pub applies_to: Option<&'static [&'static str]>, // None means every element
+	pub is_boolean: bool, // on or off depending on whether it's there at all
+}
+
+const fn boolean(property_name: &'static str, applies_to: &'static [&'static str]) -> AttributeLookup {
+	return AttributeLookup { property_name, applies_to: Some(applies_to), is_boolean: true };
+}
+
+lazy_static! {
+	static ref ATTRIBUTE_LOOKUP: HashMap<&'static str, AttributeLookup> = vec![
+		("allowfullscreen", boolean("allowFullscreen", &["iframe"])),
+		("allowpaymentrequest", boolean("allowPaymentRequest", &["iframe"])),
+		("async", boolean("async", &["script"])),
+		("autofocus", boolean("autofocus", &["button", "input", "keygen", "select", "textarea"])),
+		("autoplay", boolean("autoplay", &["audio", "video"])),
+		("checked", boolean("checked", &["input"])),
+		("controls", boolean("controls", &["audio", "video"])),
+		("default", boolean("default", &["track"])),
+		("defer", boolean("defer", &["script"])),
+		("disabled", boolean("disabled", &["button", "fieldset", "input", "keygen", "optgroup", "option", "select", "textarea"])),
+		("formnovalidate", boolean("formNoValidate", &["button", "input"])),
+		("hidden", AttributeLookup { property_name: "hidden", applies_to: None, is_boolean: true }),
+		("indeterminate", boolean("indeterminate", &["input"])),
+		("ismap", boolean("isMap", &["img"])),
+		("loop", boolean("loop", &["audio", "bgsound", "video"])),
+		("multiple", boolean("multiple", &["input", "select"])),
+		("muted", boolean("muted", &["audio", "video"])),
+		("nomodule", boolean("noModule", &["script"])),
+		("novalidate", boolean("noValidate", &["form"])),
+		("open", boolean("open", &["details", "dialog"])),
+		("playsinline", boolean("playsInline", &["video"])),
+		("readonly", boolean("readOnly", &["input", "textarea"])),
+		("required", boolean("required", &["input", "select", "textarea"])),
+		("reversed", boolean("reversed", &["ol"])),
+		("selected", boolean("selected", &["option"])),
+		// NOTE: <select> is left out, its value can only be set once its options exist
+		("value", AttributeLookup {
+			property_name: "value",
+			applies_to: Some(&["button", "option", "input", "li", "meter", "progress", "param", "textarea"]),
+			is_boolean: false,
+		}),
+	].into_iter().collect();
+}
+
+// How to set an attribute on an html element as a property, if it should be one
+pub fn get_property(element: &str, attribute: &str) -> Option<&'static AttributeLookup> {
+	let lookup = ATTRIBUTE_LOOKUP.get(&*attribute.to_lowercase())?;
+	let element = element.to_lowercase();
+	return match lookup.applies_to {
+		Some(elements) if !elements.contains(&&*element) => None,
+		_ => Some(lookup),
+	};
+}
@@ -286,8 +286,10 @@ lazy_static! {
 	};
 }
 
-pub fn fix_attribute_casing(name: &str) {
-	// let name = name.to_lowercase();
-	// return svg_attribute_lookup.get(name) || name;
-	todo!();
+// Our attributes can come in any case, but svg cares, ex: viewbox should be viewBox
+pub fn fix_attribute_casing(name: &str) -> String {
+	return match SVG_ATTRIBUTE_LOOKUP.get(&name.to_lowercase()) {
+		Some(name) => name.to_string(),
+		None => name.to_string(),
+	};
 }
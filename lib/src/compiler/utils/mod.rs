@@ -1,4 +1,8 @@
+pub mod attribute_lookup;
+pub mod entities;
 pub mod fix_attribute_casing;
 pub mod i18n;
+pub mod names;
+pub mod namespaces;
 pub mod strings;
 pub mod script;
\ No newline at end of file
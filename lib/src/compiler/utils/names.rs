@@ -0,0 +1,89 @@
+use std::collections::{HashMap, HashSet};
+use lazy_static::lazy_static;
+use crate::compiler::constants::{self, imports};
+use crate::compiler::utils::{script, strings};
+use crate::compiler::utils::script::TokenType;
+
+lazy_static! {
+	// on top of actual keywords, these are either reserved in strict mode or things our output relies on.
+	// we lowercase everything we allocate, so capitalized globals like `Object` can't clash.
+	static ref RESERVED: Vec<&'static str> = vec![
+		"arguments", "enum", "eval", "implements", "interface", "package", "private", "protected",
+		"public", "static", "undefined", "of",
+		"document", "window", "console", "globalThis",
+	];
+
+	// names our output uses as-is at the top level or in every block, ex: the `ctx` in create_fragment(ctx).
+	// names only some blocks need, like the `i` in each blocks' loops, are allocated when they're used instead.
+	static ref INTERNAL: Vec<&'static str> = vec![
+		constants::CREATE_FRAGMENT, constants::INSTANCE, constants::COMPONENT, constants::TARGET,
+		constants::ANCHOR, constants::DETACHING, constants::CURRENT, constants::LOCAL, constants::CTX,
+		constants::NEW_CTX, constants::CHILD_CTX, constants::DIRTY, constants::MOUNTED,
+		constants::DISPOSE, constants::OPTIONS, constants::OMIT_PROPS_NAMES, constants::BLOCK,
+		constants::FILE, "key_1",
+	];
+}
+
+// Hands out the names of the variables in our output, ex: `div`, `div1`, `if_block`.
+// Every name is unique, and none of them clash with keywords, svelte's runtime functions,
+// our own internal names or anything declared in the component's scripts.
+#[derive(Debug)]
+pub struct NameAllocator {
+	reserved: HashSet<String>,
+	counts: HashMap<String, usize>,
+}
+
+impl Default for NameAllocator {
+	fn default() -> Self {
+		let mut allocator = NameAllocator {
+			reserved: HashSet::new(),
+			counts: HashMap::new(),
+		};
+		let names = RESERVED.iter()
+			.chain(INTERNAL.iter())
+			.chain(imports::FUNCTIONS.iter());
+		for name in names {
+			allocator.reserve(name);
+		}
+		return allocator;
+	}
+}
+
+impl NameAllocator {
+	pub fn reserve(&mut self, name: &str) {
+		self.reserved.insert(name.to_string());
+	}
+
+	// Reserves every identifier a script uses, so we never shadow any of them
+	pub fn reserve_script(&mut self, source: &str) {
+		let tokens = script::tokenize(source);
+		for (i, token) in tokens.iter().enumerate() {
+			// property names are fine, ex: the `log` in console.log
+			let is_property = i > 0 && (tokens[i - 1].is(".") || tokens[i - 1].is("?."));
+			if token.ttype == TokenType::Identifier && !is_property {
+				self.reserve(token.value);
+			}
+		}
+	}
+
+	pub fn is_reserved(&self, name: &str) -> bool {
+		return self.reserved.contains(name) || script::is_keyword(name);
+	}
+
+	pub fn allocate(&mut self, name: &str) -> String {
+		let mut base = strings::to_identifier(&name.to_lowercase());
+		if base.len() == 0 || base.starts_with(|c: char| c.is_numeric()) {
+			base.insert(0, '_');
+		}
+
+		loop {
+			let count = self.counts.entry(base.clone()).or_insert(0);
+			let candidate = if *count == 0 { base.clone() } else { format!("{}{}", base, count) };
+			*count += 1;
+			if !self.is_reserved(&candidate) {
+				self.reserve(&candidate);
+				return candidate;
+			}
+		}
+	}
+}
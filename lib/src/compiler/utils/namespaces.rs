@@ -0,0 +1,59 @@
+use crate::compiler::utils::i18n;
+
+pub const HTML: &'static str = "http://www.w3.org/1999/xhtml";
+pub const MATHML: &'static str = "http://www.w3.org/1998/Math/MathML";
+pub const SVG: &'static str = "http://www.w3.org/2000/svg";
+pub const XLINK: &'static str = "http://www.w3.org/1999/xlink";
+pub const XML: &'static str = "http://www.w3.org/XML/1998/namespace";
+pub const XMLNS: &'static str = "http://www.w3.org/2000/xmlns";
+pub const FOREIGN: &'static str = "https://svelte.dev/docs#template-syntax-svelte-options"; // left entirely alone, ex: for svelte native
+
+const VALID: [(&'static str, &'static str); 6] = [
+	("html", HTML),
+	("mathml", MATHML),
+	("svg", SVG),
+	("xlink", XLINK),
+	("xml", XML),
+	("xmlns", XMLNS),
+];
+
+// Turns a namespace's name or URL into its URL, ex: `svg` gives http://www.w3.org/2000/svg
+pub fn resolve(namespace: &str) -> Option<&'static str> {
+	if namespace == "foreign" || namespace == FOREIGN {
+		return Some(FOREIGN);
+	}
+	return VALID.iter()
+		.find(|(name, url)| *name == namespace || *url == namespace)
+		.map(|(_, url)| *url);
+}
+
+// Which namespace an element belongs to, given its parent element's (if it has one).
+// Elements like <svg> and <math> start a new namespace, which their children inherit,
+// except for the children of <foreignObject> which go back to being HTML.
+pub fn get_namespace(name: &str, parent: Option<(&str, Option<&str>)>, explicit_namespace: Option<&str>) -> Option<&'static str> {
+	let starts_namespace = |name: &str| {
+		if name == "math" {
+			Some(MATHML)
+		} else if i18n::svg.is_match(name) || i18n::svg.is_match(&name.to_lowercase()) {
+			Some(SVG)
+		} else {
+			None
+		}
+	};
+
+	match parent {
+		None => explicit_namespace.and_then(resolve).or_else(|| starts_namespace(name)),
+		Some((parent_name, parent_namespace)) => {
+			let parent_namespace = parent_namespace.and_then(resolve);
+			if parent_namespace != Some(FOREIGN) {
+				if let Some(namespace) = starts_namespace(name) {
+					return Some(namespace);
+				}
+				if parent_name.to_lowercase() == "foreignobject" {
+					return None;
+				}
+			}
+			return parent_namespace;
+		}
+	}
+}
@@ -1,5 +1,962 @@
-// TODO: what's the plan for parsing js/ts?
-// Do we parse TS, here, or do we depend on like webpack for something?
-pub fn extract_exports(_script: &str) -> Vec<String> {
-	todo!();
-}
\ No newline at end of file
+// NOTE: we don't parse js/ts for real, we just tokenize it. That's enough to
+// pull out imports/declarations and to rewrite identifiers, which is all we need for now.
+use std::collections::{HashMap, HashSet};
+use lazy_static::lazy_static;
+
+lazy_static! {
+	static ref KEYWORDS: HashSet<&'static str> = vec![
+		"await", "break", "case", "catch", "class", "const", "continue", "debugger", "default",
+		"delete", "do", "else", "export", "extends", "false", "finally", "for", "function", "if",
+		"import", "in", "instanceof", "let", "new", "null", "return", "super", "switch", "this",
+		"throw", "true", "try", "typeof", "var", "void", "while", "with", "yield",
+	].into_iter().collect();
+
+	// keywords after which a `/` starts a regex rather than a division
+	static ref REGEX_KEYWORDS: HashSet<&'static str> = vec![
+		"return", "typeof", "instanceof", "in", "of", "new", "delete", "void", "throw", "case",
+		"do", "else", "yield", "await",
+	].into_iter().collect();
+
+	// longest first, so we always grab the biggest operator we can
+	static ref PUNCTUATORS: Vec<&'static str> = vec![
+		">>>=", "...", "===", "!==", "**=", "<<=", ">>=", ">>>", "&&=", "||=", "??=",
+		"=>", "==", "!=", "<=", ">=", "&&", "||", "??", "?.", "++", "--", "+=", "-=", "*=",
+		"/=", "%=", "&=", "|=", "^=", "**", "<<", ">>",
+		"{", "}", "(", ")", "[", "]", ";", ",", "<", ">", "+", "-", "*", "/", "%", "&", "|",
+		"^", "!", "~", "?", ":", "=", ".", "@", "#",
+	];
+
+	static ref ASSIGNMENT_OPERATORS: HashSet<&'static str> = vec![
+		"=", "+=", "-=", "*=", "/=", "%=", "**=", "<<=", ">>=", ">>>=", "&=", "|=", "^=",
+		"&&=", "||=", "??=",
+	].into_iter().collect();
+}
+
+pub fn is_keyword(name: &str) -> bool {
+	return KEYWORDS.contains(name);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenType {
+	Identifier,
+	Keyword,
+	Number,
+	String,
+	Template, // a literal chunk of a template string, including its delimiters
+	Regex,
+	Punctuator,
+	Comment,
+}
+
+#[derive(Clone, Debug)]
+pub struct Token<'a> {
+	pub ttype: TokenType,
+	pub value: &'a str,
+	pub start: usize,
+	pub end: usize,
+	pub newline_before: bool,
+}
+
+impl Token<'_> {
+	pub fn is(&self, value: &str) -> bool {
+		return self.ttype == TokenType::Punctuator && self.value == value;
+	}
+
+	pub fn is_keyword(&self, value: &str) -> bool {
+		return self.ttype == TokenType::Keyword && self.value == value;
+	}
+}
+
+fn is_identifier_start(c: char) -> bool {
+	return c == '$' || c == '_' || c.is_alphabetic();
+}
+
+fn is_identifier_char(c: char) -> bool {
+	return is_identifier_start(c) || c.is_numeric();
+}
+
+fn regex_allowed(previous: Option<&Token>) -> bool {
+	match previous {
+		None => true,
+		Some(token) => match token.ttype {
+			TokenType::Punctuator => !matches!(token.value, ")" | "]" | "}"),
+			TokenType::Keyword => REGEX_KEYWORDS.contains(token.value),
+			_ => false,
+		}
+	}
+}
+
+/// Splits some js into tokens. Comments are kept (so we can re-emit the source faithfully)
+/// but whitespace isn't. Malformed input (say, an unterminated string) never panics,
+/// the offending token just runs to the end of the source.
+pub fn tokenize(source: &str) -> Vec<Token<'_>> {
+	return Tokenizer::new(source).collect();
+}
+
+// Hands out tokens one at a time, for when we only want to read part of the source
+pub struct Tokenizer<'a> {
+	source: &'a str,
+	index: usize,
+	// true for braces that belong to a `${` inside a template literal
+	brace_stack: Vec<bool>,
+	previous: Option<Token<'a>>, // the last token that wasn't a comment
+}
+
+impl<'a> Tokenizer<'a> {
+	pub fn new(source: &'a str) -> Self {
+		Tokenizer {
+			source,
+			index: 0,
+			brace_stack: vec![],
+			previous: None,
+		}
+	}
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+	type Item = Token<'a>;
+
+	fn next(&mut self) -> Option<Token<'a>> {
+		let source = self.source;
+		let bytes = source.as_bytes();
+		let mut newline_before = false;
+		let mut i = self.index;
+
+		while let Some(c) = source[i..].chars().next() {
+			if !c.is_whitespace() {
+				break;
+			}
+			if c == '\n' {
+				newline_before = true;
+			}
+			i += c.len_utf8();
+		}
+		let c = source[i..].chars().next()?;
+		let start = i;
+
+		let ttype;
+		if source[i..].starts_with("//") {
+			i = source[i..].find('\n').map(|end| i + end).unwrap_or(source.len());
+			ttype = TokenType::Comment;
+		} else if source[i..].starts_with("/*") {
+			i = source[i + 2..].find("*/").map(|end| i + end + 4).unwrap_or(source.len());
+			ttype = TokenType::Comment;
+		} else if c == '"' || c == '\'' {
+			i = skip_string(source, i);
+			ttype = TokenType::String;
+		} else if c == '`' {
+			i = skip_template_chunk(source, i + 1, &mut self.brace_stack);
+			ttype = TokenType::Template;
+		} else if c == '}' && self.brace_stack.last() == Some(&true) {
+			self.brace_stack.pop();
+			i = skip_template_chunk(source, i + 1, &mut self.brace_stack);
+			ttype = TokenType::Template;
+		} else if c == '/' && regex_allowed(self.previous.as_ref()) {
+			i = skip_regex(source, i);
+			ttype = TokenType::Regex;
+		} else if is_identifier_start(c) {
+			i += c.len_utf8();
+			while let Some(c) = source[i..].chars().next() {
+				if !is_identifier_char(c) {
+					break;
+				}
+				i += c.len_utf8();
+			}
+			ttype = if is_keyword(&source[start..i]) { TokenType::Keyword } else { TokenType::Identifier };
+		} else if c.is_ascii_digit() || (c == '.' && bytes.get(i + 1).is_some_and(|b| b.is_ascii_digit())) {
+			i += 1;
+			while i < bytes.len() {
+				let b = bytes[i];
+				let is_exponent_sign = (b == b'+' || b == b'-') && (bytes[i - 1] == b'e' || bytes[i - 1] == b'E') && !source[start..].starts_with("0x");
+				if is_exponent_sign || b.is_ascii_alphanumeric() || b == b'.' || b == b'_' {
+					i += 1;
+				} else {
+					break;
+				}
+			}
+			ttype = TokenType::Number;
+		} else {
+			let punctuator = PUNCTUATORS.iter().find(|p| source[i..].starts_with(*p));
+			i += punctuator.map(|p| p.len()).unwrap_or(c.len_utf8());
+			ttype = TokenType::Punctuator;
+			match &source[start..i] {
+				"{" => self.brace_stack.push(false),
+				"}" => { self.brace_stack.pop(); },
+				_ => {}
+			}
+		}
+
+		self.index = i;
+		let token = Token {
+			ttype,
+			value: &source[start..i],
+			start,
+			end: i,
+			newline_before,
+		};
+		if ttype != TokenType::Comment {
+			self.previous = Some(token.clone());
+		}
+		return Some(token);
+	}
+}
+
+// Returns the index just past the `}` that closes the mustache starting at `start`, ex: the
+// whole of `{ "}" + `${a}` }`. Strings, template literals, regexes and comments can all hold
+// braces that don't count. Returns None if it's never closed.
+pub fn find_mustache_end(source: &str, start: usize) -> Option<usize> {
+	// closing tags would look like the start of a regex, ex: {/if}
+	let inner = &source[start + 1..];
+	if inner.trim_start().starts_with('/') {
+		return inner.find('}').map(|end| start + 1 + end + 1);
+	}
+
+	let mut depth = 0;
+	for token in Tokenizer::new(&source[start..]) {
+		if token.ttype != TokenType::Punctuator {
+			continue;
+		}
+		match token.value {
+			"{" => depth += 1,
+			"}" => {
+				depth -= 1;
+				if depth == 0 {
+					return Some(start + token.end);
+				}
+			}
+			_ => {}
+		}
+	}
+	return None;
+}
+
+fn skip_string(source: &str, start: usize) -> usize {
+	let bytes = source.as_bytes();
+	let quote = bytes[start];
+	let mut i = start + 1;
+	while i < bytes.len() {
+		match bytes[i] {
+			b'\\' => i += 2,
+			b if b == quote => return i + 1,
+			b'\n' => return i, // unterminated
+			_ => i += 1,
+		}
+	}
+	return bytes.len();
+}
+
+// scans template literal text up to (and including) the closing backtick or the next `${`
+fn skip_template_chunk(source: &str, start: usize, brace_stack: &mut Vec<bool>) -> usize {
+	let bytes = source.as_bytes();
+	let mut i = start;
+	while i < bytes.len() {
+		match bytes[i] {
+			b'\\' => i += 2,
+			b'`' => return i + 1,
+			b'$' if bytes.get(i + 1) == Some(&b'{') => {
+				brace_stack.push(true);
+				return i + 2;
+			}
+			_ => i += 1,
+		}
+	}
+	return bytes.len();
+}
+
+fn skip_regex(source: &str, start: usize) -> usize {
+	let bytes = source.as_bytes();
+	let mut i = start + 1;
+	let mut in_class = false;
+	while i < bytes.len() {
+		match bytes[i] {
+			b'\\' => i += 2,
+			b'[' => { in_class = true; i += 1; },
+			b']' => { in_class = false; i += 1; },
+			b'/' if !in_class => {
+				i += 1;
+				while i < bytes.len() && bytes[i].is_ascii_alphabetic() {
+					i += 1; // flags
+				}
+				return i;
+			}
+			b'\n' => return i,
+			_ => i += 1,
+		}
+	}
+	return bytes.len();
+}
+
+// Applies (position, text) insertions and (start, end, text) replacements to the source
+fn apply_edits(source: &str, mut edits: Vec<(usize, usize, String)>) -> String {
+	// stable sort, so insertions at the same spot keep the order they were added in
+	edits.sort_by_key(|(start, _, _)| *start);
+	let mut result = String::new();
+	let mut position = 0;
+	for (start, end, text) in edits {
+		if start < position {
+			continue; // overlapping edit, shouldn't happen
+		}
+		result.push_str(&source[position..start]);
+		result.push_str(&text);
+		position = end;
+	}
+	result.push_str(&source[position..]);
+	return result;
+}
+
+fn significant<'a>(tokens: &'a [Token<'a>]) -> Vec<&'a Token<'a>> {
+	return tokens.iter().filter(|token| token.ttype != TokenType::Comment).collect();
+}
+
+// Given a list of tokens and the index of an opening bracket, returns the index of its partner
+fn find_closing(tokens: &[&Token], open: usize) -> usize {
+	let mut depth = 0;
+	for (i, token) in tokens.iter().enumerate().skip(open) {
+		if token.ttype == TokenType::Punctuator {
+			match token.value {
+				"{" | "(" | "[" => depth += 1,
+				"}" | ")" | "]" => {
+					depth -= 1;
+					if depth == 0 {
+						return i;
+					}
+				}
+				_ => {}
+			}
+		} else if token.ttype == TokenType::Template {
+			// `${` opens and `}...` closes
+			if token.value.ends_with("${") {
+				depth += 1;
+			}
+			if token.value.starts_with('}') {
+				depth -= 1;
+				if depth == 0 {
+					return i;
+				}
+			}
+		}
+	}
+	return tokens.len() - 1;
+}
+
+// Can the expression that ends with `previous` keep going onto a new line starting with `next`?
+fn continues_expression(previous: &Token, next: &Token) -> bool {
+	if previous.ttype == TokenType::Punctuator && !matches!(previous.value, ")" | "]" | "}" | "++" | "--") {
+		return true;
+	}
+	if next.ttype == TokenType::Punctuator && !matches!(next.value, "(" | "[" | "{" | "++" | "--" | "!" | "~" | "@" | "#" | ";") {
+		return true;
+	}
+	return previous.ttype == TokenType::Keyword && REGEX_KEYWORDS.contains(previous.value);
+}
+
+// Returns the index just past the end of the expression starting at `start`
+fn find_expression_end(tokens: &[&Token], start: usize) -> usize {
+	let mut depth: isize = 0;
+	let mut i = start;
+	while i < tokens.len() {
+		let token = tokens[i];
+		if i > start && depth == 0 && token.newline_before && !continues_expression(tokens[i - 1], token) {
+			return i;
+		}
+
+		if token.ttype == TokenType::Template {
+			if token.value.ends_with("${") {
+				depth += 1;
+			}
+			if token.value.starts_with('}') {
+				depth -= 1;
+			}
+		} else if token.ttype == TokenType::Punctuator {
+			match token.value {
+				"{" | "(" | "[" => depth += 1,
+				"}" | ")" | "]" => {
+					if depth == 0 {
+						return i;
+					}
+					depth -= 1;
+				}
+				";" | "," if depth == 0 => return i,
+				_ => {}
+			}
+		}
+		i += 1;
+	}
+	return tokens.len();
+}
+
+/// Pulls the top-level import statements out of a script. Returns the imports and whatever is left over.
+pub fn extract_imports(script: &str) -> (Vec<String>, String) {
+	let tokens = tokenize(script);
+	let tokens = significant(&tokens);
+	let mut imports = vec![];
+	let mut edits = vec![];
+	let mut depth = 0;
+	let mut i = 0;
+	while i < tokens.len() {
+		let token = tokens[i];
+		if token.ttype == TokenType::Punctuator {
+			match token.value {
+				"{" | "(" | "[" => depth += 1,
+				"}" | ")" | "]" => depth -= 1,
+				_ => {}
+			}
+		}
+
+		let is_static_import = depth == 0
+			&& token.is_keyword("import")
+			&& tokens.get(i + 1).is_some_and(|next| !next.is("(") && !next.is("."));
+		if is_static_import {
+			// the statement ends with the module specifier, which is always a string
+			let mut end = i + 1;
+			while end < tokens.len() && tokens[end].ttype != TokenType::String {
+				end += 1;
+			}
+			let mut end_position = tokens.get(end).map_or(script.len(), |token| token.end);
+			if let Some(next) = tokens.get(end + 1) {
+				if next.is(";") {
+					end_position = next.end;
+					end += 1;
+				}
+			}
+			imports.push(script[token.start..end_position].to_string());
+			edits.push((token.start, end_position, String::new()));
+			i = end + 1;
+			continue;
+		}
+		i += 1;
+	}
+
+	return (imports, apply_edits(script, edits));
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Declaration {
+	pub name: String,
+	pub kind: String, // let, const, var, function or class
+	pub exported: bool,
+	pub has_value: bool, // whether it starts out with a value, ex: `let a = 1` but not `let a`
+}
+
+// Collects the names bound by a (possibly destructured) declaration target
+fn collect_pattern_names(tokens: &[&Token], start: usize, end: usize, names: &mut Vec<String>) {
+	let mut i = start;
+	while i < end {
+		let token = tokens[i];
+		if token.ttype == TokenType::Identifier {
+			let next = tokens.get(i + 1);
+			let is_key = next.is_some_and(|next| next.is(":"));
+			if !is_key {
+				names.push(token.value.to_string());
+			}
+		} else if token.is("=") {
+			// skip default values
+			let value_end = find_expression_end(tokens, i + 1);
+			i = value_end;
+			continue;
+		}
+		i += 1;
+	}
+}
+
+// Walks a list of declarators, ex: the `a, { b } = c` in `let a, { b } = c;`, collecting the names
+// they bind and whether they're given a value. Returns the index of the token right after the list.
+fn collect_declarators(tokens: &[&Token], start: usize, names: &mut Vec<(String, bool)>) -> usize {
+	let mut j = start;
+	loop {
+		let target_end = match tokens.get(j) {
+			Some(target) if target.is("{") || target.is("[") => find_closing(tokens, j) + 1,
+			Some(_) => j + 1,
+			None => return j,
+		};
+		let mut pattern = vec![];
+		collect_pattern_names(tokens, j, target_end, &mut pattern);
+
+		j = target_end;
+		let has_value = tokens.get(j).is_some_and(|token| token.is("="));
+		names.extend(pattern.into_iter().map(|name| (name, has_value)));
+		if has_value {
+			j = find_expression_end(tokens, j + 1);
+		}
+		if tokens.get(j).is_some_and(|token| token.is(",")) {
+			j += 1;
+		} else {
+			return j;
+		}
+	}
+}
+
+/// Finds every top-level declaration in a script, in the order they show up.
+pub fn extract_declarations(script: &str) -> Vec<Declaration> {
+	let tokens = tokenize(script);
+	let tokens = significant(&tokens);
+	let mut declarations = vec![];
+	let mut depth = 0;
+	let mut i = 0;
+	while i < tokens.len() {
+		let token = tokens[i];
+		if token.ttype == TokenType::Punctuator {
+			match token.value {
+				"{" | "(" | "[" => depth += 1,
+				"}" | ")" | "]" => depth -= 1,
+				_ => {}
+			}
+			i += 1;
+			continue;
+		}
+
+		if depth != 0 || token.ttype != TokenType::Keyword {
+			i += 1;
+			continue;
+		}
+
+		let exported = i > 0 && tokens[i - 1].is_keyword("export");
+		match token.value {
+			"let" | "const" | "var" => {
+				let mut names = vec![];
+				i = collect_declarators(&tokens, i + 1, &mut names);
+				for (name, has_value) in names {
+					declarations.push(Declaration {
+						name,
+						kind: token.value.to_string(),
+						exported,
+						has_value,
+					});
+				}
+			}
+			"function" | "class" => {
+				let mut j = i + 1;
+				if tokens.get(j).is_some_and(|token| token.is("*")) {
+					j += 1;
+				}
+				if let Some(name) = tokens.get(j) {
+					if name.ttype == TokenType::Identifier {
+						declarations.push(Declaration {
+							name: name.value.to_string(),
+							kind: token.value.to_string(),
+							exported,
+							has_value: true,
+						});
+					}
+				}
+				i = j;
+			}
+			_ => i += 1,
+		}
+	}
+	return declarations;
+}
+
+/// Whether the script has any top-level `$:` statements, ex: `$: doubled = count * 2`
+pub fn has_reactive_statements(script: &str) -> bool {
+	let tokens = tokenize(script);
+	let tokens = significant(&tokens);
+	let mut depth = 0;
+	for (i, token) in tokens.iter().enumerate() {
+		if token.ttype == TokenType::Punctuator {
+			match token.value {
+				"{" | "(" | "[" => depth += 1,
+				"}" | ")" | "]" => depth -= 1,
+				_ => {}
+			}
+			continue;
+		}
+
+		// a label has to start a statement, unlike the `$` in `a ? $ : b`
+		let starts_statement = i == 0 || token.newline_before || tokens[i - 1].is(";") || tokens[i - 1].is("}");
+		if depth == 0 && starts_statement && token.value == "$" && tokens.get(i + 1).is_some_and(|next| next.is(":")) {
+			return true;
+		}
+	}
+	return false;
+}
+
+/// Returns the names of every `export let`/`export var` in the script. These are our props.
+pub fn extract_exports(script: &str) -> Vec<String> {
+	return extract_declarations(script).into_iter()
+		.filter(|declaration| declaration.exported && (declaration.kind == "let" || declaration.kind == "var"))
+		.map(|declaration| declaration.name)
+		.collect();
+}
+
+/// Since our script is going to live in a function we can't export anything. Instead props,
+/// ex: `export let a = 1`, are pulled out of `props` with `let { a = 1 } = props`,
+/// and everything else just loses the `export` keyword.
+pub fn rewrite_exports(script: &str, props: &str) -> String {
+	let tokens = tokenize(script);
+	let tokens = significant(&tokens);
+	let mut edits = vec![];
+	let mut depth = 0;
+	for (i, token) in tokens.iter().enumerate() {
+		if token.ttype == TokenType::Punctuator {
+			match token.value {
+				"{" | "(" | "[" => depth += 1,
+				"}" | ")" | "]" => depth -= 1,
+				_ => {}
+			}
+		}
+		if depth != 0 || !token.is_keyword("export") {
+			continue;
+		}
+
+		let declaration = match tokens.get(i + 1) {
+			Some(declaration) => declaration,
+			None => continue,
+		};
+		match declaration.value {
+			"let" | "var" => {
+				let end = collect_declarators(&tokens, i + 2, &mut vec![]);
+				let end_position = tokens[end - 1].end;
+				edits.push((token.start, tokens[i + 2].start, format!("{} {{ ", declaration.value)));
+				edits.push((end_position, end_position, format!(" }} = {}", props)));
+			}
+			"const" | "function" | "class" => {
+				edits.push((token.start, declaration.start, String::new()));
+			}
+			_ => {}
+		}
+	}
+	return apply_edits(script, edits);
+}
+
+// Whether whatever's at this token starts a new expression, rather than continuing one, ex: `[` after `;`
+// starts an array (or a pattern), but after `a` it's a member access
+fn previous_starts_expression(tokens: &[&Token], i: usize) -> bool {
+	if i == 0 {
+		return true;
+	}
+	let previous = tokens[i - 1];
+	return match previous.ttype {
+		TokenType::Punctuator if previous.is("}") => tokens[i].newline_before, // after a block, unless it was an object
+		TokenType::Punctuator => !matches!(previous.value, ")" | "]" | "++" | "--"),
+		TokenType::Keyword => REGEX_KEYWORDS.contains(previous.value),
+		_ => false,
+	};
+}
+
+// Are we looking at a declaration's binding, ex: the `x` in `let x = 5`?
+fn is_declaration_target(tokens: &[&Token], i: usize, declaration_depths: &[isize], depth: isize) -> bool {
+	if i == 0 {
+		return false;
+	}
+	let previous = tokens[i - 1];
+	if previous.is_keyword("let") || previous.is_keyword("const") || previous.is_keyword("var") {
+		return true;
+	}
+	return previous.is(",") && declaration_depths.last() == Some(&depth);
+}
+
+// A function or block nested in a script, along with the names declared in it
+struct Scope {
+	start: usize, // the token range it covers, including a function's parameters
+	end: usize,
+	params_end: usize, // just past a function's parameters, or `start` for anything else
+	is_function: bool, // whether `var`s declared inside stay inside
+	names: HashSet<String>,
+}
+
+// Returns the end of an arrow function's body, ex: the `}` in `() => { ... }`, or the last token of `x => x + 1`
+fn find_arrow_body_end(tokens: &[&Token], body: usize) -> usize {
+	if tokens.get(body).is_some_and(|token| token.is("{")) {
+		return find_closing(tokens, body);
+	}
+	return find_expression_end(tokens, body).max(body + 1) - 1;
+}
+
+// Finds every function and block in a script, along with what they declare.
+// Top-level declarations are the component's own, so those aren't collected.
+fn collect_scopes(tokens: &[&Token]) -> Vec<Scope> {
+	let mut scopes: Vec<Scope> = vec![];
+	let mut open: Vec<usize> = vec![]; // the scopes we're currently in, innermost last
+	let mut function_bodies: HashSet<usize> = HashSet::new(); // braces that belong to a function's scope
+	for i in 0..tokens.len() {
+		while open.last().is_some_and(|scope| scopes[*scope].end < i) {
+			open.pop();
+		}
+		let token = tokens[i];
+		let previous = if i > 0 { Some(tokens[i - 1]) } else { None };
+
+		// functions, ex: `x => ...`, `(a, b) => ...`, `function f(a) {`, `method(a) {` and `catch (e) {`
+		let mut function = None;
+		if token.ttype == TokenType::Identifier && tokens.get(i + 1).is_some_and(|next| next.is("=>")) {
+			function = Some((i + 1, find_arrow_body_end(tokens, i + 2), i + 2, true));
+		} else if token.is("(") {
+			let close = find_closing(tokens, i);
+			let next = tokens.get(close + 1);
+			let is_declared = previous.is_some_and(|previous| previous.ttype == TokenType::Identifier || previous.is_keyword("function"));
+			if next.is_some_and(|next| next.is("=>")) {
+				function = Some((close + 1, find_arrow_body_end(tokens, close + 2), close + 2, true));
+			} else if next.is_some_and(|next| next.is("{")) && (is_declared || previous.is_some_and(|previous| previous.is_keyword("catch"))) {
+				let is_function = !previous.is_some_and(|previous| previous.is_keyword("catch"));
+				function = Some((close + 1, find_closing(tokens, close + 1), close + 1, is_function));
+			}
+		}
+		if let Some((params_end, end, body, is_function)) = function {
+			let mut names = vec![];
+			collect_pattern_names(tokens, i, params_end, &mut names);
+			function_bodies.insert(body);
+			scopes.push(Scope { start: i, end, params_end, is_function, names: names.into_iter().collect() });
+			open.push(scopes.len() - 1);
+			continue;
+		}
+
+		// for loops, whose `let`s belong to the loop, ex: for (let i = 0; ...) {
+		if token.is_keyword("for") {
+			let paren = if tokens.get(i + 1).is_some_and(|next| next.is_keyword("await")) { i + 2 } else { i + 1 };
+			if tokens.get(paren).is_some_and(|next| next.is("(")) {
+				let close = find_closing(tokens, paren);
+				let end = match tokens.get(close + 1) {
+					Some(body) if body.is("{") => find_closing(tokens, close + 1),
+					_ => find_expression_end(tokens, close + 1),
+				};
+				scopes.push(Scope { start: paren, end, params_end: paren, is_function: false, names: HashSet::new() });
+				open.push(scopes.len() - 1);
+			}
+			continue;
+		}
+
+		if token.is("{") && !function_bodies.contains(&i) {
+			let end = find_closing(tokens, i);
+			scopes.push(Scope { start: i, end, params_end: i, is_function: false, names: HashSet::new() });
+			open.push(scopes.len() - 1);
+			continue;
+		}
+
+		// declarations, ex: `let a, { b } = c`, `var d`, `function e() {}` or `class F {}`
+		let mut names = vec![];
+		let mut is_var = false;
+		if token.is_keyword("let") || token.is_keyword("const") || token.is_keyword("var") {
+			let mut declarators = vec![];
+			collect_declarators(tokens, i + 1, &mut declarators);
+			names.extend(declarators.into_iter().map(|(name, _)| name));
+			is_var = token.is_keyword("var");
+		} else if token.is_keyword("function") || token.is_keyword("class") {
+			// only declarations, not expressions like `const f = function g() {}`
+			let is_statement = previous.is_none_or(|previous| previous.is(";") || previous.is("{") || previous.is("}") || previous.is_keyword("export"));
+			let name = tokens.get(i + 1).filter(|name| name.ttype == TokenType::Identifier);
+			if let (true, Some(name)) = (is_statement, name) {
+				names.push(name.value.to_string());
+			}
+		}
+		let scope = if is_var {
+			open.iter().rev().find(|scope| scopes[**scope].is_function)
+		} else {
+			open.last()
+		};
+		if let Some(scope) = scope.copied() {
+			scopes[scope].names.extend(names);
+		}
+	}
+	return scopes;
+}
+
+// Whether the name at this token means something declared in a function or block, rather than our variable
+fn is_shadowed(scopes: &[Scope], i: usize, name: &str) -> bool {
+	return scopes.iter().any(|scope| scope.start <= i && i <= scope.end && scope.names.contains(name));
+}
+
+/// Wraps every assignment to one of the given variables in `$$invalidate`, so the component
+/// knows to update itself. Ex: `count += 1` becomes `$$invalidate(0, count += 1)`.
+/// Locals that shadow our variables are left alone, ex: the `count` in `function f(count) { count = 5 }`.
+pub fn invalidate_assignments(script: &str, invalidate: &str, variables: &HashMap<String, usize>) -> String {
+	let tokens = tokenize(script);
+	let tokens = significant(&tokens);
+	let scopes = collect_scopes(&tokens);
+	let mut edits = vec![];
+	let mut depth: isize = 0;
+	// the depths at which we're currently in the middle of a `let a = 1, b = 2` style list
+	let mut declaration_depths: Vec<isize> = vec![];
+	// where the destructuring assignment we're in the middle of ends, ex: the `]` in `[a, b] = [b, a]`
+	let mut pattern_end = None;
+
+	for i in 0..tokens.len() {
+		let token = tokens[i];
+		if pattern_end.is_some_and(|end| i > end) {
+			pattern_end = None;
+		}
+
+		// destructuring assignments invalidate everything they assign, ex: `[a, b] = [b, a]`
+		// becomes `([a, b] = [b, a], $$invalidate(0, a), $$invalidate(1, b))`
+		let is_pattern_start = (token.is("[") || token.is("{"))
+			&& pattern_end.is_none()
+			&& previous_starts_expression(&tokens, i)
+			&& !is_declaration_target(&tokens, i, &declaration_depths, depth)
+			&& !scopes.iter().any(|scope| scope.start <= i && i < scope.params_end);
+		if is_pattern_start {
+			let close = find_closing(&tokens, i);
+			if tokens.get(close + 1).is_some_and(|next| next.is("=")) {
+				let mut names = vec![];
+				collect_pattern_names(&tokens, i, close + 1, &mut names);
+				let invalidations = names.iter()
+					.filter(|name| !is_shadowed(&scopes, i, name))
+					.filter_map(|name| variables.get(name).map(|index| format!(", {}({}, {})", invalidate, index, name)))
+					.collect::<String>();
+				if invalidations.len() > 0 {
+					let end = find_expression_end(&tokens, close + 2);
+					let end_position = tokens[end - 1].end;
+					edits.push((token.start, token.start, "(".to_string()));
+					edits.push((end_position, end_position, format!("{})", invalidations)));
+				}
+				pattern_end = Some(close);
+			}
+		}
+
+		if token.ttype == TokenType::Punctuator {
+			match token.value {
+				"{" | "(" | "[" => depth += 1,
+				"}" | ")" | "]" => {
+					depth -= 1;
+					while declaration_depths.last().is_some_and(|d| *d > depth) {
+						declaration_depths.pop();
+					}
+				}
+				";" if declaration_depths.last() == Some(&depth) => {
+					declaration_depths.pop();
+				}
+				_ => {}
+			}
+			continue;
+		}
+
+		if token.is_keyword("let") || token.is_keyword("const") || token.is_keyword("var") {
+			declaration_depths.push(depth);
+			continue;
+		}
+
+		if token.ttype != TokenType::Identifier {
+			continue;
+		}
+		if i > 0 && (tokens[i - 1].is(".") || tokens[i - 1].is("?.")) {
+			continue;
+		}
+		let index = match variables.get(token.value) {
+			Some(index) => *index,
+			None => continue,
+		};
+		if pattern_end.is_some() || is_shadowed(&scopes, i, token.value) || is_declaration_target(&tokens, i, &declaration_depths, depth) {
+			continue;
+		}
+
+		// prefix update, ex: ++count
+		if i > 0 && (tokens[i - 1].is("++") || tokens[i - 1].is("--")) {
+			edits.push((tokens[i - 1].start, tokens[i - 1].start, format!("{}({}, ", invalidate, index)));
+			edits.push((token.end, token.end, ")".to_string()));
+			continue;
+		}
+
+		// skip over any member accesses, ex: the `.a[0]` in `x.a[0] = 5`
+		let mut j = i + 1;
+		let mut is_member = false;
+		loop {
+			match tokens.get(j) {
+				Some(next) if next.is(".") && tokens.get(j + 1).is_some_and(|t| t.ttype != TokenType::Punctuator) => {
+					j += 2;
+					is_member = true;
+				}
+				Some(next) if next.is("[") && !tokens[j].newline_before => {
+					j = find_closing(&tokens, j) + 1;
+					is_member = true;
+				}
+				_ => break,
+			}
+		}
+
+		let next = match tokens.get(j) {
+			Some(next) => next,
+			None => continue,
+		};
+		if next.ttype == TokenType::Punctuator && ASSIGNMENT_OPERATORS.contains(next.value) {
+			let end = find_expression_end(&tokens, j + 1);
+			let end_position = tokens[end - 1].end;
+			edits.push((token.start, token.start, format!("{}({}, ", invalidate, index)));
+			if is_member {
+				edits.push((end_position, end_position, format!(", {})", token.value)));
+			} else {
+				edits.push((end_position, end_position, ")".to_string()));
+			}
+		} else if (next.is("++") || next.is("--")) && !next.newline_before {
+			edits.push((token.start, token.start, format!("{}({}, ", invalidate, index)));
+			edits.push((next.end, next.end, format!(", {})", token.value)));
+		}
+	}
+
+	return apply_edits(script, edits);
+}
+
+/// Calls `replace` on every free identifier in the expression, swapping in whatever it returns.
+/// Property names, object keys and arrow function parameters are left alone.
+pub fn rewrite_identifiers<F: FnMut(&str) -> Option<String>>(expression: &str, mut replace: F) -> String {
+	let tokens = tokenize(expression);
+	let tokens = significant(&tokens);
+
+	// collect arrow function parameters, since those shadow anything we'd replace
+	let mut locals: HashSet<&str> = HashSet::new();
+	for (i, token) in tokens.iter().enumerate() {
+		if !token.is("=>") || i == 0 {
+			continue;
+		}
+		let previous = tokens[i - 1];
+		if previous.ttype == TokenType::Identifier {
+			locals.insert(previous.value);
+		} else if previous.is(")") {
+			let mut depth = 0;
+			let mut j = i - 1;
+			loop {
+				let param = tokens[j];
+				if param.is(")") || param.is("]") || param.is("}") {
+					depth += 1;
+				} else if param.is("(") || param.is("[") || param.is("{") {
+					depth -= 1;
+					if depth == 0 {
+						break;
+					}
+				} else if param.ttype == TokenType::Identifier && !tokens[j + 1].is(":") && !tokens[j - 1].is("=") {
+					locals.insert(param.value);
+				}
+				if j == 0 {
+					break;
+				}
+				j -= 1;
+			}
+		}
+	}
+
+	// true for braces that are object literals, rather than function bodies
+	let mut brace_stack: Vec<bool> = vec![];
+	let mut edits = vec![];
+	for (i, token) in tokens.iter().enumerate() {
+		let previous = if i > 0 { Some(tokens[i - 1]) } else { None };
+		if token.is("{") {
+			brace_stack.push(!previous.is_some_and(|previous| previous.is("=>") || previous.is(")")));
+			continue;
+		} else if token.is("}") {
+			brace_stack.pop();
+			continue;
+		}
+
+		if token.ttype != TokenType::Identifier || locals.contains(token.value) {
+			continue;
+		}
+		if previous.is_some_and(|previous| previous.is(".") || previous.is("?.")) {
+			continue;
+		}
+
+		let next = tokens.get(i + 1);
+		let in_object = brace_stack.last() == Some(&true)
+			&& previous.is_some_and(|previous| previous.is("{") || previous.is(","));
+		if in_object && next.is_some_and(|next| next.is(":") || next.is("(")) {
+			continue; // key or method name
+		}
+
+		if let Some(replacement) = replace(token.value) {
+			if in_object && next.is_some_and(|next| next.is(",") || next.is("}")) {
+				// shorthand property, ex: { a }
+				edits.push((token.start, token.end, format!("{}: {}", token.value, replacement)));
+			} else {
+				edits.push((token.start, token.end, replacement));
+			}
+		}
+	}
+
+	return apply_edits(expression, edits);
+}
@@ -1,3 +1,7 @@
+use crate::compiler::compiler::EachBlock;
+use crate::compiler::utils::script;
+use std::ops::Range;
+
 
 pub fn indent_block(block: &str, indent_level: usize) -> String {
 	let mut result = String::new();
@@ -15,27 +19,25 @@ pub fn indent_block(block: &str, indent_level: usize) -> String {
 	return result;
 }
 
-pub fn is_bind(statement: &str) -> bool {
-	return statement.starts_with("bind:");
+pub fn contains_handlebars(block: &str) -> bool {
+	return find_mustaches(block).len() > 0;
 }
 
-pub fn contains_handlebars(block: &str) -> bool {
-	// TODO: how should we do this, for real?
-	// TODO: handle things like quoted strings and template literals, etc.
-	let mut faux_stack: isize = 0; // counts the opening/closing braces
-	let mut has_braces = false;
-	for c in block.chars() {
-		if c == '{' {
-			has_braces = true;
-			faux_stack += 1;
-		} else if c == '}' {
-			faux_stack -= 1;
-			if faux_stack < 0 {
-				panic!("Improperly formed template: {}", block);
+// Where each {mustache} is, braces included. A `{` that's never closed is just text.
+pub fn find_mustaches(block: &str) -> Vec<Range<usize>> {
+	let mut spans = vec![];
+	let mut index = 0;
+	while let Some(offset) = block[index..].find('{') {
+		let start = index + offset;
+		match script::find_mustache_end(block, start) {
+			Some(end) => {
+				spans.push(start..end);
+				index = end;
 			}
+			None => break,
 		}
 	}
-	return has_braces;
+	return spans;
 }
 
 #[derive(Clone, Debug)]
@@ -52,10 +54,17 @@ pub enum HandlebarType {
 
 impl HandlebarPiece {
 
-	pub fn new(ttype: HandlebarType) -> Self {
+	pub fn text(value: &str) -> Self {
+		Self {
+			ttype: HandlebarType::Text,
+			value: value.to_string(),
+		}
+	}
+
+	pub fn code(value: &str) -> Self {
 		Self {
-			ttype,
-			value: String::new()
+			ttype: HandlebarType::Code,
+			value: value.to_string(),
 		}
 	}
 
@@ -67,45 +76,301 @@ impl HandlebarPiece {
 }
 
 pub fn split_handlebars_into_pieces(block: &str) -> Vec<HandlebarPiece> {
-	let mut faux_stack: isize = 0; // counts the opening/closing braces
-	// TODO: how should we do this, for real?
 	let mut pieces: Vec<HandlebarPiece> = vec![];
-	let mut piece = HandlebarPiece::new(HandlebarType::Text);
-
-	for c in block.chars() {
-		if c == '{' {
-			faux_stack += 1;
-			if faux_stack == 1 {
-				piece.add_to_vec(&mut pieces);
-				piece = HandlebarPiece::new(HandlebarType::Code);
-			} else {
-				// nested code block, probably
-				piece.value.push(c);
+	let mut index = 0;
+	for span in find_mustaches(block) {
+		HandlebarPiece::text(&block[index..span.start]).add_to_vec(&mut pieces);
+		HandlebarPiece::code(&block[span.start + 1..span.end - 1]).add_to_vec(&mut pieces);
+		index = span.end;
+	}
+	HandlebarPiece::text(&block[index..]).add_to_vec(&mut pieces);
+	return pieces;
+}
+
+// Splits a block tag like `#if x > 5` into its keyword and expression, ex: ("#if", "x > 5")
+pub fn split_block_tag(tag: &str) -> (&str, &str) {
+	let tag = tag.trim();
+	match tag.find(char::is_whitespace) {
+		Some(index) => (&tag[..index], tag[index..].trim()),
+		None => (tag, ""),
+	}
+}
+
+// Returns the index of the last top-level occurrence of `needle`, ignoring anything nested in brackets
+fn rfind_top_level(block: &str, needle: char) -> Option<usize> {
+	let mut depth: isize = 0;
+	for (i, c) in block.char_indices().rev() {
+		match c {
+			')' | ']' | '}' => depth += 1,
+			'(' | '[' | '{' => depth -= 1,
+			_ => {}
+		}
+		if c == needle && depth == 0 {
+			return Some(i);
+		}
+	}
+	return None;
+}
+
+// Parses the inside of an each block, ex: `items as item, i (item.id)`
+pub fn parse_each_expression(expression: &str) -> Option<(String, EachBlock)> {
+	let split = expression.rfind(" as ")?;
+	let list = expression[..split].trim();
+	let mut rest = expression[split + 4..].trim();
+
+	let mut key = None;
+	if rest.ends_with(')') {
+		let mut depth = 0;
+		for (i, c) in rest.char_indices().rev() {
+			match c {
+				')' => depth += 1,
+				'(' => {
+					depth -= 1;
+					if depth == 0 {
+						key = Some(rest[i + 1..rest.len() - 1].trim().to_string());
+						rest = rest[..i].trim();
+						break;
+					}
+				}
+				_ => {}
 			}
-		} else if c == '}' {
-			faux_stack -= 1;
-			match faux_stack.partial_cmp(&0).expect("NaN") {
-				std::cmp::Ordering::Greater => {
-					piece.value.push(c);
-				},
-				std::cmp::Ordering::Equal => {
-					piece.add_to_vec(&mut pieces);
-					piece = HandlebarPiece::new(HandlebarType::Text);
-				},
-				std::cmp::Ordering::Less => panic!("this shouldn't happen"),
+		}
+	}
+
+	let (context, index) = match rfind_top_level(rest, ',') {
+		Some(comma) => (rest[..comma].trim(), Some(rest[comma + 1..].trim().to_string())),
+		None => (rest, None),
+	};
+	if list.len() == 0 || context.len() == 0 {
+		return None;
+	}
+
+	return Some((list.to_string(), EachBlock {
+		context: context.to_string(),
+		index,
+		key,
+	}));
+}
+
+// Splits a directive like `transition:fade|local` into its type, name and modifiers.
+// Returns None for plain attributes.
+pub fn parse_directive(key: &str) -> Option<(&str, &str, Vec<String>)> {
+	let (prefix, rest) = key.split_once(':')?;
+	if !matches!(prefix, "transition" | "in" | "out" | "animate" | "let" | "on" | "bind") {
+		return None;
+	}
+
+	let mut pieces = rest.split('|');
+	let name = pieces.next().unwrap();
+	let modifiers = pieces.map(|modifier| modifier.to_string()).collect();
+	return Some((prefix, name, modifiers));
+}
+
+// Returns the expression inside of a value that's just a single mustache, ex: `{x}`
+pub fn strip_mustache(value: &str) -> Option<&str> {
+	let value = value.trim();
+	if !value.starts_with('{') || !value.ends_with('}') {
+		return None;
+	}
+	let pieces = split_handlebars_into_pieces(value);
+	if pieces.len() != 1 || pieces[0].ttype != HandlebarType::Code {
+		return None;
+	}
+	return Some(value[1..value.len() - 1].trim());
+}
+
+// Splits `x = y` into its left and right sides, ignoring defaults inside patterns
+// (ex: `{ a = 1 } = b`) and comparisons (ex: `x = a == b`)
+pub fn split_assignment(assignment: &str) -> Option<(&str, &str)> {
+	let bytes = assignment.as_bytes();
+	let mut depth: isize = 0;
+	for (i, c) in assignment.char_indices() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' => depth -= 1,
+			'=' if depth == 0 => {
+				let previous = if i > 0 { bytes[i - 1] } else { b' ' };
+				let next = bytes.get(i + 1).cloned().unwrap_or(b' ');
+				if !b"=!<>".contains(&previous) && next != b'=' && next != b'>' {
+					return Some((assignment[..i].trim(), assignment[i + 1..].trim()));
+				}
 			}
-		} else {
-			piece.value.push(c);
+			_ => {}
 		}
 	}
+	return None;
+}
 
-	match piece.ttype {
-		HandlebarType::Code => {
-			panic!("Handlebar parsing error");
+// Splits on commas that aren't nested inside brackets
+fn split_top_level(block: &str) -> Vec<&str> {
+	let mut pieces = vec![];
+	let mut depth: isize = 0;
+	let mut start = 0;
+	for (i, c) in block.char_indices() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' => depth -= 1,
+			',' if depth == 0 => {
+				pieces.push(block[start..i].trim());
+				start = i + 1;
+			}
+			_ => {}
 		}
-		HandlebarType::Text => {
-			piece.add_to_vec(&mut pieces);
+	}
+	pieces.push(block[start..].trim());
+	return pieces.into_iter().filter(|piece| piece.len() > 0).collect();
+}
+
+// Turns the context of an each block into the names it binds and how to reach each one
+// from the current item, ex: `{ id, name: title }` gives [("id", ".id"), ("title", ".name")]
+pub fn parse_each_context(context: &str) -> Vec<(String, String)> {
+	let context = context.trim();
+	let mut bindings = vec![];
+	if context.starts_with('{') && context.ends_with('}') {
+		for property in split_top_level(&context[1..context.len() - 1]) {
+			match property.split_once(':') {
+				Some((key, pattern)) => {
+					for (name, accessor) in parse_each_context(pattern) {
+						bindings.push((name, format!(".{}{}", key.trim(), accessor)));
+					}
+				}
+				None => {
+					let name = property.split('=').next().unwrap().trim();
+					bindings.push((name.to_string(), format!(".{}", name)));
+				}
+			}
+		}
+	} else if context.starts_with('[') && context.ends_with(']') {
+		for (i, element) in split_top_level(&context[1..context.len() - 1]).into_iter().enumerate() {
+			for (name, accessor) in parse_each_context(element) {
+				bindings.push((name, format!("[{}]{}", i, accessor)));
+			}
 		}
+	} else {
+		let name = context.split('=').next().unwrap().trim();
+		bindings.push((name.to_string(), String::new()));
 	}
-	return pieces;
-}
\ No newline at end of file
+	return bindings;
+}
+
+// Only these count as whitespace in html, so &nbsp; and friends stick around
+pub fn is_html_whitespace(c: char) -> bool {
+	return c == ' ' || c == '\t' || c == '\n' || c == '\r' || c == '\u{c}';
+}
+
+// Turns each run of whitespace into a single space, ex: `a \n\t b` becomes `a b`
+pub fn collapse_whitespace(text: &str) -> String {
+	let mut result = String::with_capacity(text.len());
+	for c in text.chars() {
+		if !is_html_whitespace(c) {
+			result.push(c);
+		} else if !result.ends_with(' ') {
+			result.push(' ');
+		}
+	}
+	return result;
+}
+
+// The warnings a comment silences, ex: `svelte-ignore a11y-autofocus a11y-missing-attribute`
+pub fn parse_svelte_ignore(comment: &str) -> Vec<String> {
+	return match comment.trim_start().strip_prefix("svelte-ignore") {
+		Some(codes) if codes.starts_with(is_html_whitespace) => codes
+			.split(|c: char| is_html_whitespace(c) || c == ',')
+			.filter(|code| code.len() > 0)
+			.map(|code| code.to_string())
+			.collect(),
+		_ => vec![],
+	};
+}
+
+// Same as svelte's hash, so both renderers come up with the same ids, ex: `1x7wnb3`
+pub fn hash(value: &str) -> String {
+	let mut hash: i32 = 5381;
+	for unit in value.replace('\r', "").encode_utf16().collect::<Vec<u16>>().iter().rev() {
+		hash = (hash << 5).wrapping_sub(hash) ^ *unit as i32;
+	}
+
+	// base 36, like js's toString(36)
+	let mut hash = hash as u32;
+	let mut digits = vec![];
+	loop {
+		digits.push(std::char::from_digit(hash % 36, 36).unwrap());
+		hash /= 36;
+		if hash == 0 {
+			break;
+		}
+	}
+	return digits.iter().rev().collect();
+}
+
+// The (zero-based) line and column of a byte offset, counted in utf-16 like js would, ex: for add_location
+pub fn locate(source: &str, index: usize) -> (usize, usize) {
+	let before = &source[..index];
+	let line = before.matches('\n').count();
+	let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+	return (line, before[line_start..].encode_utf16().count());
+}
+
+// Makes a name safe to use as (part of) a js identifier, ex: `my-slot` becomes `my_slot`
+pub fn to_identifier(name: &str) -> String {
+	return name.chars()
+		.map(|c| if c.is_alphanumeric() || c == '_' || c == '$' { c } else { '_' })
+		.collect();
+}
+
+pub fn is_identifier(name: &str) -> bool {
+	let mut chars = name.chars();
+	return chars.next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+		&& chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+}
+
+// Turns any text into a double-quoted js string literal, ex: `say "hi"` becomes `"say \"hi\""`
+pub fn to_js_string(value: &str) -> String {
+	let mut result = String::with_capacity(value.len() + 2);
+	result.push('"');
+	for c in value.chars() {
+		match c {
+			'"' => result.push_str("\\\""),
+			'\\' => result.push_str("\\\\"),
+			'\n' => result.push_str("\\n"),
+			'\r' => result.push_str("\\r"),
+			'\t' => result.push_str("\\t"),
+			// these are valid in js strings these days, but not in older engines
+			'\u{2028}' | '\u{2029}' => result.push_str(&format!("\\u{:04x}", c as u32)),
+			c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+			c => result.push(c),
+		}
+	}
+	result.push('"');
+	return result;
+}
+
+// Escapes text so it can sit inside a js template literal, ex: `cost: ${price}` keeps its `${`.
+// Line breaks and tabs get escaped too, otherwise re-indenting the code around them would change them.
+pub fn to_template_literal_text(value: &str) -> String {
+	return value
+		.replace('\\', "\\\\")
+		.replace('`', "\\`")
+		.replace("${", "\\${")
+		.replace('\n', "\\n")
+		.replace('\r', "\\r")
+		.replace('\t', "\\t")
+		.replace('\u{2028}', "\\u2028")
+		.replace('\u{2029}', "\\u2029");
+}
+
+// Object keys only need quotes if they aren't valid identifiers, ex: `header` vs `"my-slot"`
+pub fn to_property_key(name: &str) -> String {
+	if is_identifier(name) {
+		return name.to_string();
+	}
+	return to_js_string(name);
+}
+
+// ex: `.header` or `["my-slot"]`
+pub fn to_property_access(name: &str) -> String {
+	if is_identifier(name) {
+		return format!(".{}", name);
+	}
+	return format!("[{}]", to_js_string(name));
+}
@@ -20,7 +20,7 @@ use wasm_bindgen::prelude::*;
 use log::{LevelFilter, Record, Metadata};
 use std::sync::Once;
 mod compiler;
-use compiler::compiler::compile;
+use compiler::compiler::{compile, compile_with_options};
 
 static INIT: Once = Once::new();
 
@@ -73,6 +73,20 @@ pub extern "C" fn compile_string(s: *const c_char) -> *mut c_char {
 	return CString::new(result).unwrap().into_raw();
 }
 
+#[cfg(not(feature = "wasm"))]
+#[no_mangle]
+pub extern "C" fn compile_string_with_options(s: *const c_char, options: *const c_char) -> *mut c_char {
+	setup_logging_wrapper();
+    let (c_str, c_options) = unsafe {
+        assert!(!s.is_null() && !options.is_null());
+        (CStr::from_ptr(s), CStr::from_ptr(options))
+    };
+
+    let r_str = c_str.to_str().unwrap();
+	let result = compile_with_options(&r_str, c_options.to_str().unwrap());
+	return CString::new(result).unwrap().into_raw();
+}
+
 #[cfg(not(feature = "wasm"))]
 #[no_mangle]
 pub extern "C" fn compile_file(path: *const c_char) -> *mut c_char {
@@ -98,6 +112,14 @@ pub fn wasm_compile_file(contents: &str) -> String {
 	return result;
 }
 
+#[wasm_bindgen]
+#[cfg(feature = "wasm")]
+pub fn wasm_compile_file_with_options(contents: &str, options: &str) -> String {
+	setup_logging_wrapper();
+	let result = compile_with_options(&contents, &options);
+	return result;
+}
+
 #[cfg(not(feature = "wasm"))]
 struct NativeLogger;
 #[cfg(not(feature = "wasm"))]